@@ -0,0 +1,168 @@
+use crate::{asm::Operand, chip8::instruction::Chip8Instruction};
+use twelve_bit::u12::*;
+
+/// Encodes one instruction given its resolved operands.
+///
+/// Mnemonics follow Cowgod's Chip-8 technical reference, plus the SUPER-CHIP
+/// and XO-CHIP extensions. Instructions the interpreter executes are built as
+/// a [`Chip8Instruction`] and encoded with [`Chip8Instruction::encode`].
+pub fn encode(mnemonic: &str, operands: &[Operand]) -> Result<Vec<u8>, String> {
+    use Operand::*;
+
+    let instruction = |instruction: Chip8Instruction| Ok(instruction.encode());
+    let x_nn = |opcode: u16, x: u8, nn: i64| Ok(opcode | (x as u16) << 8 | byte(nn)? as u16);
+    let x_y = |opcode: u16, x: u8, y: u8| Ok(opcode | (x as u16) << 8 | (y as u16) << 4);
+    let x = |opcode: u16, x: u8| Ok(opcode | (x as u16) << 8);
+
+    let opcode = match (mnemonic, operands) {
+        ("CLS", []) => instruction(Chip8Instruction::ClearScreen()),
+        ("RET", []) => instruction(Chip8Instruction::Return()),
+        ("SYS", [Value(nnn)]) => Ok(addr(*nnn)?),
+        ("JP", [Value(nnn)]) => instruction(Chip8Instruction::Jump(u12![addr(*nnn)?])),
+        ("JP", [V(0), Value(nnn)]) => Ok(0xb000 | addr(*nnn)?),
+        ("CALL", [Value(nnn)]) => instruction(Chip8Instruction::Call(u12![addr(*nnn)?])),
+        ("SE", [V(x), Value(nn)]) => instruction(Chip8Instruction::SkipIfEqual(*x, byte(*nn)?)),
+        ("SE", [V(x), V(y)]) => instruction(Chip8Instruction::SkipIfEqualXY(*x, *y)),
+        ("SNE", [V(x), Value(nn)]) => instruction(Chip8Instruction::SkipIfNotEqual(*x, byte(*nn)?)),
+        ("SNE", [V(x), V(y)]) => instruction(Chip8Instruction::SkipIfNotEqualXY(*x, *y)),
+        ("LD", [V(x), Value(nn)]) => instruction(Chip8Instruction::SetVX(*x, byte(*nn)?)),
+        ("LD", [V(x), V(y)]) => instruction(Chip8Instruction::SetVXToVY(*x, *y)),
+        ("LD", [I, Value(nnn)]) => instruction(Chip8Instruction::SetIRegister(addr(*nnn)?)),
+        ("LD", [V(vx), Dt]) => x(0xf007, *vx),
+        ("LD", [V(vx), K]) => x(0xf00a, *vx),
+        ("LD", [Dt, V(vx)]) => x(0xf015, *vx),
+        ("LD", [St, V(x)]) => instruction(Chip8Instruction::SetSoundTimer(*x)),
+        ("LD", [F, V(vx)]) => x(0xf029, *vx),
+        ("LD", [B, V(x)]) => instruction(Chip8Instruction::StoreBCD(*x)),
+        ("LD", [IndirectI, V(x)]) => instruction(Chip8Instruction::StoreRegisters(*x)),
+        ("LD", [V(vx), IndirectI]) => x(0xf065, *vx),
+        ("ADD", [V(x), Value(nn)]) => instruction(Chip8Instruction::AddVX(*x, byte(*nn)?)),
+        ("ADD", [V(x), V(y)]) => instruction(Chip8Instruction::AddVYRegisterToVX(*x, *y)),
+        ("ADD", [I, V(vx)]) => x(0xf01e, *vx),
+        ("OR", [V(x), V(y)]) => instruction(Chip8Instruction::OrVXVY(*x, *y)),
+        ("AND", [V(x), V(y)]) => instruction(Chip8Instruction::AndVXVY(*x, *y)),
+        ("XOR", [V(x), V(y)]) => instruction(Chip8Instruction::XorVXVY(*x, *y)),
+        ("SUB", [V(x), V(y)]) => instruction(Chip8Instruction::SubVYFromVX(*x, *y)),
+        ("SUBN", [V(x), V(y)]) => instruction(Chip8Instruction::SubVXFromVY(*x, *y)),
+        // Vy defaults to Vx, which shifts Vx under either shift quirk
+        ("SHR", [V(x)]) => instruction(Chip8Instruction::ShiftVXRight(*x, *x)),
+        ("SHR", [V(x), V(y)]) => instruction(Chip8Instruction::ShiftVXRight(*x, *y)),
+        ("SHL", [V(x)]) => instruction(Chip8Instruction::ShiftVXLeft(*x, *x)),
+        ("SHL", [V(x), V(y)]) => instruction(Chip8Instruction::ShiftVXLeft(*x, *y)),
+        ("RND", [V(vx), Value(nn)]) => x_nn(0xc000, *vx, *nn),
+        ("DRW", [V(x), V(y), Value(n)]) => instruction(Chip8Instruction::Draw(*x, *y, nibble(*n)?)),
+        ("SKP", [V(vx)]) => x(0xe09e, *vx),
+        ("SKNP", [V(vx)]) => x(0xe0a1, *vx),
+
+        // SUPER-CHIP
+        ("SCD", [Value(n)]) => Ok(0x00c0 | nibble(*n)? as u16),
+        ("SCR", []) => Ok(0x00fb),
+        ("SCL", []) => Ok(0x00fc),
+        ("EXIT", []) => Ok(0x00fd),
+        ("LOW", []) => Ok(0x00fe),
+        ("HIGH", []) => Ok(0x00ff),
+        ("LD", [Hf, V(vx)]) => x(0xf030, *vx),
+        ("LD", [R, V(vx)]) => x(0xf075, *vx),
+        ("LD", [V(vx), R]) => x(0xf085, *vx),
+
+        // XO-CHIP
+        ("SCU", [Value(n)]) => Ok(0x00d0 | nibble(*n)? as u16),
+        ("SAVE", [V(vx), V(vy)]) => x_y(0x5002, *vx, *vy),
+        ("LOAD", [V(vx), V(vy)]) => x_y(0x5003, *vx, *vy),
+        ("LD", [I, Long(nnnn)]) => {
+            let nnnn = u16::try_from(*nnnn).map_err(|_| out_of_range(*nnnn))?;
+            return Ok([0xf0, 0x00, (nnnn >> 8) as u8, nnnn as u8].to_vec());
+        }
+        ("PLANE", [Value(n)]) => Ok(0xf001 | (nibble(*n)? as u16) << 8),
+        ("AUDIO", []) => instruction(Chip8Instruction::LoadAudioPattern()),
+        ("PITCH", [V(x)]) => instruction(Chip8Instruction::SetPitch(*x)),
+
+        _ => Err(format!(
+            "Invalid operands for {}: {}",
+            mnemonic,
+            operands
+                .iter()
+                .map(|operand| operand.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )),
+    }?;
+
+    Ok(opcode.to_be_bytes().to_vec())
+}
+
+/// Size in bytes of an instruction, known before operands are resolved
+pub fn size<T>(mnemonic: &str, operands: &[Operand<T>]) -> usize {
+    match (mnemonic, operands) {
+        ("LD", [Operand::I, Operand::Long(_)]) => 4,
+        _ => 2,
+    }
+}
+
+fn addr(value: i64) -> Result<u16, String> {
+    match value {
+        0..=0xfff => Ok(value as u16),
+        _ => Err(out_of_range(value)),
+    }
+}
+
+/// Accepts negative bytes as their two's complement
+fn byte(value: i64) -> Result<u8, String> {
+    match value {
+        -0x80..=0xff => Ok(value as u8),
+        _ => Err(out_of_range(value)),
+    }
+}
+
+fn nibble(value: i64) -> Result<u8, String> {
+    match value {
+        0..=0xf => Ok(value as u8),
+        _ => Err(out_of_range(value)),
+    }
+}
+
+fn out_of_range(value: i64) -> String {
+    format!("Value out of range: {:#x}", value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+    use Operand::*;
+
+    #[rstest]
+    #[case::cls("CLS", &[], &[0x00, 0xe0])]
+    #[case::jp("JP", &[Value(0x20a)], &[0x12, 0x0a])]
+    #[case::jp_v0("JP", &[V(0), Value(0x300)], &[0xb3, 0x00])]
+    #[case::se_byte("SE", &[V(3), Value(0x10)], &[0x33, 0x10])]
+    #[case::se_register("SE", &[V(3), V(4)], &[0x53, 0x40])]
+    #[case::negative_byte("ADD", &[V(1), Value(-1)], &[0x71, 0xff])]
+    #[case::shr_single("SHR", &[V(5)], &[0x85, 0x56])]
+    #[case::ld_b("LD", &[B, V(2)], &[0xf2, 0x33])]
+    #[case::ld_indirect("LD", &[V(7), IndirectI], &[0xf7, 0x65])]
+    #[case::drw("DRW", &[V(0), V(1), Value(15)], &[0xd0, 0x1f])]
+    #[case::scd("SCD", &[Value(4)], &[0x00, 0xc4])]
+    #[case::ld_r("LD", &[R, V(3)], &[0xf3, 0x75])]
+    #[case::save("SAVE", &[V(1), V(4)], &[0x51, 0x42])]
+    #[case::long("LD", &[I, Long(0x1234)], &[0xf0, 0x00, 0x12, 0x34])]
+    #[case::plane("PLANE", &[Value(3)], &[0xf3, 0x01])]
+    fn test_encode(#[case] mnemonic: &str, #[case] operands: &[Operand], #[case] bytes: &[u8]) {
+        assert_eq!(encode(mnemonic, operands), Ok(bytes.to_vec()));
+        assert_eq!(size(mnemonic, operands), bytes.len());
+    }
+
+    #[rstest]
+    #[case::address("JP", &[Value(0x1000)], "Value out of range: 0x1000")]
+    #[case::byte("LD", &[V(0), Value(0x100)], "Value out of range: 0x100")]
+    #[case::nibble("DRW", &[V(0), V(1), Value(16)], "Value out of range: 0x10")]
+    #[case::operands("ADD", &[Dt, V(1)], "Invalid operands for ADD: DT, V1")]
+    #[case::jp_other_register("JP", &[V(1), Value(0x300)], "Invalid operands for JP: V1, 768")]
+    fn test_encode_errors(
+        #[case] mnemonic: &str,
+        #[case] operands: &[Operand],
+        #[case] error: &str,
+    ) {
+        assert_eq!(encode(mnemonic, operands), Err(error.to_string()));
+    }
+}
@@ -0,0 +1,590 @@
+mod mnemonic;
+pub mod octo;
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use crate::disasm::ENTRY_POINT;
+
+/// Includes nested deeper than this are reported as a cycle
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// Constants referring to each other deeper than this are reported as circular
+const MAX_CONSTANT_DEPTH: usize = 64;
+
+/// Instruction operand, holding an unresolved [`Expr`] until labels are known
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Operand<T = i64> {
+    V(u8),
+    I,
+    /// `[I]`, memory at I
+    IndirectI,
+    Dt,
+    St,
+    K,
+    F,
+    Hf,
+    B,
+    R,
+    Value(T),
+    /// `LONG addr`, the 16 bit address of XO-CHIP's `F000 NNNN`
+    Long(T),
+}
+
+impl std::fmt::Display for Operand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Operand::V(x) => write!(f, "V{:X}", x),
+            Operand::I => write!(f, "I"),
+            Operand::IndirectI => write!(f, "[I]"),
+            Operand::Dt => write!(f, "DT"),
+            Operand::St => write!(f, "ST"),
+            Operand::K => write!(f, "K"),
+            Operand::F => write!(f, "F"),
+            Operand::Hf => write!(f, "HF"),
+            Operand::B => write!(f, "B"),
+            Operand::R => write!(f, "R"),
+            Operand::Value(value) => write!(f, "{}", value),
+            Operand::Long(value) => write!(f, "LONG {}", value),
+        }
+    }
+}
+
+/// Sum of numbers and symbols, such as `sprite + 5`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Expr {
+    terms: Vec<(bool, Term)>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Term {
+    Number(i64),
+    Symbol(String),
+}
+
+/// Assembled ROM and the listing of every source line with its address and bytes
+pub struct Assembly {
+    pub rom: Vec<u8>,
+    listing: Vec<String>,
+}
+
+impl Assembly {
+    pub fn listing(&self) -> String {
+        let mut listing = self.listing.join("\n");
+        listing.push('\n');
+        listing
+    }
+}
+
+/// Source line, for error messages and the listing
+#[derive(Clone)]
+struct Line {
+    file: String,
+    number: usize,
+    text: String,
+}
+
+enum Statement {
+    Instruction(String, Vec<Operand<Expr>>),
+    /// Values and their width in bytes, from `DB` and `DW`
+    Data(Vec<Expr>, usize),
+    Constant(String, Expr),
+}
+
+/// Parsed source line
+struct Parsed {
+    line: Line,
+    label: Option<String>,
+    statement: Option<Statement>,
+}
+
+enum Symbol {
+    Address(u16),
+    Constant(Expr),
+}
+
+/// Assembles a source file, resolving includes relative to it
+pub fn assemble_file(path: &str) -> Result<Assembly, String> {
+    let mut lines = vec![];
+    let mut errors = vec![];
+    read_file(Path::new(path), &mut vec![], &mut lines, &mut errors);
+
+    assemble_lines(lines, errors)
+}
+
+/// Assembles source text, resolving includes relative to the working directory
+#[cfg(test)]
+pub fn assemble(source: &str) -> Result<Assembly, String> {
+    let mut lines = vec![];
+    let mut errors = vec![];
+    read_source(
+        source,
+        "<source>",
+        Path::new("."),
+        &mut vec![],
+        &mut lines,
+        &mut errors,
+    );
+
+    assemble_lines(lines, errors)
+}
+
+fn read_file(
+    path: &Path,
+    includes: &mut Vec<PathBuf>,
+    lines: &mut Vec<Parsed>,
+    errors: &mut Vec<String>,
+) {
+    match std::fs::read_to_string(path) {
+        Ok(source) => {
+            let dir = path.parent().unwrap_or(Path::new("."));
+            read_source(
+                &source,
+                &path.display().to_string(),
+                dir,
+                includes,
+                lines,
+                errors,
+            );
+        }
+        Err(e) => errors.push(format!("Failed to read {}: {}", path.display(), e)),
+    }
+}
+
+/// Parses every line of `source`, splicing in included files
+fn read_source(
+    source: &str,
+    file: &str,
+    dir: &Path,
+    includes: &mut Vec<PathBuf>,
+    lines: &mut Vec<Parsed>,
+    errors: &mut Vec<String>,
+) {
+    for (i, text) in source.lines().enumerate() {
+        let line = Line {
+            file: file.to_string(),
+            number: i + 1,
+            text: text.to_string(),
+        };
+        let code = text.split(';').next().unwrap_or_default().trim();
+
+        // INCLUDE is handled while reading, everything else once all lines are known
+        let mut words = code.splitn(2, char::is_whitespace);
+        if words
+            .next()
+            .is_some_and(|word| word.eq_ignore_ascii_case("INCLUDE"))
+        {
+            let path = dir.join(words.next().unwrap_or_default().trim().trim_matches('"'));
+            if includes.len() >= MAX_INCLUDE_DEPTH || includes.contains(&path) {
+                errors.push(error(&line, &format!("Include cycle: {}", path.display())));
+                continue;
+            }
+
+            lines.push(Parsed {
+                line,
+                label: None,
+                statement: None,
+            });
+            includes.push(path.clone());
+            read_file(&path, includes, lines, errors);
+            includes.pop();
+            continue;
+        }
+
+        match parse_line(code) {
+            Ok((label, statement)) => lines.push(Parsed {
+                line,
+                label,
+                statement,
+            }),
+            Err(e) => errors.push(error(&line, &e)),
+        }
+    }
+}
+
+fn assemble_lines(lines: Vec<Parsed>, mut errors: Vec<String>) -> Result<Assembly, String> {
+    // First pass: addresses of labels and constants
+    let mut symbols = HashMap::new();
+    let mut addr = ENTRY_POINT as usize;
+    for parsed in &lines {
+        let mut define = |name: &str, symbol: Symbol| {
+            if symbols.insert(name.to_string(), symbol).is_some() {
+                errors.push(error(&parsed.line, &format!("Duplicate symbol: {}", name)));
+            }
+        };
+
+        if let Some(label) = &parsed.label {
+            define(label, Symbol::Address(addr as u16));
+        }
+        match &parsed.statement {
+            Some(Statement::Constant(name, expr)) => define(name, Symbol::Constant(expr.clone())),
+            Some(Statement::Instruction(mnemonic, operands)) => {
+                addr += mnemonic::size(mnemonic, operands)
+            }
+            Some(Statement::Data(values, width)) => addr += values.len() * width,
+            None => {}
+        }
+    }
+    if addr > 0x1000 {
+        errors.push(format!(
+            "Program is {} bytes, only {} fit in memory",
+            addr - ENTRY_POINT as usize,
+            0x1000 - ENTRY_POINT as usize
+        ));
+    }
+
+    // Second pass: encode with every symbol known
+    let mut rom = vec![];
+    let mut listing = vec![];
+    for parsed in &lines {
+        let addr = ENTRY_POINT as usize + rom.len();
+        let bytes = match &parsed.statement {
+            Some(Statement::Instruction(mnemonic, operands)) => operands
+                .iter()
+                .map(|operand| resolve_operand(operand, &symbols))
+                .collect::<Result<Vec<_>, _>>()
+                .and_then(|operands| mnemonic::encode(mnemonic, &operands)),
+            Some(Statement::Data(values, width)) => encode_data(values, *width, &symbols),
+            Some(Statement::Constant(..)) | None => Ok(vec![]),
+        };
+
+        match bytes {
+            Ok(bytes) => {
+                let hex: Vec<String> = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+                listing.push(
+                    format!("{:#06x}  {:<11}  {}", addr, hex.join(" "), parsed.line.text)
+                        .trim_end()
+                        .to_string(),
+                );
+                rom.extend(bytes);
+            }
+            Err(e) => errors.push(error(&parsed.line, &e)),
+        }
+    }
+
+    if !errors.is_empty() {
+        return Err(errors.join("\n"));
+    }
+    Ok(Assembly { rom, listing })
+}
+
+/// Splits a line without its comment into an optional label and statement
+fn parse_line(code: &str) -> Result<(Option<String>, Option<Statement>), String> {
+    let (label, rest) = match code.split_once(':') {
+        Some((label, rest)) if is_identifier(label.trim()) => {
+            (Some(label.trim().to_string()), rest.trim())
+        }
+        _ => (None, code),
+    };
+    if rest.is_empty() {
+        return Ok((label, None));
+    }
+
+    let (first, operands) = rest
+        .split_once(char::is_whitespace)
+        .map_or((rest, ""), |(first, operands)| (first, operands.trim()));
+
+    // NAME EQU value, or NAME = value
+    let constant = operands
+        .split_once(char::is_whitespace)
+        .filter(|(word, _)| word.eq_ignore_ascii_case("EQU"))
+        .map(|(_, value)| value)
+        .or_else(|| operands.strip_prefix('='));
+    if let Some(value) = constant {
+        if !is_identifier(first) {
+            return Err(format!("Invalid constant name: {}", first));
+        }
+        return Ok((
+            label,
+            Some(Statement::Constant(first.to_string(), parse_expr(value)?)),
+        ));
+    }
+
+    let mnemonic = first.to_ascii_uppercase();
+    let operands: Vec<&str> = if operands.is_empty() {
+        vec![]
+    } else {
+        operands.split(',').map(str::trim).collect()
+    };
+
+    let statement = match mnemonic.as_str() {
+        "DB" | "DW" => Statement::Data(
+            operands
+                .iter()
+                .map(|value| parse_expr(value))
+                .collect::<Result<_, _>>()?,
+            if mnemonic == "DB" { 1 } else { 2 },
+        ),
+        _ => Statement::Instruction(
+            mnemonic,
+            operands
+                .iter()
+                .map(|operand| parse_operand(operand))
+                .collect::<Result<_, _>>()?,
+        ),
+    };
+
+    Ok((label, Some(statement)))
+}
+
+fn parse_operand(operand: &str) -> Result<Operand<Expr>, String> {
+    let upper = operand.to_ascii_uppercase();
+    let register = match upper.as_str() {
+        "I" => Some(Operand::I),
+        "[I]" => Some(Operand::IndirectI),
+        "DT" => Some(Operand::Dt),
+        "ST" => Some(Operand::St),
+        "K" => Some(Operand::K),
+        "F" => Some(Operand::F),
+        "HF" => Some(Operand::Hf),
+        "B" => Some(Operand::B),
+        "R" => Some(Operand::R),
+        _ => upper
+            .strip_prefix('V')
+            .filter(|x| x.len() == 1)
+            .and_then(|x| u8::from_str_radix(x, 16).ok())
+            .map(Operand::V),
+    };
+    if let Some(register) = register {
+        return Ok(register);
+    }
+
+    match upper.strip_prefix("LONG ") {
+        Some(_) => Ok(Operand::Long(parse_expr(&operand[5..])?)),
+        None => Ok(Operand::Value(parse_expr(operand)?)),
+    }
+}
+
+fn parse_expr(source: &str) -> Result<Expr, String> {
+    let mut terms = vec![];
+    let mut negative = false;
+    let mut rest = source.trim();
+    if rest.is_empty() {
+        return Err("Missing value".to_string());
+    }
+
+    loop {
+        let end = rest
+            .find(['+', '-'])
+            .filter(|&end| end > 0)
+            .unwrap_or(rest.len());
+        let (term, after) = rest.split_at(end);
+        let term = term.trim();
+
+        let term = if let Some(value) = parse_number(term) {
+            Term::Number(value)
+        } else if is_identifier(term) {
+            Term::Symbol(term.to_string())
+        } else if let Some(term) = term.strip_prefix('-') {
+            // A leading minus sign
+            negative = !negative;
+            rest = term.trim_start();
+            continue;
+        } else {
+            return Err(format!("Invalid value: {}", source.trim()));
+        };
+        terms.push((negative, term));
+
+        let Some(op) = after.chars().next() else {
+            break;
+        };
+        negative = op == '-';
+        rest = after[1..].trim_start();
+        if rest.is_empty() {
+            return Err(format!("Invalid value: {}", source.trim()));
+        }
+    }
+
+    Ok(Expr { terms })
+}
+
+/// Parses decimal, `#`, `$` or `0x` prefixed hexadecimal and `%` or `0b` prefixed binary
+fn parse_number(number: &str) -> Option<i64> {
+    let (digits, radix) = if let Some(hex) = number
+        .strip_prefix('#')
+        .or_else(|| number.strip_prefix('$'))
+        .or_else(|| number.strip_prefix("0x"))
+    {
+        (hex, 16)
+    } else if let Some(binary) = number
+        .strip_prefix('%')
+        .or_else(|| number.strip_prefix("0b"))
+    {
+        (binary, 2)
+    } else {
+        (number, 10)
+    };
+
+    if digits.is_empty() || !digits.chars().all(|c| c.is_digit(radix)) {
+        return None;
+    }
+    i64::from_str_radix(digits, radix).ok()
+}
+
+fn is_identifier(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+}
+
+fn resolve_operand(
+    operand: &Operand<Expr>,
+    symbols: &HashMap<String, Symbol>,
+) -> Result<Operand, String> {
+    Ok(match operand {
+        Operand::V(x) => Operand::V(*x),
+        Operand::I => Operand::I,
+        Operand::IndirectI => Operand::IndirectI,
+        Operand::Dt => Operand::Dt,
+        Operand::St => Operand::St,
+        Operand::K => Operand::K,
+        Operand::F => Operand::F,
+        Operand::Hf => Operand::Hf,
+        Operand::B => Operand::B,
+        Operand::R => Operand::R,
+        Operand::Value(expr) => Operand::Value(eval(expr, symbols, 0)?),
+        Operand::Long(expr) => Operand::Long(eval(expr, symbols, 0)?),
+    })
+}
+
+fn eval(expr: &Expr, symbols: &HashMap<String, Symbol>, depth: usize) -> Result<i64, String> {
+    expr.terms.iter().try_fold(0i64, |sum, (negative, term)| {
+        let value = match term {
+            Term::Number(value) => *value,
+            Term::Symbol(name) => match symbols.get(name) {
+                Some(Symbol::Address(addr)) => *addr as i64,
+                Some(Symbol::Constant(_)) if depth >= MAX_CONSTANT_DEPTH => {
+                    return Err(format!("Circular constant: {}", name))
+                }
+                Some(Symbol::Constant(expr)) => eval(expr, symbols, depth + 1)?,
+                None => return Err(format!("Undefined symbol: {}", name)),
+            },
+        };
+        Ok(if *negative { sum - value } else { sum + value })
+    })
+}
+
+fn encode_data(
+    values: &[Expr],
+    width: usize,
+    symbols: &HashMap<String, Symbol>,
+) -> Result<Vec<u8>, String> {
+    let mut bytes = vec![];
+    for value in values {
+        let value = eval(value, symbols, 0)?;
+        match width {
+            1 if (-0x80..=0xff).contains(&value) => bytes.push(value as u8),
+            2 if (-0x8000..=0xffff).contains(&value) => bytes.extend((value as u16).to_be_bytes()),
+            _ => return Err(format!("Value out of range: {:#x}", value)),
+        }
+    }
+    Ok(bytes)
+}
+
+fn error(line: &Line, message: &str) -> String {
+    format!("{}:{}: {}", line.file, line.number, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disasm::{Disassembly, Style};
+    use rstest::*;
+
+    #[rstest]
+    fn test_assemble_program() {
+        let source = "\
+; Draws a sprite and loops
+SPRITE_ROWS EQU 3
+X = 8
+main:
+    CLS
+    LD V0, X
+    ld v1, #10
+    LD I, sprite
+    DRW V0, V1, SPRITE_ROWS
+loop: JP loop
+sprite:
+    DB %10000000, $40, 0xe0
+    DW sprite + 2 - 1
+";
+        let assembly = assemble(source).unwrap();
+
+        assert_eq!(
+            assembly.rom,
+            vec![
+                0x00, 0xe0, 0x60, 0x08, 0x61, 0x10, 0xa2, 0x0c, 0xd0, 0x13, 0x12, 0x0a, 0x80, 0x40,
+                0xe0, 0x02, 0x0d,
+            ]
+        );
+    }
+
+    #[rstest]
+    fn test_listing() {
+        let assembly = assemble("start:\n    JP start ; forever\n    DB 1, 2\n").unwrap();
+
+        assert_eq!(
+            assembly.listing(),
+            "\
+0x0200               start:
+0x0200  12 00            JP start ; forever
+0x0202  01 02            DB 1, 2
+"
+        );
+    }
+
+    #[rstest]
+    fn test_include() {
+        let dir = std::env::temp_dir().join(format!("chip8-asm-include-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("main.asm"), "JP sprite\nINCLUDE \"sprite.asm\"\n").unwrap();
+        std::fs::write(dir.join("sprite.asm"), "sprite: DB #f0\n").unwrap();
+
+        let assembly = assemble_file(dir.join("main.asm").to_str().unwrap());
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(assembly.unwrap().rom, vec![0x12, 0x02, 0xf0]);
+    }
+
+    #[rstest]
+    fn test_include_cycle() {
+        let dir = std::env::temp_dir().join(format!("chip8-asm-cycle-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.asm"), "INCLUDE b.asm\n").unwrap();
+        std::fs::write(dir.join("b.asm"), "INCLUDE a.asm\n").unwrap();
+
+        let result = assemble_file(dir.join("a.asm").to_str().unwrap());
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(result.err().unwrap().contains("Include cycle"));
+    }
+
+    #[rstest]
+    #[case::undefined_symbol("JP nowhere", "<source>:1: Undefined symbol: nowhere")]
+    #[case::duplicate_label("a:\na:", "<source>:2: Duplicate symbol: a")]
+    #[case::out_of_range("LD V0, 256", "<source>:1: Value out of range: 0x100")]
+    #[case::bad_value("LD V0, 1 +", "<source>:1: Invalid value: 1 +")]
+    #[case::bad_operands("CLS V0", "<source>:1: Invalid operands for CLS: V0")]
+    #[case::circular_constant("A EQU B\nB EQU A\nLD V0, A", "<source>:3: Circular constant: A")]
+    #[case::every_error(
+        "JP x\nJP y",
+        "<source>:1: Undefined symbol: x\n<source>:2: Undefined symbol: y"
+    )]
+    fn test_errors(#[case] source: &str, #[case] error: &str) {
+        assert_eq!(assemble(source).err(), Some(error.to_string()));
+    }
+
+    #[rstest]
+    fn test_reassembles_cowgod_disassembly() {
+        let rom = [
+            0x22, 0x0a, 0x30, 0x01, 0x70, 0xff, 0x12, 0x02, 0xff, 0xff, 0xa2, 0x14, 0x81, 0x2e,
+            0xd0, 0x13, 0x00, 0xee, 0x01, 0x80, 0x40, 0xe0,
+        ];
+        let source = Disassembly::new(&rom).listing(Style::Cowgod);
+
+        assert_eq!(assemble(&source).unwrap().rom, rom);
+    }
+}
@@ -0,0 +1,547 @@
+use std::collections::HashMap;
+
+use crate::{
+    asm::{mnemonic, Operand},
+    disasm::ENTRY_POINT,
+};
+
+/// Compiles Octo source into a ROM loaded at 0x200.
+///
+/// Covers labels, `:const`, `:alias`, data bytes, every statement that maps
+/// to a single instruction, `if ... then`, `if ... begin`/`else`/`end` and
+/// `loop`/`while`/`again`. Macros, `:calc` and the other metaprogramming
+/// directives are reported as unsupported.
+pub fn compile(source: &str) -> Result<Vec<u8>, String> {
+    let mut compiler = Compiler::new(source);
+    while compiler.pos < compiler.tokens.len() {
+        compiler
+            .statement()
+            .map_err(|e| format!("Line {}: {}", compiler.line(), e))?;
+    }
+    compiler.finish()
+}
+
+/// Condition of `if` and `while`, as the skip instruction taken when it holds
+struct Condition {
+    mnemonic: &'static str,
+    operands: Vec<Operand>,
+}
+
+impl Condition {
+    fn negate(self) -> Condition {
+        let mnemonic = match self.mnemonic {
+            "SE" => "SNE",
+            "SNE" => "SE",
+            "SKP" => "SKNP",
+            _ => "SKP",
+        };
+        Condition { mnemonic, ..self }
+    }
+}
+
+/// Control flow block waiting for its closing word
+enum Block {
+    /// `begin`, with the offset of the jump to `else` or `end`
+    Begin(usize),
+    /// `else`, with the offset of the jump to `end`
+    Else(usize),
+    /// `loop`, with its address and the offsets of the jumps out of `while`
+    Loop(u16, Vec<usize>),
+}
+
+struct Compiler<'a> {
+    /// Words and the line they are on
+    tokens: Vec<(&'a str, usize)>,
+    pos: usize,
+
+    rom: Vec<u8>,
+    labels: HashMap<&'a str, u16>,
+    constants: HashMap<&'a str, i64>,
+    aliases: HashMap<&'a str, u8>,
+
+    /// Opcodes waiting for the address of a label, with the line referring to it
+    fixups: Vec<(usize, &'a str, usize)>,
+    blocks: Vec<Block>,
+}
+
+impl<'a> Compiler<'a> {
+    fn new(source: &'a str) -> Self {
+        let tokens = source
+            .lines()
+            .enumerate()
+            .flat_map(|(i, line)| {
+                let code = line.split('#').next().unwrap_or_default();
+                code.split_whitespace().map(move |word| (word, i + 1))
+            })
+            .collect::<Vec<_>>();
+
+        let mut compiler = Compiler {
+            tokens,
+            pos: 0,
+            rom: vec![],
+            labels: HashMap::new(),
+            constants: HashMap::new(),
+            aliases: HashMap::new(),
+            fixups: vec![],
+            blocks: vec![],
+        };
+
+        // Octo starts at `main`, which needs a jump unless it is the first label
+        let starts_with_main = compiler.tokens.len() >= 2
+            && compiler.tokens[0].0 == ":"
+            && compiler.tokens[1].0 == "main";
+        if !starts_with_main {
+            compiler.jump_to("JP", "main", 0);
+        }
+
+        compiler
+    }
+
+    fn finish(mut self) -> Result<Vec<u8>, String> {
+        if !self.blocks.is_empty() {
+            return Err("Missing end or again at end of program".to_string());
+        }
+
+        for (offset, label, line) in std::mem::take(&mut self.fixups) {
+            match self.labels.get(label) {
+                Some(addr) => self.patch(offset, *addr),
+                // Line 0 is the jump to `main` added before the first statement
+                None if line == 0 => return Err("Missing main label".to_string()),
+                None => return Err(format!("Line {}: Undefined name: {}", line, label)),
+            }
+        }
+
+        Ok(self.rom)
+    }
+
+    fn line(&self) -> usize {
+        let last = self.tokens.last().map_or(0, |(_, line)| *line);
+        self.tokens
+            .get(self.pos.saturating_sub(1))
+            .map_or(last, |(_, line)| *line)
+    }
+
+    fn next(&mut self) -> Result<&'a str, String> {
+        let token = self.tokens.get(self.pos).map(|(token, _)| *token);
+        self.pos += 1;
+        token.ok_or_else(|| "Unexpected end of program".to_string())
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<(), String> {
+        match self.next()? {
+            token if token == expected => Ok(()),
+            token => Err(format!("Expected {}, found {}", expected, token)),
+        }
+    }
+
+    fn here(&self) -> u16 {
+        ENTRY_POINT + self.rom.len() as u16
+    }
+
+    fn statement(&mut self) -> Result<(), String> {
+        let token = self.next()?;
+        match token {
+            ":" => {
+                let name = self.name()?;
+                let addr = self.here();
+                if self.labels.insert(name, addr).is_some() {
+                    return Err(format!("Duplicate label: {}", name));
+                }
+                Ok(())
+            }
+            ":const" => {
+                let name = self.name()?;
+                let value = self.value()?;
+                self.constants.insert(name, value);
+                Ok(())
+            }
+            ":alias" => {
+                let name = self.name()?;
+                let register = self.register()?;
+                self.aliases.insert(name, register);
+                Ok(())
+            }
+            ":call" => {
+                let target = self.next()?;
+                self.address("CALL", &[], target)
+            }
+            ":byte" => {
+                let value = self.value()?;
+                self.data(value)
+            }
+            ":breakpoint" => self.name().map(|_| ()),
+
+            "clear" => self.emit("CLS", &[]),
+            "return" | ";" => self.emit("RET", &[]),
+            "exit" => self.emit("EXIT", &[]),
+            "hires" => self.emit("HIGH", &[]),
+            "lores" => self.emit("LOW", &[]),
+            "scroll-down" => {
+                let n = self.value()?;
+                self.emit("SCD", &[Operand::Value(n)])
+            }
+            "scroll-up" => {
+                let n = self.value()?;
+                self.emit("SCU", &[Operand::Value(n)])
+            }
+            "scroll-left" => self.emit("SCL", &[]),
+            "scroll-right" => self.emit("SCR", &[]),
+            "jump" => {
+                let target = self.next()?;
+                self.address("JP", &[], target)
+            }
+            "jump0" => {
+                let target = self.next()?;
+                self.address("JP", &[Operand::V(0)], target)
+            }
+            "sprite" => {
+                let x = self.register()?;
+                let y = self.register()?;
+                let n = self.value()?;
+                self.emit("DRW", &[Operand::V(x), Operand::V(y), Operand::Value(n)])
+            }
+            "bcd" => self.register_op("LD", Some(Operand::B), None),
+            "save" => self.register_op("LD", Some(Operand::IndirectI), None),
+            "load" => self.register_op("LD", None, Some(Operand::IndirectI)),
+            "delay" => {
+                self.expect(":=")?;
+                self.register_op("LD", Some(Operand::Dt), None)
+            }
+            "buzzer" => {
+                self.expect(":=")?;
+                self.register_op("LD", Some(Operand::St), None)
+            }
+            "i" => self.i_statement(),
+
+            "if" => {
+                let condition = self.condition()?;
+                match self.next()? {
+                    "then" => self.skip(condition.negate()),
+                    "begin" => {
+                        self.skip(condition)?;
+                        let jump = self.jump_to("JP", "", 0);
+                        self.blocks.push(Block::Begin(jump));
+                        Ok(())
+                    }
+                    other => Err(format!("Expected then or begin, found {}", other)),
+                }
+            }
+            "else" => match self.blocks.pop() {
+                Some(Block::Begin(jump)) => {
+                    let end = self.jump_to("JP", "", 0);
+                    self.patch(jump, self.here());
+                    self.blocks.push(Block::Else(end));
+                    Ok(())
+                }
+                _ => Err("else without begin".to_string()),
+            },
+            "end" => match self.blocks.pop() {
+                Some(Block::Begin(jump) | Block::Else(jump)) => {
+                    self.patch(jump, self.here());
+                    Ok(())
+                }
+                _ => Err("end without begin".to_string()),
+            },
+            "loop" => {
+                self.blocks.push(Block::Loop(self.here(), vec![]));
+                Ok(())
+            }
+            "while" => {
+                let condition = self.condition()?;
+                self.skip(condition)?;
+                let jump = self.jump_to("JP", "", 0);
+                match self.blocks.iter_mut().rev().find_map(|block| match block {
+                    Block::Loop(_, exits) => Some(exits),
+                    _ => None,
+                }) {
+                    Some(exits) => {
+                        exits.push(jump);
+                        Ok(())
+                    }
+                    None => Err("while outside of loop".to_string()),
+                }
+            }
+            "again" => match self.blocks.pop() {
+                Some(Block::Loop(start, exits)) => {
+                    self.emit("JP", &[Operand::Value(start as i64)])?;
+                    for exit in exits {
+                        self.patch(exit, self.here());
+                    }
+                    Ok(())
+                }
+                _ => Err("again without loop".to_string()),
+            },
+
+            _ if token.starts_with(':') => Err(format!("Unsupported directive: {}", token)),
+            _ if self.is_register(token) => {
+                self.pos -= 1;
+                self.assignment()
+            }
+            _ => match self.number(token) {
+                Some(value) => self.data(value),
+                // Any other name calls the subroutine at that label
+                None => self.address("CALL", &[], token),
+            },
+        }
+    }
+
+    fn i_statement(&mut self) -> Result<(), String> {
+        match self.next()? {
+            ":=" => match self.next()? {
+                "hex" => self.register_op("LD", Some(Operand::F), None),
+                "bighex" => self.register_op("LD", Some(Operand::Hf), None),
+                target => self.address("LD", &[Operand::I], target),
+            },
+            "+=" => self.register_op("ADD", Some(Operand::I), None),
+            other => Err(format!("Invalid operator for i: {}", other)),
+        }
+    }
+
+    fn assignment(&mut self) -> Result<(), String> {
+        let x = Operand::V(self.register()?);
+        let operator = self.next()?;
+        let source = self.next()?;
+
+        if let Some(y) = self.register_named(source) {
+            let mnemonic = match operator {
+                ":=" => "LD",
+                "+=" => "ADD",
+                "-=" => "SUB",
+                "=-" => "SUBN",
+                "|=" => "OR",
+                "&=" => "AND",
+                "^=" => "XOR",
+                ">>=" => "SHR",
+                "<<=" => "SHL",
+                _ => return Err(format!("Invalid operator: {}", operator)),
+            };
+            return self.emit(mnemonic, &[x, Operand::V(y)]);
+        }
+
+        match (operator, source) {
+            (":=", "delay") => self.emit("LD", &[x, Operand::Dt]),
+            (":=", "key") => self.emit("LD", &[x, Operand::K]),
+            (":=", "random") => {
+                let mask = self.value()?;
+                self.emit("RND", &[x, Operand::Value(mask)])
+            }
+            (":=", _) => {
+                let value = self.resolve(source)?;
+                self.emit("LD", &[x, Operand::Value(value)])
+            }
+            ("+=", _) => {
+                let value = self.resolve(source)?;
+                self.emit("ADD", &[x, Operand::Value(value)])
+            }
+            ("-=", _) => {
+                let value = self.resolve(source)?;
+                self.emit("ADD", &[x, Operand::Value(value.wrapping_neg() & 0xff)])
+            }
+            _ => Err(format!("Invalid operator: {} {}", operator, source)),
+        }
+    }
+
+    fn condition(&mut self) -> Result<Condition, String> {
+        let x = Operand::V(self.register()?);
+        let (mnemonic, operands) = match self.next()? {
+            "key" => ("SKP", vec![x]),
+            "-key" => ("SKNP", vec![x]),
+            operator @ ("==" | "!=") => {
+                let mnemonic = if operator == "==" { "SE" } else { "SNE" };
+                let source = self.next()?;
+                let y = match self.register_named(source) {
+                    Some(y) => Operand::V(y),
+                    None => Operand::Value(self.resolve(source)?),
+                };
+                (mnemonic, vec![x, y])
+            }
+            other => return Err(format!("Unsupported comparison: {}", other)),
+        };
+
+        Ok(Condition { mnemonic, operands })
+    }
+
+    /// Emits the instruction skipping the next one when `condition` holds
+    fn skip(&mut self, condition: Condition) -> Result<(), String> {
+        self.emit(condition.mnemonic, &condition.operands)
+    }
+
+    /// Emits `mnemonic vx` with an operand before or after the register
+    fn register_op(
+        &mut self,
+        mnemonic: &str,
+        before: Option<Operand>,
+        after: Option<Operand>,
+    ) -> Result<(), String> {
+        let x = Operand::V(self.register()?);
+        let operands = before
+            .into_iter()
+            .chain([x])
+            .chain(after)
+            .collect::<Vec<_>>();
+        self.emit(mnemonic, &operands)
+    }
+
+    /// Emits an instruction whose last operand is an address, resolved later for labels
+    fn address(
+        &mut self,
+        mnemonic: &str,
+        operands: &[Operand],
+        target: &'a str,
+    ) -> Result<(), String> {
+        match self
+            .number(target)
+            .or_else(|| self.constants.get(target).copied())
+        {
+            Some(addr) => {
+                let operands = [operands, &[Operand::Value(addr)]].concat();
+                self.emit(mnemonic, &operands)
+            }
+            None => {
+                let line = self.line();
+                let prefix = match operands {
+                    [Operand::V(0)] => "JP0",
+                    _ => mnemonic,
+                };
+                self.jump_to(prefix, target, line);
+                Ok(())
+            }
+        }
+    }
+
+    /// Emits an instruction addressing `label`, returning its offset for patching.
+    /// An empty label leaves the address to be patched by the caller.
+    fn jump_to(&mut self, mnemonic: &str, label: &'a str, line: usize) -> usize {
+        let offset = self.rom.len();
+        let opcode: u16 = match mnemonic {
+            "CALL" => 0x2000,
+            "LD" => 0xa000,
+            "JP0" => 0xb000,
+            _ => 0x1000,
+        };
+        self.rom.extend(opcode.to_be_bytes());
+        if !label.is_empty() {
+            self.fixups.push((offset, label, line));
+        }
+        offset
+    }
+
+    fn patch(&mut self, offset: usize, addr: u16) {
+        self.rom[offset] = (self.rom[offset] & 0xf0) | (addr >> 8) as u8 & 0x0f;
+        self.rom[offset + 1] = addr as u8;
+    }
+
+    fn emit(&mut self, mnemonic: &str, operands: &[Operand]) -> Result<(), String> {
+        let bytes = mnemonic::encode(mnemonic, operands)?;
+        self.rom.extend(bytes);
+        Ok(())
+    }
+
+    fn data(&mut self, value: i64) -> Result<(), String> {
+        match value {
+            -0x80..=0xff => {
+                self.rom.push(value as u8);
+                Ok(())
+            }
+            _ => Err(format!("Byte out of range: {}", value)),
+        }
+    }
+
+    fn name(&mut self) -> Result<&'a str, String> {
+        match self.next()? {
+            name if self.number(name).is_some() || self.is_register(name) => {
+                Err(format!("Invalid name: {}", name))
+            }
+            name => Ok(name),
+        }
+    }
+
+    fn value(&mut self) -> Result<i64, String> {
+        let token = self.next()?;
+        self.resolve(token)
+    }
+
+    /// Numbers, constants and labels defined so far
+    fn resolve(&self, token: &str) -> Result<i64, String> {
+        self.number(token)
+            .or_else(|| self.constants.get(token).copied())
+            .or_else(|| self.labels.get(token).map(|addr| *addr as i64))
+            .ok_or_else(|| format!("Undefined name: {}", token))
+    }
+
+    fn register(&mut self) -> Result<u8, String> {
+        let token = self.next()?;
+        self.register_named(token)
+            .ok_or_else(|| format!("Expected a register, found {}", token))
+    }
+
+    fn is_register(&self, token: &str) -> bool {
+        self.register_named(token).is_some()
+    }
+
+    fn register_named(&self, token: &str) -> Option<u8> {
+        if let Some(register) = self.aliases.get(token) {
+            return Some(*register);
+        }
+        match token.strip_prefix(['v', 'V']) {
+            Some(digit) if digit.len() == 1 => u8::from_str_radix(digit, 16).ok(),
+            _ => None,
+        }
+    }
+
+    fn number(&self, token: &str) -> Option<i64> {
+        let (negative, digits) = match token.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, token),
+        };
+        let value = if let Some(hex) = digits.strip_prefix("0x") {
+            i64::from_str_radix(hex, 16).ok()
+        } else if let Some(binary) = digits.strip_prefix("0b") {
+            i64::from_str_radix(binary, 2).ok()
+        } else {
+            digits.parse().ok()
+        }?;
+        Some(if negative { -value } else { value })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    #[case::main_first(": main clear jump main", &[0x00, 0xe0, 0x12, 0x00])]
+    #[case::jump_to_main(": sub return : main sub", &[0x12, 0x04, 0x00, 0xee, 0x22, 0x02])]
+    #[case::registers(
+        ": main v1 := 5 v1 += v2 v3 -= 1 va >>= vb",
+        &[0x61, 0x05, 0x81, 0x24, 0x73, 0xff, 0x8a, 0xb6]
+    )]
+    #[case::constants_and_aliases(
+        ": main :const size 3 :alias x v4 i := logo sprite x x size : logo 0xf0 0b1001 0xf0",
+        &[0xa2, 0x04, 0xd4, 0x43, 0xf0, 0x09, 0xf0]
+    )]
+    #[case::if_then(": main if v0 == 3 then v1 := 1", &[0x40, 0x03, 0x61, 0x01])]
+    #[case::if_begin_else(
+        ": main if v0 != v1 begin clear else return end",
+        &[0x90, 0x10, 0x12, 0x08, 0x00, 0xe0, 0x12, 0x0a, 0x00, 0xee]
+    )]
+    #[case::loop_while(
+        ": main loop while v0 key v0 += 1 again",
+        &[0xe0, 0x9e, 0x12, 0x08, 0x70, 0x01, 0x12, 0x00]
+    )]
+    #[case::comments(": main # comment\nclear # another", &[0x00, 0xe0])]
+    fn test_compile(#[case] source: &str, #[case] rom: &[u8]) {
+        assert_eq!(compile(source), Ok(rom.to_vec()));
+    }
+
+    #[rstest]
+    #[case::missing_main("clear", "Missing main label")]
+    #[case::undefined_call(": main\nfoo", "Line 2: Undefined name: foo")]
+    #[case::macro_(": main\n:macro foo { }", "Line 2: Unsupported directive: :macro")]
+    #[case::comparison(": main if v0 < 3 then clear", "Line 1: Unsupported comparison: <")]
+    #[case::unclosed(": main loop", "Missing end or again at end of program")]
+    fn test_compile_errors(#[case] source: &str, #[case] error: &str) {
+        assert_eq!(compile(source), Err(error.to_string()));
+    }
+}
@@ -0,0 +1,138 @@
+use std::f64::consts::TAU;
+
+use crate::audio::{samples_in_frame, Voice};
+
+/// Shape of one period of the beeper's tone
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Waveform {
+    Square,
+    Triangle,
+    Sawtooth,
+    Sine,
+}
+
+impl Waveform {
+    /// Value at `phase`, from 0.0 to 1.0 through the period, between -1.0 and 1.0
+    fn sample(&self, phase: f64) -> f64 {
+        match self {
+            Waveform::Square if phase < 0.5 => 1.0,
+            Waveform::Square => -1.0,
+            Waveform::Triangle => 1.0 - 4.0 * (phase - 0.5).abs(),
+            Waveform::Sawtooth => 2.0 * phase - 1.0,
+            Waveform::Sine => (TAU * phase).sin(),
+        }
+    }
+}
+
+/// Tone options for [`Beeper`]
+#[derive(Clone, Debug)]
+pub struct BeeperConfig {
+    /// Pitch in Hz
+    pub frequency: f64,
+    pub waveform: Waveform,
+    /// Peak amplitude, from 0.0 to 1.0
+    pub volume: f64,
+}
+
+impl Default for BeeperConfig {
+    fn default() -> Self {
+        BeeperConfig {
+            frequency: 440.0,
+            waveform: Waveform::Square,
+            volume: 0.25,
+        }
+    }
+}
+
+/// Renders the buzzer frame by frame, a tone while it is on and silence while it is off
+pub struct Beeper {
+    config: BeeperConfig,
+    sample_rate: u32,
+    /// Position within the current period, every beep starts at 0.0
+    phase: f64,
+    /// Frames rendered so far, so rates that don't divide by the frame rate keep in step
+    frames: u64,
+}
+
+impl Beeper {
+    pub fn new(config: BeeperConfig, sample_rate: u32) -> Self {
+        Beeper {
+            config,
+            sample_rate,
+            phase: 0.0,
+            frames: 0,
+        }
+    }
+}
+
+impl Voice for Beeper {
+    fn frame(&mut self, on: bool) -> Vec<f32> {
+        let count = samples_in_frame(self.frames, self.sample_rate);
+        self.frames += 1;
+
+        if !on {
+            self.phase = 0.0;
+            return vec![0.0; count];
+        }
+        let step = self.config.frequency / self.sample_rate as f64;
+        (0..count)
+            .map(|_| {
+                let sample = self.config.waveform.sample(self.phase) * self.config.volume;
+                self.phase = (self.phase + step).fract();
+                sample as f32
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    fn beeper(waveform: Waveform, sample_rate: u32) -> Beeper {
+        Beeper::new(
+            BeeperConfig {
+                // Four samples per period
+                frequency: sample_rate as f64 / 4.0,
+                waveform,
+                volume: 0.5,
+            },
+            sample_rate,
+        )
+    }
+
+    #[rstest]
+    #[case::even(44_100, &[735, 735, 735])]
+    #[case::uneven(22_050, &[367, 368, 367, 368])]
+    fn test_samples_per_frame(#[case] sample_rate: u32, #[case] counts: &[usize]) {
+        let mut beeper = beeper(Waveform::Square, sample_rate);
+        for count in counts {
+            assert_eq!(beeper.frame(true).len(), *count);
+        }
+    }
+
+    #[rstest]
+    #[case::square(Waveform::Square, [0.5, 0.5, -0.5, -0.5])]
+    #[case::triangle(Waveform::Triangle, [-0.5, 0.0, 0.5, 0.0])]
+    #[case::sawtooth(Waveform::Sawtooth, [-0.5, -0.25, 0.0, 0.25])]
+    #[case::sine(Waveform::Sine, [0.0, 0.5, 0.0, -0.5])]
+    fn test_waveforms(#[case] waveform: Waveform, #[case] period: [f32; 4]) {
+        let samples = beeper(waveform, 240).frame(true);
+        assert_eq!(samples.len(), 4);
+        for (sample, expected) in samples.iter().zip(period) {
+            assert!((sample - expected).abs() < 1e-6, "{:?}", samples);
+        }
+    }
+
+    #[rstest]
+    fn test_silence_restarts_the_tone() {
+        let mut beeper = beeper(Waveform::Sawtooth, 360);
+        assert_eq!(
+            beeper.frame(true),
+            vec![-0.5, -0.25, 0.0, 0.25, -0.5, -0.25]
+        );
+        assert_eq!(beeper.frame(false), vec![0.0; 6]);
+        assert_eq!(beeper.frame(true)[0], -0.5);
+    }
+}
@@ -0,0 +1,35 @@
+pub mod beeper;
+pub mod pattern;
+pub mod wav;
+
+use crate::chip8::FRAME_RATE;
+
+/// Samples per second rendered for audio backends
+pub const SAMPLE_RATE: u32 = 44_100;
+
+/// Output for the sound the interpreter produces, driven once per frame.
+///
+/// Backends receive rendered samples, so tone generation stays the same
+/// whichever device or file they end up in.
+pub trait Audio {
+    /// Samples per second [`Audio::play`] expects
+    fn sample_rate(&self) -> u32;
+
+    /// Called once per frame with that frame's samples, from -1.0 to 1.0
+    fn play(&mut self, samples: &[f32]);
+}
+
+/// Sound source rendered frame by frame, given whether the buzzer sounds during the frame
+pub trait Voice {
+    fn frame(&mut self, on: bool) -> Vec<f32>;
+
+    /// Takes the XO-CHIP pattern and pitch before each frame, once the program has loaded a pattern
+    fn set_pattern(&mut self, _pattern: [u8; 16], _pitch: u8) {}
+}
+
+/// Samples in frame number `frame`, spread so rates that don't divide by the
+/// frame rate keep in step
+fn samples_in_frame(frame: u64, sample_rate: u32) -> usize {
+    let rate = sample_rate as u64;
+    ((frame + 1) * rate / FRAME_RATE - frame * rate / FRAME_RATE) as usize
+}
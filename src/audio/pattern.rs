@@ -0,0 +1,161 @@
+use crate::audio::{beeper::Beeper, samples_in_frame, Voice};
+
+/// Bits in an XO-CHIP audio pattern
+const PATTERN_BITS: u64 = 128;
+
+/// Pitch at which the pattern plays at 4000 bits per second
+pub const DEFAULT_PITCH: u8 = 64;
+
+/// Renders the buzzer as XO-CHIP does, looping a 16 byte pattern of 1 bit samples
+/// (as loaded by F002) at the rate set by the pitch register (as set by FX3A).
+///
+/// Programs that never load a pattern keep the plain beep of `fallback`.
+pub struct PatternPlayer {
+    fallback: Beeper,
+    pattern: Option<[u8; 16]>,
+    /// Bits played per second
+    rate: f64,
+    /// Amplitude of set bits, clear bits play at its negative
+    volume: f64,
+    sample_rate: u32,
+    /// Samples since the buzzer turned on, every beep starts at the first bit
+    position: u64,
+    /// Frames rendered so far, so rates that don't divide by the frame rate keep in step
+    frames: u64,
+}
+
+impl PatternPlayer {
+    pub fn new(fallback: Beeper, volume: f64, sample_rate: u32) -> Self {
+        PatternPlayer {
+            fallback,
+            pattern: None,
+            rate: pitch_rate(DEFAULT_PITCH),
+            volume,
+            sample_rate,
+            position: 0,
+            frames: 0,
+        }
+    }
+
+    /// Pattern bit sounding at sample `position`, most significant bit of byte 0 first.
+    ///
+    /// Computed from the sample count rather than accumulated, so long beeps don't drift.
+    fn bit(&self, pattern: &[u8; 16], position: u64) -> bool {
+        let bit = (position as f64 * self.rate / self.sample_rate as f64) as u64 % PATTERN_BITS;
+        pattern[bit as usize / 8] & (0x80 >> (bit % 8)) != 0
+    }
+}
+
+/// Bits played per second at `pitch`
+fn pitch_rate(pitch: u8) -> f64 {
+    4000.0 * 2f64.powf((pitch as f64 - 64.0) / 48.0)
+}
+
+impl Voice for PatternPlayer {
+    fn frame(&mut self, on: bool) -> Vec<f32> {
+        let Some(pattern) = self.pattern else {
+            return self.fallback.frame(on);
+        };
+        let count = samples_in_frame(self.frames, self.sample_rate);
+        self.frames += 1;
+
+        if !on {
+            self.position = 0;
+            return vec![0.0; count];
+        }
+        let start = self.position;
+        self.position += count as u64;
+        (start..self.position)
+            .map(|position| match self.bit(&pattern, position) {
+                true => self.volume as f32,
+                false => -self.volume as f32,
+            })
+            .collect()
+    }
+
+    fn set_pattern(&mut self, pattern: [u8; 16], pitch: u8) {
+        self.pattern = Some(pattern);
+        self.rate = pitch_rate(pitch);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::beeper::BeeperConfig;
+    use rstest::*;
+
+    /// One set bit, then alternating pairs, then clear
+    const PATTERN: [u8; 16] = [0x80, 0x33, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+
+    fn player(pattern: [u8; 16], pitch: u8, volume: f64) -> PatternPlayer {
+        let mut player =
+            PatternPlayer::new(Beeper::new(BeeperConfig::default(), 8000), volume, 8000);
+        player.set_pattern(pattern, pitch);
+        player
+    }
+
+    /// Signs of the samples, as a string of `+` and `-`
+    fn signs(samples: &[f32]) -> String {
+        samples
+            .iter()
+            .map(|sample| if *sample > 0.0 { '+' } else { '-' })
+            .collect()
+    }
+
+    #[rstest]
+    #[case::default_pitch(DEFAULT_PITCH, "++------------------++++----++++----")]
+    #[case::octave_up(112, "+---------++--++--------")]
+    #[case::octave_down(16, "++++------------------------------------++++++++")]
+    fn test_pitch(#[case] pitch: u8, #[case] expected: &str) {
+        // 8000 Hz plays 2 samples per bit at the default pitch
+        let mut player = player(PATTERN, pitch, 0.5);
+        let samples = player.frame(true);
+        assert_eq!(samples.len(), 133);
+        assert_eq!(&signs(&samples)[..expected.len()], expected);
+        assert!(samples.iter().all(|sample| sample.abs() == 0.5));
+    }
+
+    #[rstest]
+    fn test_octave_up_plays_each_bit_once() {
+        let mut player = player(PATTERN, 112, 1.0);
+        let samples = player.frame(true);
+        // 0x80 then 0x33
+        assert_eq!(&signs(&samples)[..16], "+---------++--++");
+        // Wraps back to the start of the pattern after 128 bits
+        assert_eq!(&signs(&samples)[128..133], "+----");
+    }
+
+    #[rstest]
+    fn test_pattern_continues_across_frames() {
+        let mut player = player([0x55; 16], DEFAULT_PITCH, 1.0);
+        let frames = [player.frame(true), player.frame(true), player.frame(true)];
+        assert_eq!(
+            frames.iter().map(Vec::len).collect::<Vec<_>>(),
+            vec![133, 133, 134]
+        );
+
+        // 0x55 alternates every bit, so the stream alternates every 2 samples
+        let stream = signs(&frames.concat());
+        assert_eq!(stream, "--++".repeat(100));
+    }
+
+    #[rstest]
+    fn test_silence_restarts_the_pattern() {
+        let mut player = player(PATTERN, 112, 1.0);
+        player.frame(true);
+        assert_eq!(player.frame(false), vec![0.0; 133]);
+        assert_eq!(&signs(&player.frame(true))[..4], "+---");
+    }
+
+    #[rstest]
+    fn test_beeps_until_a_pattern_is_loaded() {
+        let config = BeeperConfig::default();
+        let mut beeper = Beeper::new(config.clone(), 8000);
+        let mut player = PatternPlayer::new(Beeper::new(config, 8000), 1.0, 8000);
+
+        assert_eq!(player.frame(true), beeper.frame(true));
+        player.set_pattern(PATTERN, 112);
+        assert_eq!(&signs(&player.frame(true))[..4], "+---");
+    }
+}
@@ -0,0 +1,124 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Seek, SeekFrom, Write},
+};
+
+use crate::audio::Audio;
+
+/// Writes everything played to a 16 bit mono PCM WAV file.
+///
+/// The header's sizes are filled in when the writer is dropped, so the
+/// file can be recorded headlessly without an audio device.
+pub struct WavWriter {
+    output: BufWriter<File>,
+    path: String,
+    sample_rate: u32,
+    /// Bytes of samples written so far
+    data_len: u32,
+    /// Set once writing failed, after which samples are dropped
+    failed: bool,
+}
+
+/// RIFF header of a 16 bit mono PCM file holding `data_len` bytes of samples
+fn header(sample_rate: u32, data_len: u32) -> Vec<u8> {
+    let mut header = vec![];
+    header.extend_from_slice(b"RIFF");
+    header.extend_from_slice(&(36 + data_len).to_le_bytes());
+    header.extend_from_slice(b"WAVEfmt ");
+    header.extend_from_slice(&16u32.to_le_bytes());
+    // PCM, one channel
+    header.extend_from_slice(&1u16.to_le_bytes());
+    header.extend_from_slice(&1u16.to_le_bytes());
+    header.extend_from_slice(&sample_rate.to_le_bytes());
+    // Bytes per second and per sample, then bits per sample
+    header.extend_from_slice(&(sample_rate * 2).to_le_bytes());
+    header.extend_from_slice(&2u16.to_le_bytes());
+    header.extend_from_slice(&16u16.to_le_bytes());
+    header.extend_from_slice(b"data");
+    header.extend_from_slice(&data_len.to_le_bytes());
+    header
+}
+
+impl WavWriter {
+    pub fn create(path: &str, sample_rate: u32) -> Result<Self, String> {
+        let error = |e: std::io::Error| format!("Failed to create WAV file {}: {}", path, e);
+        let mut output = BufWriter::new(File::create(path).map_err(error)?);
+        output.write_all(&header(sample_rate, 0)).map_err(error)?;
+
+        Ok(WavWriter {
+            output,
+            path: path.to_string(),
+            sample_rate,
+            data_len: 0,
+            failed: false,
+        })
+    }
+
+    fn finish(&mut self) -> std::io::Result<()> {
+        self.output.flush()?;
+        let file = self.output.get_mut();
+        file.seek(SeekFrom::Start(0))?;
+        file.write_all(&header(self.sample_rate, self.data_len))
+    }
+}
+
+impl Audio for WavWriter {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn play(&mut self, samples: &[f32]) {
+        if self.failed {
+            return;
+        }
+        let bytes = samples
+            .iter()
+            .flat_map(|sample| ((sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16).to_le_bytes())
+            .collect::<Vec<_>>();
+
+        match self.output.write_all(&bytes) {
+            Ok(()) => self.data_len += bytes.len() as u32,
+            Err(e) => {
+                eprintln!(
+                    "Failed to write WAV file {}, audio stopped: {}",
+                    self.path, e
+                );
+                self.failed = true;
+            }
+        }
+    }
+}
+
+impl Drop for WavWriter {
+    fn drop(&mut self) {
+        if let Err(e) = self.finish() {
+            eprintln!("Failed to finish WAV file {}: {}", self.path, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    fn test_write() {
+        let path = std::env::temp_dir().join(format!("chip8-audio-{}.wav", std::process::id()));
+        let mut wav = WavWriter::create(path.to_str().unwrap(), 8000).unwrap();
+        wav.play(&[0.0, 1.0]);
+        wav.play(&[-1.0, 2.0]);
+        drop(wav);
+
+        let file = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(&file[..44], header(8000, 8).as_slice());
+        assert_eq!(&file[4..8], &44u32.to_le_bytes());
+        assert_eq!(&file[24..28], &8000u32.to_le_bytes());
+        assert_eq!(
+            &file[44..],
+            &[0x00, 0x00, 0xff, 0x7f, 0x01, 0x80, 0xff, 0x7f]
+        );
+    }
+}
@@ -0,0 +1,271 @@
+use crate::{asm::octo, chip8::json::Json, display::palette::parse_color};
+
+/// Both GIF versions start with this, ROMs practically never do
+const GIF_SIGNATURE: &[u8] = b"GIF8";
+
+/// Payload bits stored in the low bits of every pixel's palette index
+const BITS_PER_PIXEL: u32 = 2;
+
+/// Octo cartridge: a GIF label with the program source and its options
+/// hidden in the palette indices of its pixels.
+///
+/// The payload is a 32 bit big endian length followed by that many bytes of
+/// JSON, `{"program": "<octo source>", "options": {...}}`, spread over the
+/// pixels of every frame, two bits per pixel, most significant bits first.
+pub struct Cartridge {
+    pub rom: Vec<u8>,
+    pub options: Options,
+}
+
+/// Cartridge options this interpreter reads, absent ones keep the current setting.
+///
+/// Only the quirks in [`Options::unsupported_quirks`] are not emulated. Key
+/// bindings are not read, the keypad keeps its default layout whatever the
+/// cartridge holds.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Options {
+    /// Instructions per frame
+    pub tickrate: Option<u32>,
+    /// Shifts ignore vy, as on CHIP-48
+    pub shift_quirks: Option<bool>,
+    /// Sprites are clipped at the screen edges rather than wrapped
+    pub clip_quirks: Option<bool>,
+    /// 8XY1, 8XY2 and 8XY3 reset vF
+    pub logic_quirks: Option<bool>,
+    /// Arithmetic writes its result after vF
+    pub vf_order_quirks: Option<bool>,
+    /// BNNN jumps to NNN plus vX rather than v0
+    pub jump_quirks: Option<bool>,
+    /// FX55 leaves I unchanged, as on CHIP-48
+    pub load_store_quirks: Option<bool>,
+    pub fill_color: Option<u32>,
+    pub fill_color2: Option<u32>,
+    pub blend_color: Option<u32>,
+    pub background_color: Option<u32>,
+}
+
+/// Returns true if `file` looks like a cartridge rather than a raw ROM
+pub fn is_cartridge(file: &[u8]) -> bool {
+    file.starts_with(GIF_SIGNATURE)
+}
+
+impl Cartridge {
+    pub fn decode(file: &[u8]) -> Result<Self, String> {
+        let payload = String::from_utf8(payload(file)?)
+            .map_err(|_| "Cartridge payload is not UTF-8".to_string())?;
+        let json = Json::parse(&payload)?;
+
+        let source = match json.get("program") {
+            Some(Json::String(source)) => source,
+            _ => return Err("Cartridge has no program".to_string()),
+        };
+        let rom = octo::compile(source).map_err(|e| format!("Cartridge program: {}", e))?;
+        let options = match json.get("options") {
+            Some(options) => Options::from_json(options)?,
+            None => Options::default(),
+        };
+
+        Ok(Cartridge { rom, options })
+    }
+}
+
+impl Options {
+    fn from_json(json: &Json) -> Result<Self, String> {
+        let color = |name| match json.get(name) {
+            Some(Json::String(color)) => parse_color(color)
+                .map(Some)
+                .ok_or_else(|| format!("Invalid {}: {}", name, color)),
+            _ => Ok(None),
+        };
+        let quirk = |name| match json.get(name) {
+            Some(Json::Bool(quirks)) => Some(*quirks),
+            _ => None,
+        };
+
+        Ok(Options {
+            tickrate: match json.get("tickrate") {
+                Some(Json::Number(tickrate)) if *tickrate >= 1.0 => Some(*tickrate as u32),
+                Some(tickrate) => return Err(format!("Invalid tickrate: {:?}", tickrate)),
+                None => None,
+            },
+            shift_quirks: quirk("shiftQuirks"),
+            clip_quirks: quirk("clipQuirks"),
+            logic_quirks: quirk("logicQuirks"),
+            vf_order_quirks: quirk("vfOrderQuirks"),
+            jump_quirks: quirk("jumpQuirks"),
+            load_store_quirks: quirk("loadStoreQuirks"),
+            fill_color: color("fillColor")?,
+            fill_color2: color("fillColor2")?,
+            blend_color: color("blendColor")?,
+            background_color: color("backgroundColor")?,
+        })
+    }
+
+    /// Describes every quirk the cartridge asks for that the interpreter doesn't emulate
+    pub fn unsupported_quirks(&self) -> Vec<&'static str> {
+        [
+            (
+                self.clip_quirks == Some(false),
+                "clipQuirks is off, but sprites are always clipped at the screen edges",
+            ),
+            (
+                self.logic_quirks == Some(true),
+                "logicQuirks is on, but 8XY1, 8XY2 and 8XY3 never reset vF",
+            ),
+            (
+                self.vf_order_quirks == Some(true),
+                "vfOrderQuirks is on, but 8XY4, 8XY5 and 8XY7 always write vF last",
+            ),
+            (
+                self.jump_quirks == Some(true),
+                "jumpQuirks is on, but BNNN is not supported",
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(unsupported, quirk)| unsupported.then_some(quirk))
+        .collect()
+    }
+}
+
+/// Extracts the payload from the low bits of every pixel
+fn payload(file: &[u8]) -> Result<Vec<u8>, String> {
+    let invalid = |e: gif::DecodingError| format!("Invalid cartridge image: {}", e);
+
+    let mut options = gif::DecodeOptions::new();
+    options.set_color_output(gif::ColorOutput::Indexed);
+    let mut decoder = options.read_info(file).map_err(invalid)?;
+
+    let pixels_per_byte = u8::BITS / BITS_PER_PIXEL;
+    let mut bytes = vec![];
+    let mut byte = 0u8;
+    let mut bits = 0;
+    while let Some(frame) = decoder.read_next_frame().map_err(invalid)? {
+        for index in frame.buffer.iter() {
+            byte = byte << BITS_PER_PIXEL | index & ((1 << BITS_PER_PIXEL) - 1);
+            bits += 1;
+            if bits == pixels_per_byte {
+                bytes.push(byte);
+                bits = 0;
+            }
+        }
+    }
+
+    let truncated = || "Cartridge payload is truncated".to_string();
+    let len = bytes
+        .first_chunk::<4>()
+        .map(|len| u32::from_be_bytes(*len) as usize)
+        .ok_or_else(truncated)?;
+    bytes
+        .get(4..4 + len)
+        .map(<[u8]>::to_vec)
+        .ok_or_else(truncated)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use rstest::*;
+
+    /// Builds a cartridge with the payload in a single 64 pixel wide frame
+    pub fn encode_cartridge(json: &str) -> Vec<u8> {
+        let mut payload = (json.len() as u32).to_be_bytes().to_vec();
+        payload.extend(json.as_bytes());
+
+        let mut pixels = payload
+            .iter()
+            .flat_map(|byte| (0..4).rev().map(move |pair| byte >> (pair * 2) & 0b11))
+            .collect::<Vec<_>>();
+        let height = pixels.len().div_ceil(64);
+        pixels.resize(64 * height, 0);
+
+        // Four label colours, each repeated for every value of the payload bits
+        let palette = [0x00, 0x40, 0x80, 0xc0]
+            .iter()
+            .flat_map(|shade| [*shade; 3 * 4])
+            .collect::<Vec<u8>>();
+        let mut gif = vec![];
+        let mut encoder = gif::Encoder::new(&mut gif, 64, height as u16, &palette).unwrap();
+        encoder
+            .write_frame(&gif::Frame::from_indexed_pixels(
+                64,
+                height as u16,
+                pixels,
+                None,
+            ))
+            .unwrap();
+        drop(encoder);
+        gif
+    }
+
+    #[rstest]
+    fn test_decode() {
+        let cartridge = Cartridge::decode(&encode_cartridge(
+            r##"{"program": ": main\n\tclear # \"quoted\" é\n", "options": {"tickrate": 20, "shiftQuirks": true, "fillColor": "#FFCC00", "fillColor2": "#FF6600", "blendColor": "#662200", "backgroundColor": "#996600", "clipQuirks": false, "loadStoreQuirks": true, "maxSize": 3584}}"##,
+        ))
+        .unwrap();
+
+        assert_eq!(cartridge.rom, vec![0x00, 0xe0]);
+        assert_eq!(
+            cartridge.options,
+            Options {
+                tickrate: Some(20),
+                shift_quirks: Some(true),
+                clip_quirks: Some(false),
+                logic_quirks: None,
+                vf_order_quirks: None,
+                jump_quirks: None,
+                load_store_quirks: Some(true),
+                fill_color: Some(0xffcc00),
+                fill_color2: Some(0xff6600),
+                blend_color: Some(0x662200),
+                background_color: Some(0x996600),
+            }
+        );
+    }
+
+    #[rstest]
+    #[case::supported(r#"{"clipQuirks": true, "logicQuirks": false, "vfOrderQuirks": false, "jumpQuirks": false}"#, &[])]
+    #[case::unsupported(
+        r#"{"clipQuirks": false, "logicQuirks": true, "vfOrderQuirks": true, "jumpQuirks": true}"#,
+        &[
+            "clipQuirks is off, but sprites are always clipped at the screen edges",
+            "logicQuirks is on, but 8XY1, 8XY2 and 8XY3 never reset vF",
+            "vfOrderQuirks is on, but 8XY4, 8XY5 and 8XY7 always write vF last",
+            "jumpQuirks is on, but BNNN is not supported",
+        ]
+    )]
+    fn test_unsupported_quirks(#[case] options: &str, #[case] expected: &[&str]) {
+        let json = format!(r#"{{"program": ": main", "options": {}}}"#, options);
+        let cartridge = Cartridge::decode(&encode_cartridge(&json)).unwrap();
+        assert_eq!(cartridge.options.unsupported_quirks(), expected);
+    }
+
+    #[rstest]
+    fn test_decode_without_options() {
+        let cartridge = Cartridge::decode(&encode_cartridge(r#"{"program": ": main"}"#)).unwrap();
+        assert_eq!(cartridge.rom, vec![]);
+        assert_eq!(cartridge.options, Options::default());
+    }
+
+    #[rstest]
+    #[case::not_a_gif(b"GIF8 but not really", "Invalid cartridge image")]
+    #[case::no_program(&encode_cartridge("{}"), "Cartridge has no program")]
+    #[case::bad_program(&encode_cartridge(r#"{"program": "clear"}"#), "Cartridge program: Missing main label")]
+    #[case::bad_color(
+        &encode_cartridge(r#"{"program": ": main", "options": {"fillColor": "red"}}"#),
+        "Invalid fillColor: red"
+    )]
+    #[case::bad_json(&encode_cartridge(r#"{"program": ": main""#), "Invalid JSON: expected ',' or '}'")]
+    fn test_decode_errors(#[case] file: &[u8], #[case] error: &str) {
+        match Cartridge::decode(file) {
+            Ok(_) => panic!("Decoded an invalid cartridge"),
+            Err(e) => assert!(e.starts_with(error), "{}", e),
+        }
+    }
+
+    #[rstest]
+    fn test_is_cartridge() {
+        assert!(is_cartridge(&encode_cartridge("{}")));
+        assert!(!is_cartridge(&[0x00, 0xe0, 0x12, 0x00]));
+    }
+}
@@ -0,0 +1,253 @@
+use std::fmt;
+
+use crate::{chip8::Chip8, display::Display};
+
+/// Instruction write to memory that also runs as code, after which any
+/// decoded copy of the instruction at `addr` is stale
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CodeWrite {
+    /// Address of the instruction that wrote
+    pub writer: u16,
+    /// First written byte that runs as code
+    pub addr: u16,
+    /// The code had run before the write, rather than running after it
+    pub executed_before: bool,
+}
+
+impl fmt::Display for CodeWrite {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.executed_before {
+            write!(
+                f,
+                "Instruction at {:#06x} overwrote code at {:#06x} that already ran",
+                self.writer, self.addr
+            )
+        } else {
+            write!(
+                f,
+                "Code at {:#06x} runs after the instruction at {:#06x} wrote it",
+                self.addr, self.writer
+            )
+        }
+    }
+}
+
+/// Bytes executed and written so far, matched against each other on every
+/// instruction fetch and memory write
+pub(super) struct CodeWriteTracker {
+    /// Bytes fetched as instructions
+    executed: Vec<bool>,
+    /// Instruction that wrote each byte, until the byte is executed
+    writers: Vec<Option<u16>>,
+    /// Code writes not yet taken
+    events: Vec<CodeWrite>,
+}
+
+impl CodeWriteTracker {
+    fn new(memory_size: usize) -> Self {
+        CodeWriteTracker {
+            executed: vec![false; memory_size],
+            writers: vec![None; memory_size],
+            events: vec![],
+        }
+    }
+
+    fn execute(&mut self, pc: u16) {
+        // One event per fetch, even when a store wrote both bytes
+        let mut event = None;
+        for addr in [pc as usize, (pc as usize + 1) % self.executed.len()] {
+            self.executed[addr] = true;
+            if let Some(writer) = self.writers[addr].take() {
+                event.get_or_insert(CodeWrite {
+                    writer,
+                    addr: addr as u16,
+                    executed_before: false,
+                });
+            }
+        }
+        self.events.extend(event);
+    }
+
+    fn write(&mut self, writer: u16, addr: u16, len: u16) {
+        let range = addr as usize..(addr as usize + len as usize).min(self.executed.len());
+        if let Some(executed) = range.clone().find(|addr| self.executed[*addr]) {
+            self.events.push(CodeWrite {
+                writer,
+                addr: executed as u16,
+                executed_before: true,
+            });
+        }
+        for addr in range {
+            self.writers[addr] = Some(writer);
+        }
+    }
+}
+
+impl<D> Chip8<D>
+where
+    D: Display,
+{
+    /// Starts matching instruction fetches against instruction memory writes.
+    ///
+    /// [`Chip8::run`] prints the writes as warnings, steppers collect them with
+    /// [`Chip8::take_code_writes`].
+    pub fn track_code_writes(&mut self) {
+        self.code_writes = Some(CodeWriteTracker::new(self.memory.len()));
+    }
+
+    /// Returns and clears the code writes since the last call
+    pub fn take_code_writes(&mut self) -> Vec<CodeWrite> {
+        match &mut self.code_writes {
+            Some(tracker) => std::mem::take(&mut tracker.events),
+            None => vec![],
+        }
+    }
+
+    pub(super) fn track_execution(&mut self, pc: u16) {
+        if let Some(tracker) = &mut self.code_writes {
+            tracker.execute(pc);
+        }
+    }
+
+    /// Records a write of `len` bytes at `addr` by the executing instruction
+    pub(super) fn track_write(&mut self, addr: u16, len: u16) {
+        // pc has already moved past the executing instruction
+        let writer = u16::from(self.pc).wrapping_sub(2) & 0xfff;
+        if let Some(tracker) = &mut self.code_writes {
+            tracker.write(writer, addr, len);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{chip8::compat::Compatibility, display::test_display::TestDisplay};
+    use rstest::*;
+    use twelve_bit::u12::*;
+
+    /// Draws a sprite from its own code, then loops
+    static PROGRAM: [u8; 6] = [
+        0xa2, 0x00, // 0x200: i = 0x200
+        0xd0, 0x02, // 0x202: draw 2 rows at (v0, v0)
+        0x12, 0x02, // 0x204: jump to 0x202
+    ];
+
+    fn tracked() -> Chip8<TestDisplay> {
+        let mut chip8 = Chip8::new(TestDisplay::new(), Compatibility::Cosmac);
+        chip8.memory[0x200..0x206].copy_from_slice(&PROGRAM);
+        chip8.pc = u12![0x200];
+        chip8.track_code_writes();
+        chip8
+    }
+
+    #[rstest]
+    fn test_steps_mark_code_executed() {
+        let mut chip8 = tracked();
+        chip8.step().unwrap();
+        chip8.step().unwrap();
+
+        let tracker = chip8.code_writes.as_ref().unwrap();
+        assert_eq!(
+            tracker.executed[0x1ff..0x205],
+            [false, true, true, true, true, false]
+        );
+    }
+
+    #[rstest]
+    #[case::tracked(true)]
+    #[case::untracked(false)]
+    fn test_reading_code_is_not_a_write(#[case] tracking: bool) {
+        let mut chip8 = tracked();
+        if !tracking {
+            chip8.code_writes = None;
+        }
+        for _ in 0..4 {
+            chip8.step().unwrap();
+        }
+        assert_eq!(chip8.take_code_writes(), vec![]);
+    }
+
+    fn tracked_program(program: &[u8]) -> Chip8<TestDisplay> {
+        let mut chip8 = Chip8::new(TestDisplay::new(), Compatibility::Cosmac);
+        chip8.memory[0x200..0x200 + program.len()].copy_from_slice(program);
+        chip8.pc = u12![0x200];
+        chip8.track_code_writes();
+        chip8
+    }
+
+    #[rstest]
+    fn test_write_to_executed_code() {
+        let mut chip8 = tracked_program(&[
+            0x60, 0x00, // 0x200: v0 = 0
+            0xa2, 0x00, // 0x202: i = 0x200
+            0xf0, 0x33, // 0x204: bcd v0, over the first two instructions
+        ]);
+        for _ in 0..3 {
+            chip8.step().unwrap();
+        }
+
+        assert_eq!(
+            chip8.take_code_writes(),
+            vec![CodeWrite {
+                writer: 0x204,
+                addr: 0x200,
+                executed_before: true,
+            }]
+        );
+        assert_eq!(chip8.take_code_writes(), vec![]);
+    }
+
+    #[rstest]
+    fn test_code_executed_after_write() {
+        let mut chip8 = tracked_program(&[
+            0xa2, 0x08, // 0x200: i = 0x208
+            0x60, 0x00, // 0x202: v0 = 0x00
+            0x61, 0xe0, // 0x204: v1 = 0xe0
+            0xf1, 0x55, // 0x206: save v1, writing clear to 0x208
+        ]);
+        for _ in 0..4 {
+            chip8.step().unwrap();
+        }
+        assert_eq!(chip8.take_code_writes(), vec![]);
+
+        chip8.step().unwrap();
+
+        assert_eq!(
+            chip8.take_code_writes(),
+            vec![CodeWrite {
+                writer: 0x206,
+                addr: 0x208,
+                executed_before: false,
+            }]
+        );
+    }
+
+    #[rstest]
+    fn test_data_writes_are_ignored() {
+        let mut chip8 = tracked_program(&[
+            0xa3, 0x00, // 0x200: i = 0x300
+            0xf1, 0x55, // 0x202: save v1
+            0x12, 0x00, // 0x204: jump to 0x200
+        ]);
+        for _ in 0..6 {
+            chip8.step().unwrap();
+        }
+        assert_eq!(chip8.take_code_writes(), vec![]);
+    }
+
+    #[rstest]
+    #[case::before(
+        true,
+        "Instruction at 0x0200 overwrote code at 0x0203 that already ran"
+    )]
+    #[case::after(false, "Code at 0x0203 runs after the instruction at 0x0200 wrote it")]
+    fn test_display(#[case] executed_before: bool, #[case] expected: &str) {
+        let write = CodeWrite {
+            writer: 0x200,
+            addr: 0x203,
+            executed_before,
+        };
+        assert_eq!(write.to_string(), expected);
+    }
+}
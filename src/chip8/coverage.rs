@@ -0,0 +1,353 @@
+use std::collections::BTreeMap;
+
+use crate::{
+    chip8::{instruction::Chip8Instruction, state::fnv1a, Chip8},
+    disasm::{Disassembly, Style, ENTRY_POINT},
+    display::Display,
+};
+
+/// Executed instructions and skip directions of a ROM, merged over any number of runs.
+///
+/// In LCOV, line n is the instruction at ROM offset n - 1, as LCOV lines count
+/// from 1. Every skip is a branch with two directions, 0 falls through and 1 skips.
+/// The test name holds the ROM's hash, so only coverage of the same ROM merges.
+pub struct Coverage {
+    rom: Vec<u8>,
+    /// Executions by address
+    hits: BTreeMap<u16, u64>,
+    /// Executions of each skip that fell through and that skipped, by address
+    branches: BTreeMap<u16, [u64; 2]>,
+}
+
+fn is_skip(instruction: Chip8Instruction) -> bool {
+    matches!(
+        instruction,
+        Chip8Instruction::SkipIfEqual(..)
+            | Chip8Instruction::SkipIfNotEqual(..)
+            | Chip8Instruction::SkipIfEqualXY(..)
+            | Chip8Instruction::SkipIfNotEqualXY(..)
+    )
+}
+
+impl Coverage {
+    pub fn new(rom: Vec<u8>) -> Self {
+        Coverage {
+            rom,
+            hits: BTreeMap::new(),
+            branches: BTreeMap::new(),
+        }
+    }
+
+    /// Counts the instruction at `pc`, which left the program counter at `next`
+    fn record(&mut self, pc: u16, instruction: Chip8Instruction, next: u16) {
+        *self.hits.entry(pc).or_default() += 1;
+        if is_skip(instruction) {
+            let skipped = next == pc.wrapping_add(4) & 0xfff;
+            self.branches.entry(pc).or_default()[skipped as usize] += 1;
+        }
+    }
+
+    /// Addresses of the instructions worth covering: everything reachable and everything executed
+    fn instructions(&self) -> BTreeMap<u16, bool> {
+        let mut instructions = Disassembly::new(&self.rom)
+            .instructions()
+            .map(|(addr, instruction)| (addr, is_skip(instruction)))
+            .collect::<BTreeMap<_, _>>();
+        for addr in self.hits.keys() {
+            instructions
+                .entry(*addr)
+                .or_insert(self.branches.contains_key(addr));
+        }
+        instructions.retain(|addr, _| self.offset(*addr).is_some());
+        instructions
+    }
+
+    /// `TN` of the ROM's tracefiles, `rom_` followed by its FNV-1a hash
+    fn test_name(&self) -> String {
+        format!("rom_{:016x}", fnv1a(&self.rom))
+    }
+
+    fn offset(&self, addr: u16) -> Option<usize> {
+        let offset = addr.checked_sub(ENTRY_POINT)? as usize;
+        (offset < self.rom.len()).then_some(offset)
+    }
+
+    /// Tracefile for the ROM at `name`
+    pub fn lcov(&self, name: &str) -> String {
+        let instructions = self.instructions();
+        let mut lcov = format!("TN:{}\nSF:{}\n", self.test_name(), name);
+
+        let (mut found, mut hit) = (0, 0);
+        for (addr, _) in instructions.iter().filter(|(_, skip)| **skip) {
+            let line = self.offset(*addr).unwrap() + 1;
+            let executed = self.hits.contains_key(addr);
+            let counts = self.branches.get(addr).copied().unwrap_or_default();
+            for (branch, count) in counts.iter().enumerate() {
+                let taken = if executed {
+                    count.to_string()
+                } else {
+                    "-".to_string()
+                };
+                lcov.push_str(&format!("BRDA:{},0,{},{}\n", line, branch, taken));
+                found += 1;
+                hit += (*count > 0) as usize;
+            }
+        }
+        lcov.push_str(&format!("BRF:{}\nBRH:{}\n", found, hit));
+
+        for addr in instructions.keys() {
+            let line = self.offset(*addr).unwrap() + 1;
+            let count = self.hits.get(addr).copied().unwrap_or_default();
+            lcov.push_str(&format!("DA:{},{}\n", line, count));
+        }
+        let hit = instructions
+            .keys()
+            .filter(|addr| self.hits.contains_key(addr))
+            .count();
+        lcov.push_str(&format!(
+            "LF:{}\nLH:{}\nend_of_record\n",
+            instructions.len(),
+            hit
+        ));
+        lcov
+    }
+
+    /// Adds the counts of a tracefile written by [`Coverage::lcov`] for the same ROM
+    pub fn merge_lcov(&mut self, lcov: &str) -> Result<(), String> {
+        let test_name = self.test_name();
+        let mut same_rom = false;
+        for (n, line) in lcov.lines().enumerate() {
+            let error = |message: &str| format!("Line {}: {}", n + 1, message);
+            let Some((kind, fields)) = line.split_once(':') else {
+                continue;
+            };
+            let fields = fields.split(',').collect::<Vec<_>>();
+            let addr = |line: &str| {
+                line.parse::<usize>()
+                    .ok()
+                    .filter(|line| (1..=self.rom.len()).contains(line))
+                    .map(|line| ENTRY_POINT + line as u16 - 1)
+                    .ok_or_else(|| error("Line number outside the ROM"))
+            };
+            let count = |count: &str| match count {
+                "-" => Ok(0),
+                count => count.parse::<u64>().map_err(|_| error("Invalid count")),
+            };
+
+            match (kind, fields.as_slice()) {
+                ("TN", [name]) if *name == test_name => same_rom = true,
+                // Written before tracefiles held the hash
+                ("TN", [""]) => {}
+                ("TN", [name]) => {
+                    return Err(format!(
+                        "Coverage is of a different ROM: {}, expected {}",
+                        name, test_name
+                    ));
+                }
+                ("DA" | "BRDA", _) if !same_rom => {
+                    return Err(error("Coverage without the ROM's hash in TN"));
+                }
+                ("DA", [line, hits, ..]) => {
+                    let (addr, hits) = (addr(line)?, count(hits)?);
+                    if hits > 0 {
+                        *self.hits.entry(addr).or_default() += hits;
+                    }
+                }
+                ("BRDA", [line, _, branch @ ("0" | "1"), taken]) => {
+                    let (addr, taken) = (addr(line)?, count(taken)?);
+                    self.branches.entry(addr).or_default()[(*branch == "1") as usize] += taken;
+                }
+                ("BRDA", _) => return Err(error("Invalid branch")),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Executed and reachable instruction totals followed by the listing annotated
+    /// with every instruction's executions, `#####` marking those that never ran
+    pub fn listing(&self) -> String {
+        let instructions = self.instructions();
+        let percent = |hit: usize, found: usize| 100.0 * hit as f64 / found.max(1) as f64;
+
+        let lines_hit = instructions
+            .keys()
+            .filter(|addr| self.hits.contains_key(addr))
+            .count();
+        let branches_found = 2 * instructions.values().filter(|skip| **skip).count();
+        let branches_hit = self
+            .branches
+            .values()
+            .flatten()
+            .filter(|count| **count > 0)
+            .count();
+        let mut listing = format!(
+            "Instructions executed: {}/{} ({:.1}%)\nSkip directions taken: {}/{} ({:.1}%)\n\n",
+            lines_hit,
+            instructions.len(),
+            percent(lines_hit, instructions.len()),
+            branches_hit,
+            branches_found,
+            percent(branches_hit, branches_found)
+        );
+
+        let disassembly = Disassembly::new(&self.rom);
+        listing.push_str(&disassembly.annotated_listing(Style::Octo, 24, |addr| {
+            let hits = match self.hits.get(&addr) {
+                Some(hits) => hits.to_string(),
+                None => "#####".to_string(),
+            };
+            let branches = match (instructions.get(&addr), self.branches.get(&addr)) {
+                (Some(true), counts) => {
+                    let [fell_through, skipped] = counts.copied().unwrap_or_default();
+                    format!("skipped {}/{}", skipped, fell_through + skipped)
+                }
+                _ => String::new(),
+            };
+            Some(format!("{:>8}  {:<14}", hits, branches))
+        }));
+        listing
+    }
+}
+
+impl<D> Chip8<D>
+where
+    D: Display,
+{
+    /// Records the coverage of the loaded ROM from now on
+    pub fn start_coverage(&mut self) {
+        self.coverage = Some(Coverage::new(self.rom().to_vec()));
+    }
+
+    /// Coverage recorded since [`Chip8::start_coverage`]
+    pub fn take_coverage(&mut self) -> Option<Coverage> {
+        self.coverage.take()
+    }
+
+    pub(super) fn cover(&mut self, pc: u16, instruction: Chip8Instruction) {
+        let next = u16::from(self.pc);
+        if let Some(coverage) = &mut self.coverage {
+            coverage.record(pc, instruction, next);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{chip8::compat::Compatibility, display::test_display::TestDisplay};
+    use rstest::*;
+    use twelve_bit::u12::*;
+
+    /// Counts v1 up to 2 and stops, leaving the reset unreached
+    static PROGRAM: [u8; 10] = [
+        0x71, 0x01, // 0x200: v1 += 1
+        0x31, 0x02, // 0x202: skip if v1 == 2
+        0x12, 0x00, // 0x204: loop
+        0x12, 0x06, // 0x206: halt
+        0x61, 0x00, // 0x208: v1 = 0, unreachable
+    ];
+
+    fn covered(steps: usize) -> Coverage {
+        let mut chip8 = Chip8::new(TestDisplay::new(), Compatibility::Cosmac);
+        chip8.memory[0x200..0x20a].copy_from_slice(&PROGRAM);
+        chip8.rom_len = PROGRAM.len();
+        chip8.pc = u12![0x200];
+        chip8.start_coverage();
+
+        for _ in 0..steps {
+            chip8.step().unwrap();
+        }
+        chip8.take_coverage().unwrap()
+    }
+
+    #[rstest]
+    fn test_record() {
+        let coverage = covered(7);
+        assert_eq!(
+            coverage.hits.into_iter().collect::<Vec<_>>(),
+            vec![(0x200, 2), (0x202, 2), (0x204, 1), (0x206, 2)]
+        );
+        assert_eq!(
+            coverage.branches.into_iter().collect::<Vec<_>>(),
+            vec![(0x202, [1, 1])]
+        );
+    }
+
+    #[rstest]
+    fn test_lcov() {
+        assert_eq!(
+            covered(4).lcov("roms/count.ch8"),
+            "TN:rom_601a3a2a02c3551b
+SF:roms/count.ch8
+BRDA:3,0,0,1
+BRDA:3,0,1,0
+BRF:2
+BRH:1
+DA:1,2
+DA:3,1
+DA:5,1
+DA:7,0
+LF:4
+LH:3
+end_of_record
+"
+        );
+    }
+
+    #[rstest]
+    fn test_merge_lcov() {
+        let mut coverage = covered(4);
+        coverage.merge_lcov(&covered(7).lcov("count.ch8")).unwrap();
+
+        assert_eq!(coverage.hits.get(&0x200), Some(&4));
+        assert_eq!(coverage.hits.get(&0x206), Some(&2));
+        assert_eq!(coverage.branches.get(&0x202), Some(&[2, 1]));
+    }
+
+    #[rstest]
+    #[case::other_rom(
+        "TN:rom_0000000000000000",
+        "Coverage is of a different ROM: rom_0000000000000000, expected rom_601a3a2a02c3551b"
+    )]
+    #[case::no_hash(
+        "TN:\nSF:count.ch8\nDA:1,1",
+        "Line 3: Coverage without the ROM's hash in TN"
+    )]
+    #[case::outside_rom(
+        "TN:rom_601a3a2a02c3551b\nDA:11,1",
+        "Line 2: Line number outside the ROM"
+    )]
+    #[case::bad_count("TN:rom_601a3a2a02c3551b\nDA:1,lots", "Line 2: Invalid count")]
+    #[case::bad_branch("TN:rom_601a3a2a02c3551b\nBRDA:3,0,2,1", "Line 2: Invalid branch")]
+    fn test_merge_lcov_errors(#[case] lcov: &str, #[case] error: &str) {
+        assert_eq!(covered(0).merge_lcov(lcov), Err(error.to_string()));
+    }
+
+    #[rstest]
+    fn test_merge_lcov_of_same_named_rom() {
+        // Another count.ch8, differing in its last byte
+        let mut other = Coverage::new(PROGRAM[..9].to_vec());
+        other.record(0x200, Chip8Instruction::AddVX(1, 1), 0x202);
+
+        assert!(covered(0).merge_lcov(&other.lcov("count.ch8")).is_err());
+    }
+
+    #[rstest]
+    fn test_listing() {
+        assert_eq!(
+            covered(7).listing(),
+            "Instructions executed: 4/4 (100.0%)
+Skip directions taken: 2/2 (100.0%)
+
+                        : main
+       2                    v1 += 0x01
+       2  skipped 1/2       if v1 != 0x02 then
+       1                    jump main
+                        : label_206
+       2                    jump label_206
+                            0x61 0x00
+"
+        );
+    }
+}
@@ -0,0 +1,169 @@
+use crate::{
+    chip8::{instruction::Chip8Instruction, Chip8},
+    display::Display,
+};
+use twelve_bit::u12::*;
+
+/// Stepping and inspection API used by the debugger
+impl<D> Chip8<D>
+where
+    D: Display,
+{
+    /// Executes the instruction at pc and returns it.
+    ///
+    /// pc is left on an instruction that can't be decoded.
+    pub fn step(&mut self) -> Result<Chip8Instruction, String> {
+        let pc = self.pc;
+        let code = self.fetch();
+        let instruction = self.decode(code).inspect_err(|_| self.pc = pc)?;
+
+        self.track_execution(pc.into());
+        let trace = self.begin_trace(pc.into());
+        self.execute(instruction);
+        if let Some(trace) = trace {
+            self.end_trace(trace, pc.into(), code, instruction);
+        }
+        self.profile(pc.into(), instruction);
+        self.cover(pc.into(), instruction);
+
+        Ok(instruction)
+    }
+
+    /// Presents the framebuffer and samples the keypad, once per frame while stepping
+    pub fn sync_display(&mut self) {
+        self.render_buffer();
+        self.end_frame_sound();
+        self.keypad = self.display.keypad();
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.display.is_open()
+    }
+
+    pub fn cycles_per_frame(&self) -> u32 {
+        self.cycles_per_frame
+    }
+
+    pub fn pc(&self) -> u16 {
+        u16::from(self.pc)
+    }
+
+    pub fn set_pc(&mut self, addr: u16) -> Result<(), String> {
+        match addr {
+            0..=0xfff => {
+                self.pc = u12![addr];
+                Ok(())
+            }
+            _ => Err(format!("Address out of range: {:#06x}", addr)),
+        }
+    }
+
+    pub fn i_reg(&self) -> u16 {
+        self.i_reg
+    }
+
+    pub fn set_i_reg(&mut self, value: u16) -> Result<(), String> {
+        match value {
+            0..=0xfff => {
+                self.i_reg = value;
+                Ok(())
+            }
+            _ => Err(format!("Address out of range: {:#06x}", value)),
+        }
+    }
+
+    pub fn v_reg(&self) -> &[u8] {
+        &self.v_reg
+    }
+
+    pub fn set_v_reg(&mut self, x: usize, value: u8) {
+        self.v_reg[x] = value;
+    }
+
+    /// Return addresses, innermost call last
+    pub fn stack(&self) -> Vec<u16> {
+        self.stack.iter().map(|addr| u16::from(*addr)).collect()
+    }
+
+    pub fn stack_depth(&self) -> usize {
+        self.stack.len()
+    }
+
+    pub fn memory(&self) -> &[u8] {
+        &self.memory
+    }
+
+    pub fn write_memory(&mut self, addr: u16, bytes: &[u8]) -> Result<(), String> {
+        let start = addr as usize;
+        match self.memory.get_mut(start..start + bytes.len()) {
+            Some(memory) => {
+                memory.copy_from_slice(bytes);
+                Ok(())
+            }
+            None => Err(format!(
+                "Write of {} bytes at {:#06x} is out of memory",
+                bytes.len(),
+                addr
+            )),
+        }
+    }
+
+    /// Instruction at `addr`, None if it doesn't decode
+    pub fn instruction_at(&self, addr: u16) -> Option<Chip8Instruction> {
+        let code = u16::from_be_bytes([
+            *self.memory.get(addr as usize)?,
+            *self.memory.get(addr as usize + 1)?,
+        ]);
+        Chip8Instruction::decode(code)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{chip8::compat::Compatibility, display::test_display::TestDisplay};
+    use rstest::*;
+
+    #[rstest]
+    fn test_step_executes_one_instruction() {
+        let mut chip8 = get_test_chip8(&[0x60, 0x42, 0x22, 0x08]);
+
+        assert_eq!(chip8.step(), Ok(Chip8Instruction::SetVX(0, 0x42)));
+        assert_eq!(chip8.v_reg()[0], 0x42);
+        assert_eq!(chip8.pc(), 0x202);
+
+        assert_eq!(chip8.step(), Ok(Chip8Instruction::Call(u12![0x208])));
+        assert_eq!(chip8.pc(), 0x208);
+        assert_eq!(chip8.stack(), vec![0x204]);
+    }
+
+    #[rstest]
+    fn test_step_stays_on_invalid_instruction() {
+        let mut chip8 = get_test_chip8(&[0xff, 0xff]);
+
+        assert_eq!(
+            chip8.step(),
+            Err("Invalid instruction at 0x0200: 0xffff".to_string())
+        );
+        assert_eq!(chip8.pc(), 0x200);
+    }
+
+    #[rstest]
+    #[case::in_range(0xffe, &[1, 2], true)]
+    #[case::out_of_range(0xfff, &[1, 2], false)]
+    fn test_write_memory(#[case] addr: u16, #[case] bytes: &[u8], #[case] ok: bool) {
+        let mut chip8 = get_test_chip8(&[]);
+
+        assert_eq!(chip8.write_memory(addr, bytes).is_ok(), ok);
+        if ok {
+            assert_eq!(&chip8.memory()[addr as usize..], bytes);
+        }
+    }
+
+    fn get_test_chip8(program: &[u8]) -> Chip8<TestDisplay> {
+        let mut chip8 = Chip8::new(TestDisplay::new(), Compatibility::Cosmac);
+        chip8.memory[0x200..0x200 + program.len()].copy_from_slice(program);
+        chip8.pc = u12![0x200];
+        chip8
+    }
+}
@@ -9,59 +9,148 @@ where
     D: Display,
 {
     pub(super) fn decode(&mut self, code: u16) -> Result<Chip8Instruction, String> {
-        if let Ok(inst) = Chip8::<D>::_decode(code) {
+        if let Some(inst) = Chip8Instruction::decode(code) {
             Ok(inst)
         } else {
             Err(self.bad_instruction(code))
         }
     }
 
-    fn _decode(code: u16) -> Result<Chip8Instruction, ()> {
+    fn bad_instruction(&self, code: u16) -> String {
+        format!(
+            "Invalid instruction at {:#06x}: {:#06x}",
+            u16::from(self.pc - u12![2]),
+            code
+        )
+    }
+}
+
+impl Chip8Instruction {
+    /// Decodes a single opcode, independently of any machine state
+    pub fn decode(code: u16) -> Option<Chip8Instruction> {
         let x = ((code & 0x0f00) >> 8) as u8;
         let y = ((code & 0x00f0) >> 4) as u8;
         let n = (code & 0x000f) as u8;
         let nn = (code & 0x00ff) as u8;
-        let nnn = (code & 0x0fff) as u16;
+        let nnn = code & 0x0fff;
 
         match code & 0xf000 {
             0x0000 => match nnn {
-                0x0e0 => Ok(Chip8Instruction::ClearScreen()),
-                0x0ee => Ok(Chip8Instruction::Return()),
-                _ => Err(()),
+                0x0e0 => Some(Chip8Instruction::ClearScreen()),
+                0x0ee => Some(Chip8Instruction::Return()),
+                _ => None,
             },
-            0x1000 => Ok(Chip8Instruction::Jump(u12![nnn])),
-            0x2000 => Ok(Chip8Instruction::Call(u12![nnn])),
-            0x3000 => Ok(Chip8Instruction::SkipIfEqual(x, nn)),
-            0x4000 => Ok(Chip8Instruction::SkipIfNotEqual(x, nn)),
-            0x5000 => Ok(Chip8Instruction::SkipIfEqualXY(x, y)),
-            0x6000 => Ok(Chip8Instruction::SetVX(x, nn)),
-            0x7000 => Ok(Chip8Instruction::AddVX(x, nn)),
+            0x1000 => Some(Chip8Instruction::Jump(u12![nnn])),
+            0x2000 => Some(Chip8Instruction::Call(u12![nnn])),
+            0x3000 => Some(Chip8Instruction::SkipIfEqual(x, nn)),
+            0x4000 => Some(Chip8Instruction::SkipIfNotEqual(x, nn)),
+            0x5000 => Some(Chip8Instruction::SkipIfEqualXY(x, y)),
+            0x6000 => Some(Chip8Instruction::SetVX(x, nn)),
+            0x7000 => Some(Chip8Instruction::AddVX(x, nn)),
             0x8000 => match n {
-                0x0 => Ok(Chip8Instruction::SetVXToVY(x, y)),
-                0x1 => Ok(Chip8Instruction::OrVXVY(x, y)),
-                0x2 => Ok(Chip8Instruction::AndVXVY(x, y)),
-                0x3 => Ok(Chip8Instruction::XorVXVY(x, y)),
-                0x4 => Ok(Chip8Instruction::AddVYRegisterToVX(x, y)),
-                0x5 => Ok(Chip8Instruction::SubVYFromVX(x, y)),
-                0x6 => Ok(Chip8Instruction::ShiftVXRight(x, y)),
-                0x7 => Ok(Chip8Instruction::SubVXFromVY(x, y)),
-                0xE => Ok(Chip8Instruction::ShiftVXLeft(x, y)),
-                _ => Err(()),
+                0x0 => Some(Chip8Instruction::SetVXToVY(x, y)),
+                0x1 => Some(Chip8Instruction::OrVXVY(x, y)),
+                0x2 => Some(Chip8Instruction::AndVXVY(x, y)),
+                0x3 => Some(Chip8Instruction::XorVXVY(x, y)),
+                0x4 => Some(Chip8Instruction::AddVYRegisterToVX(x, y)),
+                0x5 => Some(Chip8Instruction::SubVYFromVX(x, y)),
+                0x6 => Some(Chip8Instruction::ShiftVXRight(x, y)),
+                0x7 => Some(Chip8Instruction::SubVXFromVY(x, y)),
+                0xE => Some(Chip8Instruction::ShiftVXLeft(x, y)),
+                _ => None,
             },
-            0x9000 => Ok(Chip8Instruction::SkipIfNotEqualXY(x, y)),
+            0x9000 => Some(Chip8Instruction::SkipIfNotEqualXY(x, y)),
 
-            0xA000 => Ok(Chip8Instruction::SetIRegister(nnn)),
-            0xD000 => Ok(Chip8Instruction::Draw(x, y, n)),
-            _ => Err(()),
+            0xA000 => Some(Chip8Instruction::SetIRegister(nnn)),
+            0xD000 => Some(Chip8Instruction::Draw(x, y, n)),
+            0xF000 => match nn {
+                0x02 if x == 0 => Some(Chip8Instruction::LoadAudioPattern()),
+                0x18 => Some(Chip8Instruction::SetSoundTimer(x)),
+                0x33 => Some(Chip8Instruction::StoreBCD(x)),
+                0x3a => Some(Chip8Instruction::SetPitch(x)),
+                0x55 => Some(Chip8Instruction::StoreRegisters(x)),
+                _ => None,
+            },
+            _ => None,
         }
     }
 
-    fn bad_instruction(&self, code: u16) -> String {
-        format!(
-            "Invalid instruction at {:#06x}: {:#06x}",
-            u16::from(self.pc - u12![2]),
-            code
-        )
+    /// Decodes a single opcode only if it is the one the instruction encodes to.
+    ///
+    /// The interpreter ignores the low nibble of 5XY0 and 9XY0, as the COSMAC
+    /// VIP did, so [`Chip8Instruction::decode`] accepts any. Listings use this
+    /// instead, so bytes that don't assemble back to themselves stay data.
+    pub fn decode_exact(code: u16) -> Option<Chip8Instruction> {
+        Chip8Instruction::decode(code).filter(|instruction| instruction.encode() == code)
+    }
+
+    /// Opcode of the instruction, the inverse of [`Chip8Instruction::decode_exact`]
+    pub fn encode(&self) -> u16 {
+        let xy = |opcode: u16, x: u8, y: u8, n: u16| {
+            opcode | (x as u16 & 0xf) << 8 | (y as u16 & 0xf) << 4 | n
+        };
+        let xnn = |opcode: u16, x: u8, nn: u8| opcode | (x as u16 & 0xf) << 8 | nn as u16;
+
+        match *self {
+            Chip8Instruction::ClearScreen() => 0x00e0,
+            Chip8Instruction::Return() => 0x00ee,
+            Chip8Instruction::Jump(nnn) => 0x1000 | u16::from(nnn),
+            Chip8Instruction::Call(nnn) => 0x2000 | u16::from(nnn),
+            Chip8Instruction::SkipIfEqual(x, nn) => xnn(0x3000, x, nn),
+            Chip8Instruction::SkipIfNotEqual(x, nn) => xnn(0x4000, x, nn),
+            Chip8Instruction::SkipIfEqualXY(x, y) => xy(0x5000, x, y, 0x0),
+            Chip8Instruction::SetVX(x, nn) => xnn(0x6000, x, nn),
+            Chip8Instruction::AddVX(x, nn) => xnn(0x7000, x, nn),
+            Chip8Instruction::SetVXToVY(x, y) => xy(0x8000, x, y, 0x0),
+            Chip8Instruction::OrVXVY(x, y) => xy(0x8000, x, y, 0x1),
+            Chip8Instruction::AndVXVY(x, y) => xy(0x8000, x, y, 0x2),
+            Chip8Instruction::XorVXVY(x, y) => xy(0x8000, x, y, 0x3),
+            Chip8Instruction::AddVYRegisterToVX(x, y) => xy(0x8000, x, y, 0x4),
+            Chip8Instruction::SubVYFromVX(x, y) => xy(0x8000, x, y, 0x5),
+            Chip8Instruction::ShiftVXRight(x, y) => xy(0x8000, x, y, 0x6),
+            Chip8Instruction::SubVXFromVY(x, y) => xy(0x8000, x, y, 0x7),
+            Chip8Instruction::ShiftVXLeft(x, y) => xy(0x8000, x, y, 0xe),
+            Chip8Instruction::SkipIfNotEqualXY(x, y) => xy(0x9000, x, y, 0x0),
+            Chip8Instruction::SetIRegister(nnn) => 0xa000 | (nnn & 0xfff),
+            Chip8Instruction::Draw(x, y, n) => xy(0xd000, x, y, n as u16 & 0xf),
+            Chip8Instruction::LoadAudioPattern() => 0xf002,
+            Chip8Instruction::SetSoundTimer(x) => xnn(0xf000, x, 0x18),
+            Chip8Instruction::SetPitch(x) => xnn(0xf000, x, 0x3a),
+            Chip8Instruction::StoreBCD(x) => xnn(0xf000, x, 0x33),
+            Chip8Instruction::StoreRegisters(x) => xnn(0xf000, x, 0x55),
+        }
+    }
+
+    /// Opcode pattern the instruction is an instance of, e.g. `8XY4`
+    pub fn pattern(&self) -> &'static str {
+        match self {
+            Chip8Instruction::ClearScreen() => "00E0",
+            Chip8Instruction::Return() => "00EE",
+            Chip8Instruction::Jump(_) => "1NNN",
+            Chip8Instruction::Call(_) => "2NNN",
+            Chip8Instruction::SkipIfEqual(..) => "3XNN",
+            Chip8Instruction::SkipIfNotEqual(..) => "4XNN",
+            Chip8Instruction::SkipIfEqualXY(..) => "5XY0",
+            Chip8Instruction::SetVX(..) => "6XNN",
+            Chip8Instruction::AddVX(..) => "7XNN",
+            Chip8Instruction::SetVXToVY(..) => "8XY0",
+            Chip8Instruction::OrVXVY(..) => "8XY1",
+            Chip8Instruction::AndVXVY(..) => "8XY2",
+            Chip8Instruction::XorVXVY(..) => "8XY3",
+            Chip8Instruction::AddVYRegisterToVX(..) => "8XY4",
+            Chip8Instruction::SubVYFromVX(..) => "8XY5",
+            Chip8Instruction::ShiftVXRight(..) => "8XY6",
+            Chip8Instruction::SubVXFromVY(..) => "8XY7",
+            Chip8Instruction::ShiftVXLeft(..) => "8XYE",
+            Chip8Instruction::SkipIfNotEqualXY(..) => "9XY0",
+            Chip8Instruction::SetIRegister(_) => "ANNN",
+            Chip8Instruction::Draw(..) => "DXYN",
+            Chip8Instruction::LoadAudioPattern() => "F002",
+            Chip8Instruction::SetSoundTimer(_) => "FX18",
+            Chip8Instruction::SetPitch(_) => "FX3A",
+            Chip8Instruction::StoreBCD(_) => "FX33",
+            Chip8Instruction::StoreRegisters(_) => "FX55",
+        }
     }
 }
 
@@ -92,11 +181,49 @@ mod tests {
     #[case::shift_vx_left(0x812E, Chip8Instruction::ShiftVXLeft(1, 2))]
     #[case::set_i_register(0xa123, Chip8Instruction::SetIRegister(0x123))]
     #[case::draw(0xd123, Chip8Instruction::Draw(1, 2, 3))]
+    #[case::load_audio_pattern(0xf002, Chip8Instruction::LoadAudioPattern())]
+    #[case::set_pitch(0xf13a, Chip8Instruction::SetPitch(1))]
+    #[case::set_sound_timer(0xf118, Chip8Instruction::SetSoundTimer(1))]
+    #[case::store_bcd(0xf133, Chip8Instruction::StoreBCD(1))]
+    #[case::store_registers(0xf155, Chip8Instruction::StoreRegisters(1))]
     fn test_decode_success(#[case] input: u16, #[case] expected: Chip8Instruction) {
         let mut chip8 = get_test_chip8();
         assert_eq!(expected, chip8.decode(input).unwrap());
     }
 
+    #[rstest]
+    #[case::sys(0x0123)]
+    #[case::arithmetic(0x8128)]
+    #[case::random(0xc123)]
+    #[case::load_registers(0xf165)]
+    #[case::audio_with_register(0xf102)]
+    fn test_decode_failure(#[case] input: u16) {
+        assert_eq!(Chip8Instruction::decode(input), None);
+    }
+
+    #[rstest]
+    #[case::skip_if_equal_xy(0x5120, Some(Chip8Instruction::SkipIfEqualXY(1, 2)))]
+    #[case::skip_if_equal_xy_low_nibble(0x5123, None)]
+    #[case::skip_if_not_equal_xy_low_nibble(0x9123, None)]
+    fn test_decode_exact(#[case] input: u16, #[case] expected: Option<Chip8Instruction>) {
+        assert_eq!(Chip8Instruction::decode_exact(input), expected);
+    }
+
+    #[rstest]
+    fn test_encode_inverts_decode_for_every_opcode() {
+        for code in 0..=u16::MAX {
+            if let Some(instruction) = Chip8Instruction::decode(code) {
+                assert_eq!(
+                    Chip8Instruction::decode(instruction.encode()),
+                    Some(instruction)
+                );
+            }
+            if let Some(instruction) = Chip8Instruction::decode_exact(code) {
+                assert_eq!(instruction.encode(), code, "{:#06x}", code);
+            }
+        }
+    }
+
     fn get_test_chip8() -> Chip8<TestDisplay> {
         Chip8::new(TestDisplay::new(), Compatibility::Cosmac)
     }
@@ -1,5 +1,5 @@
 use crate::{
-    chip8::{compat::Compatibility, instruction::Chip8Instruction, Chip8},
+    chip8::{compat::Compatibility, instruction::Chip8Instruction, watch::Access, Chip8},
     display::Display,
 };
 
@@ -8,12 +8,8 @@ where
     D: Display,
 {
     pub(super) fn execute(&mut self, instruction: Chip8Instruction) {
-        println!("{}", instruction);
         match instruction {
-            Chip8Instruction::ClearScreen() => {
-                let display_size = self.display.get_size();
-                self.display_buffer = vec![false; display_size.0 * display_size.1];
-            }
+            Chip8Instruction::ClearScreen() => self.display_buffer.clear(),
             Chip8Instruction::Jump(nnn) => self.pc = nnn,
             Chip8Instruction::Return() => {
                 let popped = self.stack.pop();
@@ -56,13 +52,13 @@ where
                 self.v_reg[x as usize] = self.v_reg[y as usize];
             }
             Chip8Instruction::OrVXVY(x, y) => {
-                self.v_reg[x as usize] = self.v_reg[x as usize] | self.v_reg[y as usize];
+                self.v_reg[x as usize] |= self.v_reg[y as usize];
             }
             Chip8Instruction::AndVXVY(x, y) => {
-                self.v_reg[x as usize] = self.v_reg[x as usize] & self.v_reg[y as usize];
+                self.v_reg[x as usize] &= self.v_reg[y as usize];
             }
             Chip8Instruction::XorVXVY(x, y) => {
-                self.v_reg[x as usize] = self.v_reg[x as usize] ^ self.v_reg[y as usize];
+                self.v_reg[x as usize] ^= self.v_reg[y as usize];
             }
             Chip8Instruction::AddVYRegisterToVX(x, y) => {
                 let (result, overflow) =
@@ -91,7 +87,7 @@ where
                 }
                 Compatibility::Chip48 => {
                     self.v_reg[0xf] = self.v_reg[x as usize] & 0x1;
-                    self.v_reg[x as usize] = self.v_reg[x as usize] >> 1;
+                    self.v_reg[x as usize] >>= 1;
                 }
             },
             Chip8Instruction::ShiftVXLeft(x, y) => match self.compatibility {
@@ -101,68 +97,75 @@ where
                 }
                 Compatibility::Chip48 => {
                     self.v_reg[0xf] = (self.v_reg[x as usize] & 0x80) >> 7;
-                    self.v_reg[x as usize] = self.v_reg[x as usize] << 1;
+                    self.v_reg[x as usize] <<= 1;
                 }
             },
             Chip8Instruction::SetIRegister(nnn) => self.i_reg = nnn,
             Chip8Instruction::Draw(vx, vy, n) => {
-                let display_size = self.display.get_size();
-                let mut y = (self.v_reg[vy as usize] as usize) & (display_size.1 - 1);
-                self.v_reg[0xf] = 0;
-
-                'rows: for i in 0..n {
-                    let mut x = (self.v_reg[vx as usize] as usize) & (display_size.0 - 1);
-
-                    let sprite_byte = self.memory[self.i_reg as usize + i as usize];
-                    'cols: for j in 0..8 {
-                        let sprite_bit = sprite_byte & (0x1 << (7 - j));
-                        let display_px_inx = y * display_size.0 + x;
-                        if Self::set_display_pixel(
-                            &mut self.display_buffer,
-                            display_px_inx,
-                            sprite_bit != 0,
-                        ) {
-                            self.v_reg[0xf] = 1;
-                        }
-
-                        x += 1;
-                        if x >= display_size.0 {
-                            break 'cols;
-                        }
-                    }
-
-                    y += 1;
-                    if y >= display_size.1 {
-                        break 'rows;
-                    }
+                let x = self.v_reg[vx as usize] as usize;
+                let y = self.v_reg[vy as usize] as usize;
+                self.watch_memory(self.i_reg, n as u16, Access::Read);
+                // Sprites running past the end of memory wrap around to address 0
+                let mut sprite = [0; 15];
+                for (row, byte) in sprite.iter_mut().enumerate().take(n as usize) {
+                    *byte = self.memory[(self.i_reg as usize + row) % self.memory.len()];
+                }
+
+                let collision = self.display_buffer.draw_sprite(x, y, &sprite[..n as usize]);
+                self.v_reg[0xf] = collision as u8;
+            }
+            Chip8Instruction::LoadAudioPattern() => {
+                self.watch_memory(self.i_reg, 16, Access::Read);
+                let mut pattern = [0; 16];
+                for (offset, byte) in pattern.iter_mut().enumerate() {
+                    *byte = self.memory[(self.i_reg as usize + offset) % self.memory.len()];
+                }
+                self.audio_pattern = Some(pattern);
+            }
+            Chip8Instruction::SetPitch(x) => self.pitch = self.v_reg[x as usize],
+            Chip8Instruction::SetSoundTimer(x) => self.sound_timer = self.v_reg[x as usize],
+            Chip8Instruction::StoreBCD(x) => {
+                let value = self.v_reg[x as usize];
+                self.store(&[value / 100, value / 10 % 10, value % 10]);
+            }
+            Chip8Instruction::StoreRegisters(x) => {
+                let registers = self.v_reg[..=x as usize].to_vec();
+                self.store(&registers);
+                if self.compatibility == Compatibility::Cosmac {
+                    self.i_reg = (self.i_reg + x as u16 + 1) & 0xfff;
                 }
             }
         }
     }
 
-    #[inline]
-    fn set_display_pixel(display_buffer: &mut Vec<bool>, px_idx: usize, px_val: bool) -> bool {
-        let px_old = display_buffer[px_idx];
-        display_buffer[px_idx] = px_old ^ px_val;
-        px_old && !display_buffer[px_idx]
+    /// Writes `bytes` from I onwards, wrapping around to address 0 like sprite reads
+    fn store(&mut self, bytes: &[u8]) {
+        self.watch_memory(self.i_reg, bytes.len() as u16, Access::Write);
+        for (offset, byte) in bytes.iter().enumerate() {
+            let addr = (self.i_reg as usize + offset) % self.memory.len();
+            self.memory[addr] = *byte;
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{chip8::compat::Compatibility, display::test_display::TestDisplay};
+    use crate::{
+        chip8::compat::Compatibility,
+        display::{framebuffer::Framebuffer, test_display::TestDisplay},
+    };
     use rstest::*;
     use twelve_bit::u12::*;
 
     #[rstest]
     fn test_clear_screen() {
         let mut chip8 = get_test_chip8(None);
-        chip8.display_buffer = vec![true; 64 * 32];
+        chip8.display_buffer.draw_sprite(0, 0, &[0xff; 15]);
 
         chip8.execute(Chip8Instruction::ClearScreen());
 
-        assert_eq!(chip8.display_buffer, vec![false; 64 * 32]);
+        assert_eq!(chip8.display_buffer, Framebuffer::new(64, 32));
     }
 
     #[rstest]
@@ -501,26 +504,6 @@ mod tests {
         assert_eq!(expected, chip8.i_reg);
     }
 
-    #[rstest]
-    // true should be returned if the pixel is being turned off
-    #[case::set_display_pixel(&mut vec![true], 0, true, false, true)]
-    #[case::set_display_pixel(&mut vec![false], 0, true, true, false)]
-    #[case::set_display_pixel(&mut vec![false], 0, false, false, false)]
-    #[case::set_display_pixel(&mut vec![true], 0, false, true, false)]
-    fn test_set_display_pixel(
-        #[case] display_buffer: &mut Vec<bool>,
-        #[case] px_idx: usize,
-        #[case] px_val: bool,
-        #[case] expected_px_value: bool,
-        #[case] expected_return_value: bool,
-    ) {
-        assert_eq!(
-            expected_return_value,
-            Chip8::<TestDisplay>::set_display_pixel(display_buffer, px_idx, px_val)
-        );
-        assert_eq!(expected_px_value, display_buffer[px_idx]);
-    }
-
     fn get_test_chip8(compatibility: Option<Compatibility>) -> Chip8<TestDisplay> {
         let compat = compatibility.unwrap_or(Compatibility::Cosmac);
         Chip8::new(TestDisplay::new(), compat)
@@ -555,20 +538,19 @@ mod tests {
         chip8.execute(Chip8Instruction::Draw(0, 1, 4));
 
         // Verify sprite was drawn correctly
-        let display_size = chip8.display.get_size();
 
         // Check first row of sprite (####....)
-        assert_eq!(chip8.display_buffer[10 * display_size.0 + 5], true); // pixel (5,10)
-        assert_eq!(chip8.display_buffer[10 * display_size.0 + 6], true); // pixel (6,10)
-        assert_eq!(chip8.display_buffer[10 * display_size.0 + 7], true); // pixel (7,10)
-        assert_eq!(chip8.display_buffer[10 * display_size.0 + 8], true); // pixel (8,10)
-        assert_eq!(chip8.display_buffer[10 * display_size.0 + 9], false); // pixel (9,10)
+        assert!(chip8.display_buffer.get(5, 10)); // pixel (5,10)
+        assert!(chip8.display_buffer.get(6, 10)); // pixel (6,10)
+        assert!(chip8.display_buffer.get(7, 10)); // pixel (7,10)
+        assert!(chip8.display_buffer.get(8, 10)); // pixel (8,10)
+        assert!(!chip8.display_buffer.get(9, 10)); // pixel (9,10)
 
         // Check second row of sprite (#..#....)
-        assert_eq!(chip8.display_buffer[11 * display_size.0 + 5], true); // pixel (5,11)
-        assert_eq!(chip8.display_buffer[11 * display_size.0 + 6], false); // pixel (6,11)
-        assert_eq!(chip8.display_buffer[11 * display_size.0 + 7], false); // pixel (7,11)
-        assert_eq!(chip8.display_buffer[11 * display_size.0 + 8], true); // pixel (8,11)
+        assert!(chip8.display_buffer.get(5, 11)); // pixel (5,11)
+        assert!(!chip8.display_buffer.get(6, 11)); // pixel (6,11)
+        assert!(!chip8.display_buffer.get(7, 11)); // pixel (7,11)
+        assert!(chip8.display_buffer.get(8, 11)); // pixel (8,11)
 
         // Check that VF (collision flag) is 0 (no collision on clear screen)
         assert_eq!(chip8.v_reg[0xf], 0);
@@ -584,9 +566,7 @@ mod tests {
         chip8.memory[sprite_address] = sprite_data[0];
 
         // Pre-fill some pixels at the draw location to test collision
-        let display_size = chip8.display.get_size();
-        chip8.display_buffer[5 * display_size.0 + 3] = true; // pixel (3,5)
-        chip8.display_buffer[5 * display_size.0 + 4] = true; // pixel (4,5)
+        chip8.display_buffer.draw_sprite(3, 5, &[0b11000000]); // pixels (3,5) and (4,5)
 
         // Set I register and position
         chip8.execute(Chip8Instruction::SetIRegister(sprite_address as u16));
@@ -600,10 +580,10 @@ mod tests {
         assert_eq!(chip8.v_reg[0xf], 1);
 
         // Check XOR behavior - overlapping pixels should be turned off
-        assert_eq!(chip8.display_buffer[5 * display_size.0 + 3], false); // was on, now off (collision)
-        assert_eq!(chip8.display_buffer[5 * display_size.0 + 4], false); // was on, now off (collision)
-        assert_eq!(chip8.display_buffer[5 * display_size.0 + 5], true); // was off, now on
-        assert_eq!(chip8.display_buffer[5 * display_size.0 + 6], true); // was off, now on
+        assert!(!chip8.display_buffer.get(3, 5)); // was on, now off (collision)
+        assert!(!chip8.display_buffer.get(4, 5)); // was on, now off (collision)
+        assert!(chip8.display_buffer.get(5, 5)); // was off, now on
+        assert!(chip8.display_buffer.get(6, 5)); // was off, now on
     }
 
     #[rstest]
@@ -620,13 +600,11 @@ mod tests {
         chip8.execute(Chip8Instruction::SetVX(1, 0)); // y=0
         chip8.execute(Chip8Instruction::Draw(0, 1, 1));
 
-        let display_size = chip8.display.get_size();
-
         // Check pattern from 0x200 (10101010)
-        assert_eq!(chip8.display_buffer[0 * display_size.0 + 0], true); // bit 7
-        assert_eq!(chip8.display_buffer[0 * display_size.0 + 1], false); // bit 6
-        assert_eq!(chip8.display_buffer[0 * display_size.0 + 2], true); // bit 5
-        assert_eq!(chip8.display_buffer[0 * display_size.0 + 3], false); // bit 4
+        assert!(chip8.display_buffer.get(0, 0)); // bit 7
+        assert!(!chip8.display_buffer.get(1, 0)); // bit 6
+        assert!(chip8.display_buffer.get(2, 0)); // bit 5
+        assert!(!chip8.display_buffer.get(3, 0)); // bit 4
 
         // Clear screen and test drawing from second location
         chip8.execute(Chip8Instruction::ClearScreen());
@@ -634,9 +612,83 @@ mod tests {
         chip8.execute(Chip8Instruction::Draw(0, 1, 1));
 
         // Check pattern from 0x300 (01010101)
-        assert_eq!(chip8.display_buffer[0 * display_size.0 + 0], false); // bit 7
-        assert_eq!(chip8.display_buffer[0 * display_size.0 + 1], true); // bit 6
-        assert_eq!(chip8.display_buffer[0 * display_size.0 + 2], false); // bit 5
-        assert_eq!(chip8.display_buffer[0 * display_size.0 + 3], true); // bit 4
+        assert!(!chip8.display_buffer.get(0, 0)); // bit 7
+        assert!(chip8.display_buffer.get(1, 0)); // bit 6
+        assert!(!chip8.display_buffer.get(2, 0)); // bit 5
+        assert!(chip8.display_buffer.get(3, 0)); // bit 4
+    }
+
+    #[rstest]
+    fn test_draw_wraps_past_end_of_memory() {
+        let mut chip8 = get_test_chip8(None);
+        chip8.memory[0xfff] = 0b10000000;
+        chip8.memory[0x000] = 0b01000000;
+        chip8.memory[0x001] = 0b00100000;
+
+        chip8.execute(Chip8Instruction::SetIRegister(0xfff));
+        chip8.execute(Chip8Instruction::Draw(0, 0, 0xf));
+
+        assert!(chip8.display_buffer.get(0, 0));
+        assert!(chip8.display_buffer.get(1, 1));
+        assert!(chip8.display_buffer.get(2, 2));
+    }
+
+    #[rstest]
+    #[case::three_digits(254, [2, 5, 4])]
+    #[case::one_digit(7, [0, 0, 7])]
+    fn test_store_bcd(#[case] value: u8, #[case] digits: [u8; 3]) {
+        let mut chip8 = get_test_chip8(None);
+        chip8.v_reg[3] = value;
+        chip8.i_reg = 0x300;
+
+        chip8.execute(Chip8Instruction::StoreBCD(3));
+
+        assert_eq!(chip8.memory[0x300..0x303], digits);
+        assert_eq!(chip8.i_reg, 0x300);
+    }
+
+    #[rstest]
+    #[case::cosmac(Compatibility::Cosmac, 0x303)]
+    #[case::chip48(Compatibility::Chip48, 0x300)]
+    fn test_store_registers(#[case] compatibility: Compatibility, #[case] i_reg: u16) {
+        let mut chip8 = get_test_chip8(Some(compatibility));
+        chip8.v_reg[..4].copy_from_slice(&[1, 2, 3, 4]);
+        chip8.i_reg = 0x300;
+
+        chip8.execute(Chip8Instruction::StoreRegisters(2));
+
+        assert_eq!(chip8.memory[0x300..0x304], [1, 2, 3, 0]);
+        assert_eq!(chip8.i_reg, i_reg);
+    }
+
+    #[rstest]
+    fn test_store_wraps_past_end_of_memory() {
+        let mut chip8 = get_test_chip8(None);
+        chip8.v_reg[..2].copy_from_slice(&[1, 2]);
+        chip8.i_reg = 0xfff;
+
+        chip8.execute(Chip8Instruction::StoreRegisters(1));
+
+        assert_eq!(chip8.memory[0xfff], 1);
+        assert_eq!(chip8.memory[0x000], 2);
+        assert_eq!(chip8.i_reg, 0x001);
+    }
+
+    #[rstest]
+    fn test_load_audio_pattern_and_pitch() {
+        let mut chip8 = get_test_chip8(None);
+        chip8.memory[0xff8..0x1000].copy_from_slice(&[0xaa; 8]);
+        chip8.memory[0x000..0x008].copy_from_slice(&[0x55; 8]);
+        chip8.i_reg = 0xff8;
+        chip8.v_reg[2] = 112;
+
+        chip8.execute(Chip8Instruction::LoadAudioPattern());
+        chip8.execute(Chip8Instruction::SetPitch(2));
+
+        let mut pattern = [0xaa; 16];
+        pattern[8..].fill(0x55);
+        assert_eq!(chip8.audio_pattern, Some(pattern));
+        assert_eq!(chip8.pitch, 112);
+        assert_eq!(chip8.i_reg, 0xff8);
     }
 }
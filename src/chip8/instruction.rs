@@ -2,7 +2,7 @@ use std::fmt::Display;
 
 use twelve_bit::u12::U12;
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Chip8Instruction {
     /// 0x00E0
     ClearScreen(),
@@ -54,6 +54,23 @@ pub enum Chip8Instruction {
     SetIRegister(u16),
     /// 0xDXYN
     Draw(u8, u8, u8),
+    /// 0xF002
+    /// Load the 16 byte XO-CHIP audio pattern from I
+    LoadAudioPattern(),
+    /// 0xFX18
+    /// The buzzer sounds for VX frames
+    SetSoundTimer(u8),
+    /// 0xFX3A
+    /// Set the XO-CHIP audio pattern's pitch to VX
+    SetPitch(u8),
+    /// 0xFX33
+    /// Store the hundreds, tens and ones digits of VX at I, I + 1 and I + 2
+    StoreBCD(u8),
+    /// 0xFX55
+    /// Store V0 to VX from I onwards
+    /// COSMAC: I is left pointing past the stored registers
+    /// Chip48: I is unchanged
+    StoreRegisters(u8),
 }
 
 impl Display for Chip8Instruction {
@@ -114,6 +131,13 @@ impl Display for Chip8Instruction {
             Chip8Instruction::ShiftVXLeft(x, y) => write!(f, "0x8XYE - Shift v{}, v{} left", x, y),
             Chip8Instruction::SetIRegister(addr) => write!(f, "0xANNN - Set i to {:03X}", addr),
             Chip8Instruction::Draw(v, x, y) => write!(f, "0xDXYN - Draw v{} at ({}, {})", v, x, y),
+            Chip8Instruction::LoadAudioPattern() => write!(f, "0xF002 - Load audio pattern at i"),
+            Chip8Instruction::SetPitch(x) => write!(f, "0xFX3A - Set pitch to v{}", x),
+            Chip8Instruction::SetSoundTimer(x) => write!(f, "0xFX18 - Set sound timer to v{}", x),
+            Chip8Instruction::StoreBCD(x) => write!(f, "0xFX33 - Store BCD of v{} at i", x),
+            Chip8Instruction::StoreRegisters(x) => {
+                write!(f, "0xFX55 - Store v0 to v{} at i", x)
+            }
         }
     }
 }
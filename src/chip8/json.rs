@@ -0,0 +1,177 @@
+use std::{iter::Peekable, str::Chars};
+
+/// Just enough JSON for cartridge payloads and trace files
+#[derive(Debug, PartialEq)]
+pub(super) enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    pub(super) fn parse(text: &str) -> Result<Json, String> {
+        let mut chars = text.chars().peekable();
+        let value = parse_value(&mut chars)?;
+        skip_whitespace(&mut chars);
+        match chars.next() {
+            None => Ok(value),
+            Some(c) => Err(format!("Invalid JSON: unexpected {:?} after value", c)),
+        }
+    }
+
+    /// Member `name` of an object
+    pub(super) fn get(&self, name: &str) -> Option<&Json> {
+        match self {
+            Json::Object(members) => members
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+}
+
+fn parse_value(chars: &mut Peekable<Chars>) -> Result<Json, String> {
+    skip_whitespace(chars);
+    match chars.peek() {
+        Some('{') => {
+            chars.next();
+            let mut members = vec![];
+            for_each_element(chars, '}', |chars| {
+                skip_whitespace(chars);
+                let key = parse_string(chars)?;
+                skip_whitespace(chars);
+                expect(chars, ':')?;
+                members.push((key, parse_value(chars)?));
+                Ok(())
+            })?;
+            Ok(Json::Object(members))
+        }
+        Some('[') => {
+            chars.next();
+            let mut elements = vec![];
+            for_each_element(chars, ']', |chars| {
+                elements.push(parse_value(chars)?);
+                Ok(())
+            })?;
+            Ok(Json::Array(elements))
+        }
+        Some('"') => parse_string(chars).map(Json::String),
+        Some(c) if *c == '-' || c.is_ascii_digit() => {
+            let mut number = String::new();
+            while let Some(c) = chars.next_if(|c| "+-.eE".contains(*c) || c.is_ascii_digit()) {
+                number.push(c);
+            }
+            number
+                .parse()
+                .map(Json::Number)
+                .map_err(|_| format!("Invalid JSON number: {}", number))
+        }
+        Some(_) => {
+            let mut word = String::new();
+            while let Some(c) = chars.next_if(char::is_ascii_alphabetic) {
+                word.push(c);
+            }
+            match word.as_str() {
+                "true" => Ok(Json::Bool(true)),
+                "false" => Ok(Json::Bool(false)),
+                "null" => Ok(Json::Null),
+                _ => Err(format!("Invalid JSON value: {}", word)),
+            }
+        }
+        None => Err("Invalid JSON: unexpected end".to_string()),
+    }
+}
+
+/// Parses comma separated elements up to `close`, the opening bracket already consumed
+fn for_each_element(
+    chars: &mut Peekable<Chars>,
+    close: char,
+    mut element: impl FnMut(&mut Peekable<Chars>) -> Result<(), String>,
+) -> Result<(), String> {
+    skip_whitespace(chars);
+    if chars.next_if_eq(&close).is_some() {
+        return Ok(());
+    }
+    loop {
+        element(chars)?;
+        skip_whitespace(chars);
+        match chars.next() {
+            Some(',') => {}
+            Some(c) if c == close => return Ok(()),
+            _ => return Err(format!("Invalid JSON: expected ',' or '{}'", close)),
+        }
+    }
+}
+
+fn parse_string(chars: &mut Peekable<Chars>) -> Result<String, String> {
+    expect(chars, '"')?;
+    let mut string = String::new();
+    loop {
+        match chars.next() {
+            Some('"') => return Ok(string),
+            Some('\\') => match chars.next() {
+                Some('n') => string.push('\n'),
+                Some('t') => string.push('\t'),
+                Some('r') => string.push('\r'),
+                Some('b') => string.push('\u{8}'),
+                Some('f') => string.push('\u{c}'),
+                Some('u') => {
+                    let mut code = parse_hex4(chars)?;
+                    // Characters outside the BMP are escaped as a surrogate pair
+                    if (0xd800..0xdc00).contains(&code) {
+                        expect(chars, '\\')?;
+                        expect(chars, 'u')?;
+                        code = 0x10000 + ((code - 0xd800) << 10) + (parse_hex4(chars)? - 0xdc00);
+                    }
+                    string.push(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER));
+                }
+                Some(c) => string.push(c),
+                None => break,
+            },
+            Some(c) => string.push(c),
+            None => break,
+        }
+    }
+    Err("Invalid JSON: unterminated string".to_string())
+}
+
+fn parse_hex4(chars: &mut Peekable<Chars>) -> Result<u32, String> {
+    let hex = chars.take(4).collect::<String>();
+    match u32::from_str_radix(&hex, 16) {
+        Ok(code) if hex.len() == 4 => Ok(code),
+        _ => Err(format!("Invalid JSON escape: \\u{}", hex)),
+    }
+}
+
+fn expect(chars: &mut Peekable<Chars>, expected: char) -> Result<(), String> {
+    match chars.next() {
+        Some(c) if c == expected => Ok(()),
+        _ => Err(format!("Invalid JSON: expected '{}'", expected)),
+    }
+}
+
+fn skip_whitespace(chars: &mut Peekable<Chars>) {
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    #[case::nested(r#"[1, -2.5e1, [], {"a": null}]"#, Json::Array(vec![
+        Json::Number(1.0),
+        Json::Number(-25.0),
+        Json::Array(vec![]),
+        Json::Object(vec![("a".to_string(), Json::Null)]),
+    ]))]
+    #[case::surrogate_pair(r#""\ud83d\ude00\/""#, Json::String("\u{1f600}/".to_string()))]
+    fn test_parse_json(#[case] text: &str, #[case] expected: Json) {
+        assert_eq!(Json::parse(text), Ok(expected));
+    }
+}
@@ -1,4 +1,12 @@
-use crate::{chip8::Chip8, display::Display};
+use crate::{
+    chip8::{
+        cartridge::{self, Cartridge},
+        compat::Compatibility,
+        state::fnv1a,
+        Chip8,
+    },
+    display::{palette::Palette, Display},
+};
 use twelve_bit::u12::*;
 
 const PROGRAM_START_ADDR: u16 = 0x200;
@@ -7,20 +15,146 @@ impl<D> Chip8<D>
 where
     D: Display,
 {
-    pub fn load_rom(&mut self, rom_path: &str) {
-        let program = std::fs::read(rom_path);
-        match program {
-            Ok(program) => {
-                for (i, byte) in program.iter().enumerate() {
-                    let addr = PROGRAM_START_ADDR as usize + i;
-                    self.memory[addr] = *byte;
+    /// Loads a raw ROM or an Octo cartridge, applying the cartridge's options
+    pub fn load_rom(&mut self, rom_path: &str) -> Result<(), String> {
+        let file =
+            std::fs::read(rom_path).map_err(|e| format!("Failed to read ROM file: {}", e))?;
+
+        let program = if cartridge::is_cartridge(&file) {
+            match Cartridge::decode(&file) {
+                Ok(cartridge) => {
+                    self.apply_cartridge_options(&cartridge.options);
+                    cartridge.rom
+                }
+                // The built-in compiler has no metaprogramming, which many published cartridges use
+                Err(e) if e.contains("Unsupported directive") => {
+                    return Err(format!(
+                        "Failed to load cartridge: {}. Macros, :calc and the other Octo \
+                         directives aren't supported, export the program as a ROM from Octo instead",
+                        e
+                    ))
                 }
+                Err(e) => return Err(format!("Failed to load cartridge: {}", e)),
+            }
+        } else {
+            file
+        };
+
+        for (i, byte) in program.iter().enumerate() {
+            let addr = PROGRAM_START_ADDR as usize + i;
+            self.memory[addr] = *byte;
+        }
+
+        self.pc = u12![PROGRAM_START_ADDR];
+        self.rom_hash = fnv1a(&program);
+        self.rom_len = program.len();
+        Ok(())
+    }
+
+    /// The loaded program as it is now in memory
+    pub(super) fn rom(&self) -> &[u8] {
+        let start = PROGRAM_START_ADDR as usize;
+        &self.memory[start..start + self.rom_len]
+    }
 
-                self.pc = u12![PROGRAM_START_ADDR];
+    fn apply_cartridge_options(&mut self, options: &cartridge::Options) {
+        for quirk in options.unsupported_quirks() {
+            eprintln!("Warning: {}", quirk);
+        }
+        if let Some(tickrate) = options.tickrate {
+            self.cycles_per_frame = tickrate;
+        }
+        if let Some(shift_quirks) = options.shift_quirks {
+            self.compatibility = if shift_quirks {
+                Compatibility::Chip48
+            } else {
+                Compatibility::Cosmac
+            };
+        }
+        // The compatibility mode covers both quirks, so they can't be set apart
+        if let Some(load_store_quirks) = options.load_store_quirks {
+            if load_store_quirks != (self.compatibility == Compatibility::Chip48) {
+                eprintln!(
+                    "Warning: loadStoreQuirks doesn't match shiftQuirks, FX55 follows the {} mode",
+                    self.compatibility
+                );
             }
-            Err(e) => {
-                eprintln!("Failed to read ROM file: {}", e);
+        }
+        let colors = [
+            options.background_color,
+            options.fill_color,
+            options.fill_color2,
+            options.blend_color,
+        ];
+        if colors.iter().any(Option::is_some) {
+            let mut palette = Palette::default();
+            for (color, option) in palette.0.iter_mut().zip(colors) {
+                *color = option.unwrap_or(*color);
             }
+            self.display.set_palette(palette);
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{chip8::cartridge::tests::encode_cartridge, display::test_display::TestDisplay};
+    use rstest::*;
+
+    #[rstest]
+    fn test_load_cartridge_applies_options() {
+        let mut chip8 = Chip8::new(TestDisplay::new(), Compatibility::Cosmac);
+        load_cartridge(
+            &mut chip8,
+            "options",
+            r##"{"program": ": main clear", "options": {"tickrate": 30, "shiftQuirks": true, "fillColor": "#FFFFFF", "backgroundColor": "#000080"}}"##,
+        )
+        .unwrap();
+
+        assert_eq!(&chip8.memory[0x200..0x203], &[0x00, 0xe0, 0x00]);
+        assert_eq!(chip8.pc, u12![0x200]);
+        assert_eq!(chip8.rom_hash, fnv1a(&[0x00, 0xe0]));
+        assert_eq!(chip8.cycles_per_frame, 30);
+        assert_eq!(chip8.compatibility, Compatibility::Chip48);
+        assert_eq!(
+            chip8.display.palette,
+            Some(Palette([0x000080, 0xffffff, 0x008000, 0xccffcc]))
+        );
+    }
+
+    #[rstest]
+    fn test_load_cartridge_with_macros() {
+        let mut chip8 = Chip8::new(TestDisplay::new(), Compatibility::Cosmac);
+        let result = load_cartridge(
+            &mut chip8,
+            "macros",
+            r#"{"program": ":macro twice { clear clear }\n: main twice"}"#,
+        );
+
+        assert_eq!(
+            result,
+            Err("Failed to load cartridge: Cartridge program: Line 1: Unsupported directive: :macro. \
+                 Macros, :calc and the other Octo directives aren't supported, export the program \
+                 as a ROM from Octo instead"
+                .to_string())
+        );
+        assert_eq!(chip8.rom_len, 0);
+    }
+
+    fn load_cartridge(
+        chip8: &mut Chip8<TestDisplay>,
+        name: &str,
+        json: &str,
+    ) -> Result<(), String> {
+        let path = std::env::temp_dir().join(format!(
+            "chip8-cartridge-{}-{}.gif",
+            name,
+            std::process::id()
+        ));
+        std::fs::write(&path, encode_cartridge(json)).unwrap();
+        let result = chip8.load_rom(path.to_str().unwrap());
+        std::fs::remove_file(&path).unwrap();
+        result
+    }
+}
@@ -1,17 +1,52 @@
+mod cartridge;
+pub mod code_write;
 pub mod compat;
+pub mod coverage;
+mod debug;
 mod decode;
 mod execute;
 mod fetch;
-mod instruction;
+pub mod instruction;
+mod json;
 mod load;
-use std::{thread::sleep, time::Duration};
+pub mod movie;
+mod profile;
+mod rewind;
+mod state;
+pub mod trace;
+pub mod watch;
+use std::{
+    thread::sleep,
+    time::{Duration, Instant},
+};
 
 use crate::{
-    chip8::compat::Compatibility,
-    display::{minifb::MinifbDisplay, Display},
+    audio::{pattern::DEFAULT_PITCH, Audio, Voice},
+    chip8::{
+        code_write::CodeWriteTracker,
+        compat::Compatibility,
+        coverage::Coverage,
+        movie::Movie,
+        profile::Profiler,
+        rewind::RewindBuffer,
+        trace::Tracer,
+        watch::{WatchHit, Watchpoint},
+    },
+    display::{
+        framebuffer::Framebuffer,
+        palette::Palette,
+        phosphor::{Phosphor, PhosphorConfig},
+        Display, Hotkey,
+    },
 };
 use twelve_bit::u12::*;
 
+/// Number of frames presented per second
+pub const FRAME_RATE: u64 = 60;
+
+/// Default number of instructions executed per frame
+pub const DEFAULT_CYCLES_PER_FRAME: u32 = 10;
+
 pub struct Chip8<D>
 where
     D: Display,
@@ -34,11 +69,65 @@ where
     /// I register
     i_reg: u16,
 
-    /// Display buffer, draws every cycle
-    display_buffer: Vec<bool>,
+    /// Keypad state, bit n is set while key n is held
+    keypad: u16,
+
+    /// Frames the buzzer keeps sounding for
+    sound_timer: u8,
+
+    /// XO-CHIP audio pattern, once F002 has loaded one
+    audio_pattern: Option<[u8; 16]>,
+
+    /// XO-CHIP playback rate of the audio pattern
+    pitch: u8,
+
+    /// Display buffer, presented once per frame when it changed
+    display_buffer: Framebuffer,
+
+    /// Persistence filter the display buffer is presented through, when enabled
+    phosphor: Option<Phosphor>,
 
     /// Compatibility mode
     compatibility: Compatibility,
+
+    /// Instructions executed between two presented frames
+    cycles_per_frame: u32,
+
+    /// Hash of the loaded ROM, save states only load against the same ROM
+    rom_hash: u64,
+
+    /// Size of the loaded ROM, which starts at the entry point
+    rom_len: usize,
+
+    /// File written and read by the save and load state hotkeys
+    state_path: Option<String>,
+
+    /// Snapshots of recent frames, played back while the rewind hotkey is held
+    rewind_buffer: RewindBuffer,
+
+    /// Movie being recorded, one keypad state per frame
+    recording: Option<Movie>,
+
+    /// Memory ranges checked on every instruction memory access
+    watchpoints: Vec<Watchpoint>,
+
+    /// First watchpoint hit since the debugger last checked
+    watch_hit: Option<WatchHit>,
+
+    /// Execution trace, written one record per instruction when enabled
+    tracer: Option<Tracer>,
+
+    /// Execution counts, gathered when profiling
+    profiler: Option<Profiler>,
+
+    /// Executed instructions and skip directions, gathered when measuring coverage
+    coverage: Option<Coverage>,
+
+    /// Executed bytes matched against instruction writes, when tracking self-modifying code
+    code_writes: Option<CodeWriteTracker>,
+
+    /// Buzzer sound and the backend it plays through, once audio is enabled
+    audio: Option<(Box<dyn Voice>, Box<dyn Audio>)>,
 }
 
 impl<D> Chip8<D>
@@ -49,49 +138,300 @@ where
         let display_size = display.get_size();
 
         Chip8 {
-            display: display,
+            display,
             memory: vec![0; Into::<usize>::into(U12::max_value()) + 1],
 
             pc: u12![0],
             v_reg: vec![0; 16],
             i_reg: 0,
+            keypad: 0,
+            sound_timer: 0,
+            audio_pattern: None,
+            pitch: DEFAULT_PITCH,
             stack: vec![],
 
-            display_buffer: vec![false; display_size.0 * display_size.1],
+            display_buffer: Framebuffer::new(display_size.0, display_size.1),
+            phosphor: None,
 
             compatibility,
+
+            cycles_per_frame: DEFAULT_CYCLES_PER_FRAME,
+
+            rom_hash: state::fnv1a(&[]),
+            rom_len: 0,
+            state_path: None,
+
+            rewind_buffer: RewindBuffer::new(0),
+
+            recording: None,
+
+            watchpoints: vec![],
+            watch_hit: None,
+
+            tracer: None,
+            profiler: None,
+            coverage: None,
+            code_writes: None,
+            audio: None,
         }
     }
 
+    pub fn set_compatibility(&mut self, compatibility: Compatibility) {
+        self.compatibility = compatibility;
+    }
+
+    /// Colours pixels are drawn in, for displays drawing in colour
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.display.set_palette(palette);
+    }
+
+    /// Presents frames through a persistence filter from now on, to hide sprite flicker
+    pub fn set_phosphor(&mut self, config: PhosphorConfig) {
+        self.phosphor = Some(Phosphor::new(config));
+    }
+
+    pub fn set_cycles_per_frame(&mut self, cycles_per_frame: u32) {
+        self.cycles_per_frame = cycles_per_frame;
+    }
+
+    pub fn set_state_path(&mut self, state_path: &str) {
+        self.state_path = Some(state_path.to_string());
+    }
+
+    /// Keeps a snapshot of the last `frames` frames for rewinding, 0 disables rewind
+    pub fn set_rewind_depth(&mut self, frames: usize) {
+        self.rewind_buffer = RewindBuffer::new(frames);
+    }
+
     pub fn run(&mut self) {
         while self.display.is_open() {
-            let code = self.fetch();
-            let instruction = self.decode(code);
+            let frame_start = Instant::now();
+
+            if self.display.is_hotkey_down(Hotkey::Rewind) {
+                // Rewound frames are dropped from the recording so it follows the final timeline
+                if self.rewind_frame() {
+                    if let Some(recording) = &mut self.recording {
+                        recording.pop_frame();
+                    }
+                }
+            } else {
+                self.keypad = self.display.keypad();
+                if let Some(recording) = &mut self.recording {
+                    recording.push_frame(self.keypad);
+                }
 
-            match instruction {
-                Ok(instruction) => self.execute(instruction),
-                Err(e) => panic!("Error: {}", e),
+                self.run_frame();
+                self.rewind_buffer.push(self.save_state());
+                for write in self.take_code_writes() {
+                    eprintln!("Warning: {}", write);
+                }
             }
 
             self.render_buffer();
-            self.sleep();
+            self.end_frame_sound();
+            self.handle_hotkeys();
+            wait_for_next_frame(frame_start);
+        }
+    }
+
+    fn run_frame(&mut self) {
+        for _ in 0..self.cycles_per_frame {
+            self.cycle();
+        }
+    }
+
+    fn cycle(&mut self) {
+        if let Err(e) = self.step() {
+            panic!("Error: {}", e);
         }
     }
 
+    /// Plays the buzzer as `voice` through `output` from now on, one frame of samples per frame
+    pub fn set_audio(&mut self, voice: Box<dyn Voice>, output: Box<dyn Audio>) {
+        self.audio = Some((voice, output));
+    }
+
+    /// Plays the sound of the frame just run, then counts the sound timer down
+    fn end_frame_sound(&mut self) {
+        if let Some((voice, output)) = &mut self.audio {
+            if let Some(pattern) = self.audio_pattern {
+                voice.set_pattern(pattern, self.pitch);
+            }
+            output.play(&voice.frame(self.sound_timer > 0));
+        }
+        self.sound_timer = self.sound_timer.saturating_sub(1);
+    }
+
+    /// Hands the framebuffer to the display, which only redraws the dirty rows
     fn render_buffer(&mut self) {
-        self.display.update(&mut self.display_buffer);
+        match &mut self.phosphor {
+            Some(phosphor) => {
+                phosphor.apply(&self.display_buffer);
+                self.display.update_phosphor(phosphor);
+                phosphor.clear_dirty();
+            }
+            None => self.display.update(&self.display_buffer),
+        }
+        self.display_buffer.clear_dirty();
+    }
+
+    fn handle_hotkeys(&mut self) {
+        let Some(state_path) = self.state_path.clone() else {
+            return;
+        };
+
+        for hotkey in self.display.pressed_hotkeys() {
+            let result = match hotkey {
+                Hotkey::SaveState => self.save_state_file(&state_path),
+                Hotkey::LoadState if self.recording.is_some() => {
+                    Err("Loading a state while recording a movie isn't supported".to_string())
+                }
+                Hotkey::LoadState => self.load_state_file(&state_path),
+                // Rewinding lasts as long as the key is held, see run()
+                Hotkey::Rewind => continue,
+            };
+
+            match result {
+                Ok(()) => eprintln!("{:?}: {}", hotkey, state_path),
+                Err(e) => eprintln!("{}", e),
+            }
+        }
+    }
+
+    /// Restores the snapshot taken one frame earlier, returns false if there is none left.
+    ///
+    /// A snapshot that fails to restore is reported and ends rewinding, the
+    /// machine carries on from where it is.
+    fn rewind_frame(&mut self) -> bool {
+        let Some(snapshot) = self.rewind_buffer.rewind() else {
+            return false;
+        };
+
+        let snapshot = snapshot.to_vec();
+        if let Err(e) = self.load_state(&snapshot) {
+            eprintln!("Failed to restore rewind snapshot, rewind stopped: {}", e);
+            self.rewind_buffer.clear();
+            return false;
+        }
+        true
     }
 
     fn inc_pc(&mut self, x: u16) {
         self.pc = self.pc + u12![x];
     }
+}
 
-    fn sleep(&self) {
-        sleep(Duration::from_millis(10));
+/// Sleeps for the remainder of the frame started at `frame_start`
+pub fn wait_for_next_frame(frame_start: Instant) {
+    let frame_duration = Duration::from_micros(1_000_000 / FRAME_RATE);
+    if let Some(remaining) = frame_duration.checked_sub(frame_start.elapsed()) {
+        sleep(remaining);
     }
 }
 
-type Chip8Macos = Chip8<MinifbDisplay>;
-pub fn get_chip8_macos(compatibility: Compatibility) -> Chip8Macos {
-    Chip8::new(MinifbDisplay::new(), compatibility)
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        audio::{
+            beeper::{Beeper, BeeperConfig},
+            pattern::PatternPlayer,
+        },
+        chip8::state::fnv1a,
+        display::test_display::TestDisplay,
+    };
+    use rstest::*;
+    use std::{cell::RefCell, rc::Rc};
+
+    #[rstest]
+    fn test_rewind_frame_stops_at_unloadable_snapshot() {
+        let mut chip8 = Chip8::new(TestDisplay::new(), Compatibility::Cosmac);
+        chip8.set_rewind_depth(10);
+        for _ in 0..3 {
+            chip8.rewind_buffer.push(chip8.save_state());
+        }
+        chip8.rom_hash = fnv1a(&[0x12, 0x00]);
+
+        assert!(!chip8.rewind_frame());
+        assert_eq!(chip8.rewind_buffer.rewind(), None);
+    }
+
+    /// Keeps every frame of samples it is given
+    struct Recorder(Rc<RefCell<Vec<Vec<f32>>>>);
+
+    impl Audio for Recorder {
+        fn sample_rate(&self) -> u32 {
+            1200
+        }
+
+        fn play(&mut self, samples: &[f32]) {
+            self.0.borrow_mut().push(samples.to_vec());
+        }
+    }
+
+    #[rstest]
+    fn test_buzzer_sounds_while_the_sound_timer_runs() {
+        let frames = Rc::new(RefCell::new(vec![]));
+        let mut chip8 = Chip8::new(TestDisplay::new(), Compatibility::Cosmac);
+        chip8.memory[0x200..0x206].copy_from_slice(&[
+            0x60, 0x02, // 0x200: v0 = 2
+            0xf0, 0x18, // 0x202: buzzer := v0
+            0x12, 0x04, // 0x204: jump to 0x204
+        ]);
+        chip8.pc = u12![0x200];
+        chip8.set_audio(
+            Box::new(Beeper::new(BeeperConfig::default(), 1200)),
+            Box::new(Recorder(frames.clone())),
+        );
+        chip8.start_recording();
+        let mut movie = chip8.take_recording().unwrap();
+        for _ in 0..4 {
+            movie.push_frame(0);
+        }
+
+        chip8.replay(&movie).unwrap();
+
+        let sounding = frames
+            .borrow()
+            .iter()
+            .map(|frame| frame.len() == 20 && frame.iter().any(|sample| *sample != 0.0))
+            .collect::<Vec<_>>();
+        assert_eq!(sounding, [true, true, false, false]);
+        assert_eq!(chip8.sound_timer, 0);
+    }
+
+    #[rstest]
+    fn test_buzzer_plays_the_loaded_pattern() {
+        let frames = Rc::new(RefCell::new(vec![]));
+        let mut chip8 = Chip8::new(TestDisplay::new(), Compatibility::Cosmac);
+        chip8.memory[0x200..0x20e].copy_from_slice(&[
+            0xa3, 0x00, // 0x200: i = 0x300
+            0xf0, 0x02, // 0x202: audio
+            0x60, 0x70, // 0x204: v0 = 112
+            0xf0, 0x3a, // 0x206: pitch := v0
+            0x60, 0x01, // 0x208: v0 = 1
+            0xf0, 0x18, // 0x20A: buzzer := v0
+            0x12, 0x0c, // 0x20C: jump to 0x20c
+        ]);
+        chip8.memory[0x300..0x310].copy_from_slice(&[0x0f; 16]);
+        chip8.pc = u12![0x200];
+        chip8.set_audio(
+            Box::new(PatternPlayer::new(
+                Beeper::new(BeeperConfig::default(), 8000),
+                1.0,
+                8000,
+            )),
+            Box::new(Recorder(frames.clone())),
+        );
+        chip8.start_recording();
+        let mut movie = chip8.take_recording().unwrap();
+        movie.push_frame(0);
+
+        chip8.replay(&movie).unwrap();
+
+        // An octave up plays one sample per bit, 0x0f is 4 clear then 4 set
+        let frames = frames.borrow();
+        assert_eq!(frames[0].len(), 133);
+        assert_eq!(frames[0][..8], [-1.0, -1.0, -1.0, -1.0, 1.0, 1.0, 1.0, 1.0]);
+    }
 }
@@ -0,0 +1,236 @@
+use crate::{
+    chip8::{
+        compat::Compatibility,
+        state::{fnv1a, fnv1a_continue, ByteReader},
+        Chip8,
+    },
+    display::Display,
+};
+
+/// Identifies a movie file
+const MAGIC: &[u8; 4] = b"C8MV";
+
+/// Bumped whenever the layout below changes
+const VERSION: u16 = 1;
+
+/// Keypad input of a play session, replayed frame by frame from power on.
+///
+/// Movie layout, all values little endian:
+///
+///   magic            4 bytes  "C8MV"
+///   version          u16
+///   rom hash         u64      FNV-1a of the ROM the movie was recorded with
+///   compatibility    u8       0 = COSMAC, 1 = CHIP-48
+///   cycles per frame u32
+///   frame count      u32, followed by the u16 keypad state of every frame
+#[derive(Debug, PartialEq, Eq)]
+pub struct Movie {
+    rom_hash: u64,
+    compatibility: Compatibility,
+    cycles_per_frame: u32,
+    frames: Vec<u16>,
+}
+
+impl Movie {
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub(super) fn push_frame(&mut self, keypad: u16) {
+        self.frames.push(keypad);
+    }
+
+    pub(super) fn pop_frame(&mut self) {
+        self.frames.pop();
+    }
+
+    pub fn save(&self) -> Vec<u8> {
+        let mut movie = Vec::with_capacity(24 + self.frames.len() * 2);
+
+        movie.extend_from_slice(MAGIC);
+        movie.extend_from_slice(&VERSION.to_le_bytes());
+        movie.extend_from_slice(&self.rom_hash.to_le_bytes());
+        movie.push(match self.compatibility {
+            Compatibility::Cosmac => 0,
+            Compatibility::Chip48 => 1,
+        });
+        movie.extend_from_slice(&self.cycles_per_frame.to_le_bytes());
+
+        movie.extend_from_slice(&(self.frames.len() as u32).to_le_bytes());
+        for keypad in &self.frames {
+            movie.extend_from_slice(&keypad.to_le_bytes());
+        }
+
+        movie
+    }
+
+    pub fn load(movie: &[u8]) -> Result<Self, String> {
+        let mut reader = ByteReader::new(movie, "Movie");
+
+        if reader.bytes(MAGIC.len())? != MAGIC {
+            return Err("Not a movie file".to_string());
+        }
+        let version = reader.u16()?;
+        if version != VERSION {
+            return Err(format!("Unsupported movie version: {}", version));
+        }
+
+        let rom_hash = reader.u64()?;
+        let compatibility = match reader.u8()? {
+            0 => Compatibility::Cosmac,
+            1 => Compatibility::Chip48,
+            other => return Err(format!("Invalid compatibility mode: {}", other)),
+        };
+        let cycles_per_frame = reader.u32()?;
+
+        let frame_count = reader.u32()?;
+        let mut frames = Vec::with_capacity(frame_count.min(1 << 20) as usize);
+        for _ in 0..frame_count {
+            frames.push(reader.u16()?);
+        }
+        reader.finish()?;
+
+        Ok(Movie {
+            rom_hash,
+            compatibility,
+            cycles_per_frame,
+            frames,
+        })
+    }
+
+    pub fn save_file(&self, path: &str) -> Result<(), String> {
+        std::fs::write(path, self.save())
+            .map_err(|e| format!("Failed to write movie {}: {}", path, e))
+    }
+
+    pub fn load_file(path: &str) -> Result<Self, String> {
+        let movie =
+            std::fs::read(path).map_err(|e| format!("Failed to read movie {}: {}", path, e))?;
+        Movie::load(&movie)
+    }
+}
+
+impl<D> Chip8<D>
+where
+    D: Display,
+{
+    /// Starts recording the keypad state of every following frame.
+    ///
+    /// Movies replay from power on, so this has to be called right after loading the ROM.
+    pub fn start_recording(&mut self) {
+        self.recording = Some(Movie {
+            rom_hash: self.rom_hash,
+            compatibility: self.compatibility,
+            cycles_per_frame: self.cycles_per_frame,
+            frames: vec![],
+        });
+    }
+
+    pub fn take_recording(&mut self) -> Option<Movie> {
+        self.recording.take()
+    }
+
+    /// Runs every frame of `movie` as fast as possible and returns the final state hash.
+    ///
+    /// The machine must be freshly loaded with the ROM the movie was recorded with.
+    pub fn replay(&mut self, movie: &Movie) -> Result<u64, String> {
+        if movie.rom_hash != self.rom_hash {
+            return Err("Movie was recorded with a different ROM".to_string());
+        }
+
+        self.compatibility = movie.compatibility;
+        self.cycles_per_frame = movie.cycles_per_frame;
+
+        for &keypad in &movie.frames {
+            self.keypad = keypad;
+            self.run_frame();
+            self.render_buffer();
+            self.end_frame_sound();
+        }
+
+        Ok(self.state_hash())
+    }
+
+    /// Hash of the framebuffer and memory, equal for runs that ended in the same state
+    pub fn state_hash(&self) -> u64 {
+        let mut hash = fnv1a(&self.memory);
+        for y in 0..self.display_buffer.height() {
+            hash = fnv1a_continue(hash, &self.display_buffer.row(y).to_le_bytes());
+        }
+        hash
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::test_display::TestDisplay;
+    use rstest::*;
+    use twelve_bit::u12::*;
+
+    #[rstest]
+    fn test_save_and_load_movie() {
+        let movie = Movie {
+            rom_hash: 0x1234,
+            compatibility: Compatibility::Chip48,
+            cycles_per_frame: 12,
+            frames: vec![0, 0b1, 0x8000, 0],
+        };
+
+        assert_eq!(Movie::load(&movie.save()), Ok(movie));
+    }
+
+    #[rstest]
+    #[case::bad_magic(&b"C8ST\x01\x00"[..], "Not a movie file")]
+    #[case::bad_version(&b"C8MV\x07\x00"[..], "Unsupported movie version: 7")]
+    #[case::truncated(&b"C8MV\x01\x00\x00"[..], "Movie is truncated")]
+    fn test_load_invalid_movie(#[case] movie: &[u8], #[case] error: &str) {
+        assert_eq!(Movie::load(movie), Err(error.to_string()));
+    }
+
+    #[rstest]
+    fn test_replay_is_deterministic() {
+        let mut recorder = get_test_chip8();
+        recorder.start_recording();
+        let mut movie = recorder.take_recording().unwrap();
+        movie.frames = vec![0; 30];
+
+        let first = get_test_chip8().replay(&movie).unwrap();
+        let second = get_test_chip8().replay(&movie).unwrap();
+
+        assert_eq!(first, second);
+        assert_ne!(first, get_test_chip8().state_hash());
+    }
+
+    #[rstest]
+    fn test_replay_with_different_rom() {
+        let mut chip8 = get_test_chip8();
+        chip8.start_recording();
+        let movie = chip8.take_recording().unwrap();
+
+        let mut other = get_test_chip8();
+        other.rom_hash = fnv1a(&[0x00, 0xe0]);
+
+        assert_eq!(
+            other.replay(&movie),
+            Err("Movie was recorded with a different ROM".to_string())
+        );
+    }
+
+    /// Machine running a loop that moves a sprite one pixel per iteration
+    fn get_test_chip8() -> Chip8<TestDisplay> {
+        let mut chip8 = Chip8::new(TestDisplay::new(), Compatibility::Cosmac);
+        let program = [
+            0xa2, 0x0a, // 0x200: I = 0x20A
+            0xd0, 0x11, // 0x202: draw 1 row at (v0, v1)
+            0x70, 0x01, // 0x204: v0 += 1
+            0x12, 0x02, // 0x206: jump to 0x202
+            0x00, 0x00, // 0x208: padding
+            0xf0, // 0x20A: sprite
+        ];
+        chip8.memory[0x200..0x200 + program.len()].copy_from_slice(&program);
+        chip8.pc = u12![0x200];
+        chip8.rom_hash = fnv1a(&program);
+        chip8
+    }
+}
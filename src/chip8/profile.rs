@@ -0,0 +1,253 @@
+use std::{cmp::Reverse, collections::BTreeMap};
+
+use crate::{
+    chip8::{instruction::Chip8Instruction, Chip8},
+    disasm::{Disassembly, LabelKind, Style},
+    display::Display,
+};
+
+/// Addresses listed in the hot spot ranking
+const HOT_SPOTS: usize = 20;
+
+/// Execution counts per address, per opcode class and per subroutine.
+///
+/// Time is measured in executed instructions, subroutines include the
+/// instructions of everything they call.
+#[derive(Default)]
+pub struct Profiler {
+    /// Instructions executed since profiling started
+    total: u64,
+    /// Last instruction executed at each address and how often it ran
+    addresses: BTreeMap<u16, (Chip8Instruction, u64)>,
+    /// Executions by opcode pattern
+    classes: BTreeMap<&'static str, u64>,
+    subroutines: BTreeMap<u16, Subroutine>,
+    /// Subroutines not yet returned from with the instruction count at their call, innermost last
+    calls: Vec<(u16, u64)>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct Subroutine {
+    calls: u64,
+    /// Instructions executed from the calls up to their returns
+    instructions: u64,
+}
+
+impl Profiler {
+    fn record(&mut self, pc: u16, instruction: Chip8Instruction) {
+        self.total += 1;
+        let executed = self.addresses.entry(pc).or_insert((instruction, 0));
+        *executed = (instruction, executed.1 + 1);
+        *self.classes.entry(instruction.pattern()).or_default() += 1;
+
+        match instruction {
+            Chip8Instruction::Call(addr) => {
+                self.subroutines.entry(addr.into()).or_default().calls += 1;
+                self.calls.push((addr.into(), self.total));
+            }
+            Chip8Instruction::Return() => {
+                if let Some((addr, start)) = self.calls.pop() {
+                    self.subroutines.entry(addr).or_default().instructions += self.total - start;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Subroutine totals, counting the calls still running up to now
+    fn subroutines(&self) -> BTreeMap<u16, Subroutine> {
+        let mut subroutines = self.subroutines.clone();
+        for (addr, start) in &self.calls {
+            subroutines.entry(*addr).or_default().instructions += self.total - start;
+        }
+        subroutines
+    }
+
+    /// Ranks addresses, opcode classes and subroutines by instructions executed,
+    /// followed by the listing of `rom` annotated with the counts
+    fn report(&self, rom: &[u8]) -> String {
+        let disassembly = Disassembly::new(rom);
+        let label = |addr| disassembly.label(addr);
+        let percent = |count: u64| 100.0 * count as f64 / self.total.max(1) as f64;
+        let mut report = format!("Executed {} instructions\n", self.total);
+
+        report.push_str("\nHot spots\n   count       %  address  instruction\n");
+        let mut addresses = self.addresses.iter().collect::<Vec<_>>();
+        addresses.sort_by_key(|(addr, (_, count))| (Reverse(*count), **addr));
+        for (addr, (instruction, count)) in addresses.into_iter().take(HOT_SPOTS) {
+            report.push_str(&format!(
+                "{:>8}  {:>5.1}%  {:03x}      {}\n",
+                count,
+                percent(*count),
+                addr,
+                Style::Octo.instruction(*instruction, label)
+            ));
+        }
+
+        report.push_str("\nOpcode classes\n   count       %  class\n");
+        let mut classes = self.classes.iter().collect::<Vec<_>>();
+        classes.sort_by_key(|(class, count)| (Reverse(**count), **class));
+        for (class, count) in classes {
+            report.push_str(&format!(
+                "{:>8}  {:>5.1}%  {}\n",
+                count,
+                percent(*count),
+                class
+            ));
+        }
+
+        report.push_str("\nSubroutines\n   calls  instructions       %  subroutine\n");
+        let mut subroutines = self.subroutines().into_iter().collect::<Vec<_>>();
+        subroutines.sort_by_key(|(addr, subroutine)| (Reverse(subroutine.instructions), *addr));
+        for (addr, subroutine) in subroutines {
+            report.push_str(&format!(
+                "{:>8}  {:>12}  {:>5.1}%  {}\n",
+                subroutine.calls,
+                subroutine.instructions,
+                percent(subroutine.instructions),
+                label(addr).unwrap_or_else(|| LabelKind::Subroutine.name(addr))
+            ));
+        }
+
+        report.push_str("\nAnnotated listing\n");
+        report.push_str(&disassembly.annotated_listing(Style::Octo, 16, |addr| {
+            let (_, count) = self.addresses.get(&addr)?;
+            Some(format!("{:>8}  {:>5.1}%", count, percent(*count)))
+        }));
+        report
+    }
+}
+
+impl<D> Chip8<D>
+where
+    D: Display,
+{
+    /// Counts every instruction executed from now on
+    pub fn start_profiling(&mut self) {
+        self.profiler = Some(Profiler::default());
+    }
+
+    /// Report of the instructions executed since profiling started
+    pub fn profile_report(&self) -> Option<String> {
+        Some(self.profiler.as_ref()?.report(self.rom()))
+    }
+
+    pub(super) fn profile(&mut self, pc: u16, instruction: Chip8Instruction) {
+        if let Some(profiler) = &mut self.profiler {
+            profiler.record(pc, instruction);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{chip8::compat::Compatibility, display::test_display::TestDisplay};
+    use rstest::*;
+    use twelve_bit::u12::*;
+
+    /// Calls a subroutine twice and loops
+    static PROGRAM: [u8; 10] = [
+        0x22, 0x06, // 0x200: call 0x206
+        0x22, 0x06, // 0x202: call 0x206
+        0x12, 0x04, // 0x204: loop
+        0x71, 0x01, // 0x206: v1 += 1
+        0x00, 0xee, // 0x208: return
+    ];
+
+    fn profiled(steps: usize) -> Chip8<TestDisplay> {
+        let mut chip8 = Chip8::new(TestDisplay::new(), Compatibility::Cosmac);
+        chip8.memory[0x200..0x20a].copy_from_slice(&PROGRAM);
+        chip8.rom_len = PROGRAM.len();
+        chip8.pc = u12![0x200];
+        chip8.start_profiling();
+
+        for _ in 0..steps {
+            chip8.step().unwrap();
+        }
+        chip8
+    }
+
+    #[rstest]
+    fn test_counts() {
+        let chip8 = profiled(8);
+        let profiler = chip8.profiler.as_ref().unwrap();
+
+        assert_eq!(profiler.total, 8);
+        assert_eq!(
+            profiler
+                .addresses
+                .iter()
+                .map(|(addr, (_, count))| (*addr, *count))
+                .collect::<Vec<_>>(),
+            vec![(0x200, 1), (0x202, 1), (0x204, 2), (0x206, 2), (0x208, 2)]
+        );
+        assert_eq!(profiler.classes.get("2NNN"), Some(&2));
+        assert_eq!(profiler.classes.get("00EE"), Some(&2));
+        assert_eq!(
+            profiler.subroutines.get(&0x206),
+            Some(&Subroutine {
+                calls: 2,
+                instructions: 4
+            })
+        );
+    }
+
+    #[rstest]
+    fn test_running_call_counts_until_now() {
+        let chip8 = profiled(5);
+        let profiler = chip8.profiler.as_ref().unwrap();
+
+        assert_eq!(
+            profiler.subroutines().get(&0x206),
+            Some(&Subroutine {
+                calls: 2,
+                instructions: 3
+            })
+        );
+    }
+
+    #[rstest]
+    fn test_report() {
+        assert_eq!(
+            profiled(8).profile_report().unwrap(),
+            "Executed 8 instructions
+
+Hot spots
+   count       %  address  instruction
+       2   25.0%  204      jump label_204
+       2   25.0%  206      v1 += 0x01
+       2   25.0%  208      return
+       1   12.5%  200      sub_206
+       1   12.5%  202      sub_206
+
+Opcode classes
+   count       %  class
+       2   25.0%  00EE
+       2   25.0%  1NNN
+       2   25.0%  2NNN
+       2   25.0%  7XNN
+
+Subroutines
+   calls  instructions       %  subroutine
+       2             4   50.0%  sub_206
+
+Annotated listing
+                : main
+       1   12.5%    sub_206
+       1   12.5%    sub_206
+                : label_204
+       2   25.0%    jump label_204
+                : sub_206
+       2   25.0%    v1 += 0x01
+       2   25.0%    return
+"
+        );
+    }
+
+    #[rstest]
+    fn test_no_report_without_profiling() {
+        let chip8 = Chip8::new(TestDisplay::new(), Compatibility::Cosmac);
+        assert_eq!(chip8.profile_report(), None);
+    }
+}
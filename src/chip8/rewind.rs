@@ -0,0 +1,178 @@
+use std::collections::VecDeque;
+
+/// Differing bytes closer together than this are stored as a single run
+const RUN_MERGE_GAP: usize = 8;
+
+/// Ring buffer of recent machine snapshots for stepping backwards.
+///
+/// Only the newest snapshot is kept in full. Every older snapshot is stored as
+/// a delta that turns its successor back into it, so rewinding walks backwards
+/// from the newest snapshot and forgetting the oldest one is a single pop.
+pub struct RewindBuffer {
+    /// Maximum number of snapshots kept, including the newest one
+    depth: usize,
+
+    newest: Option<Vec<u8>>,
+    /// Oldest delta first, the last one restores the snapshot before `newest`
+    deltas: VecDeque<Delta>,
+}
+
+impl RewindBuffer {
+    pub fn new(depth: usize) -> Self {
+        RewindBuffer {
+            depth,
+            newest: None,
+            deltas: VecDeque::new(),
+        }
+    }
+
+    /// Records a snapshot, forgetting the oldest one once the buffer is full
+    pub fn push(&mut self, snapshot: Vec<u8>) {
+        if self.depth == 0 {
+            return;
+        }
+
+        if let Some(previous) = self.newest.take() {
+            self.deltas.push_back(Delta::between(&snapshot, &previous));
+            if self.deltas.len() >= self.depth {
+                self.deltas.pop_front();
+            }
+        }
+
+        self.newest = Some(snapshot);
+    }
+
+    /// Drops the newest snapshot and returns the one recorded before it
+    pub fn rewind(&mut self) -> Option<&[u8]> {
+        let delta = self.deltas.pop_back()?;
+        let newest = self.newest.as_mut()?;
+        delta.apply(newest);
+
+        Some(newest)
+    }
+
+    /// Forgets every snapshot
+    pub fn clear(&mut self) {
+        self.newest = None;
+        self.deltas.clear();
+    }
+}
+
+/// Byte runs turning one snapshot into another
+struct Delta {
+    len: usize,
+    runs: Vec<(usize, Vec<u8>)>,
+}
+
+impl Delta {
+    /// Builds the delta turning `from` into `to`
+    fn between(from: &[u8], to: &[u8]) -> Self {
+        let mut runs: Vec<(usize, Vec<u8>)> = vec![];
+
+        for (i, &byte) in to.iter().enumerate() {
+            if from.get(i) == Some(&byte) {
+                continue;
+            }
+
+            match runs.last_mut() {
+                Some((start, run)) if i - (*start + run.len()) < RUN_MERGE_GAP => {
+                    run.extend_from_slice(&to[*start + run.len()..=i]);
+                }
+                _ => runs.push((i, vec![byte])),
+            }
+        }
+
+        Delta {
+            len: to.len(),
+            runs,
+        }
+    }
+
+    fn apply(&self, snapshot: &mut Vec<u8>) {
+        snapshot.resize(self.len, 0);
+        for (start, run) in &self.runs {
+            snapshot[*start..*start + run.len()].copy_from_slice(run);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    fn test_rewind_returns_snapshots_newest_first() {
+        let mut buffer = RewindBuffer::new(10);
+        buffer.push(vec![1, 2, 3, 4]);
+        buffer.push(vec![1, 9, 3, 4]);
+        buffer.push(vec![1, 9, 3, 4, 5]);
+
+        assert_eq!(buffer.rewind(), Some(&[1, 9, 3, 4][..]));
+        assert_eq!(buffer.rewind(), Some(&[1, 2, 3, 4][..]));
+        assert_eq!(buffer.rewind(), None);
+        assert_eq!(buffer.newest, Some(vec![1, 2, 3, 4]));
+    }
+
+    #[rstest]
+    fn test_push_after_rewind_continues_from_rewound_snapshot() {
+        let mut buffer = RewindBuffer::new(10);
+        buffer.push(vec![1]);
+        buffer.push(vec![2]);
+        buffer.rewind();
+        buffer.push(vec![3]);
+
+        assert_eq!(buffer.rewind(), Some(&[1][..]));
+    }
+
+    #[rstest]
+    fn test_depth_forgets_oldest_snapshots() {
+        let mut buffer = RewindBuffer::new(3);
+        for i in 0..10 {
+            buffer.push(vec![i]);
+        }
+
+        assert_eq!(buffer.deltas.len(), 2);
+        assert_eq!(buffer.rewind(), Some(&[8][..]));
+        assert_eq!(buffer.rewind(), Some(&[7][..]));
+        assert_eq!(buffer.rewind(), None);
+    }
+
+    #[rstest]
+    fn test_zero_depth_disables_rewind() {
+        let mut buffer = RewindBuffer::new(0);
+        buffer.push(vec![1]);
+
+        assert_eq!(buffer.newest, None);
+        assert_eq!(buffer.rewind(), None);
+    }
+
+    #[rstest]
+    fn test_deltas_store_only_changed_bytes() {
+        let mut buffer = RewindBuffer::new(100);
+        let mut snapshot = vec![0; 4096];
+        for i in 0..100 {
+            snapshot[0x200 + i] = i as u8;
+            buffer.push(snapshot.clone());
+        }
+
+        // Every delta holds just the one byte that changed
+        assert_eq!(buffer.deltas.len(), 99);
+        for delta in &buffer.deltas {
+            assert_eq!(delta.runs.len(), 1);
+            assert_eq!(delta.runs[0].1.len(), 1);
+        }
+    }
+
+    #[rstest]
+    #[case::same_length(vec![0, 1, 2, 3, 4, 5], vec![0, 9, 2, 3, 4, 8])]
+    #[case::longer(vec![0, 1], vec![0, 1, 2, 3])]
+    #[case::shorter(vec![0, 1, 2, 3], vec![5, 1])]
+    fn test_delta_round_trip(#[case] from: Vec<u8>, #[case] to: Vec<u8>) {
+        let delta = Delta::between(&from, &to);
+        let mut snapshot = from.clone();
+        delta.apply(&mut snapshot);
+
+        assert_eq!(snapshot, to);
+    }
+}
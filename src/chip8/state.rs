@@ -0,0 +1,349 @@
+use crate::{
+    chip8::{compat::Compatibility, Chip8},
+    display::{framebuffer::Framebuffer, Display},
+};
+use twelve_bit::u12::*;
+
+/// Identifies a save state file
+const MAGIC: &[u8; 4] = b"C8ST";
+
+/// Bumped whenever the layout below changes
+const VERSION: u16 = 5;
+
+// Save state layout, all values little endian:
+//
+//   magic            4 bytes  "C8ST"
+//   version          u16
+//   rom hash         u64      FNV-1a of the loaded ROM
+//   compatibility    u8       0 = COSMAC, 1 = CHIP-48
+//   cycles per frame u32
+//   pc               u16
+//   i                u16
+//   v registers      16 bytes
+//   keypad           u16      bit n set while key n is held
+//   sound timer      u8
+//   pitch            u8
+//   audio pattern    u8       1 once loaded, followed by its 16 bytes
+//   stack depth      u32, followed by one u16 per entry
+//   memory           4096 bytes
+//   framebuffer      width u16, height u16, then width / 8 bytes per row
+impl<D> Chip8<D>
+where
+    D: Display,
+{
+    /// Serialises the complete machine state
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut state = Vec::with_capacity(self.memory.len() + 512);
+
+        state.extend_from_slice(MAGIC);
+        state.extend_from_slice(&VERSION.to_le_bytes());
+        state.extend_from_slice(&self.rom_hash.to_le_bytes());
+
+        state.push(match self.compatibility {
+            Compatibility::Cosmac => 0,
+            Compatibility::Chip48 => 1,
+        });
+        state.extend_from_slice(&self.cycles_per_frame.to_le_bytes());
+
+        state.extend_from_slice(&u16::from(self.pc).to_le_bytes());
+        state.extend_from_slice(&self.i_reg.to_le_bytes());
+        state.extend_from_slice(&self.v_reg);
+        state.extend_from_slice(&self.keypad.to_le_bytes());
+        state.push(self.sound_timer);
+        state.push(self.pitch);
+        match self.audio_pattern {
+            Some(pattern) => {
+                state.push(1);
+                state.extend_from_slice(&pattern);
+            }
+            None => state.push(0),
+        }
+
+        state.extend_from_slice(&(self.stack.len() as u32).to_le_bytes());
+        for addr in &self.stack {
+            state.extend_from_slice(&u16::from(*addr).to_le_bytes());
+        }
+
+        state.extend_from_slice(&self.memory);
+
+        let (width, height) = (self.display_buffer.width(), self.display_buffer.height());
+        state.extend_from_slice(&(width as u16).to_le_bytes());
+        state.extend_from_slice(&(height as u16).to_le_bytes());
+        for y in 0..height {
+            let row = self.display_buffer.row(y).to_be_bytes();
+            state.extend_from_slice(&row[row.len() - width / 8..]);
+        }
+
+        state
+    }
+
+    /// Restores a state created by [`Chip8::save_state`] for the currently loaded ROM.
+    ///
+    /// The machine is left untouched if the state can't be loaded.
+    pub fn load_state(&mut self, state: &[u8]) -> Result<(), String> {
+        let mut reader = ByteReader::new(state, "Save state");
+
+        if reader.bytes(MAGIC.len())? != MAGIC {
+            return Err("Not a save state file".to_string());
+        }
+        let version = reader.u16()?;
+        if version != VERSION {
+            return Err(format!("Unsupported save state version: {}", version));
+        }
+        if reader.u64()? != self.rom_hash {
+            return Err("Save state was made for a different ROM".to_string());
+        }
+
+        let compatibility = match reader.u8()? {
+            0 => Compatibility::Cosmac,
+            1 => Compatibility::Chip48,
+            other => return Err(format!("Invalid compatibility mode: {}", other)),
+        };
+        let cycles_per_frame = reader.u32()?;
+
+        let pc = reader.addr()?;
+        let i_reg = u16::from(reader.addr()?);
+        let v_reg = reader.bytes(self.v_reg.len())?.to_vec();
+        let keypad = reader.u16()?;
+        let sound_timer = reader.u8()?;
+        let pitch = reader.u8()?;
+        let audio_pattern = match reader.u8()? {
+            0 => None,
+            1 => Some(reader.bytes(16)?.try_into().unwrap()),
+            other => return Err(format!("Invalid audio pattern flag: {}", other)),
+        };
+
+        // The stack isn't limited, a truncated state runs out of bytes instead
+        let stack_depth = reader.u32()?;
+        let mut stack = vec![];
+        for _ in 0..stack_depth {
+            stack.push(reader.addr()?);
+        }
+
+        let memory = reader.bytes(self.memory.len())?.to_vec();
+
+        let width = reader.u16()? as usize;
+        let height = reader.u16()? as usize;
+        if (width, height) != (self.display_buffer.width(), self.display_buffer.height()) {
+            return Err(format!(
+                "Save state framebuffer is {}x{}, expected {}x{}",
+                width,
+                height,
+                self.display_buffer.width(),
+                self.display_buffer.height()
+            ));
+        }
+        let mut display_buffer = Framebuffer::new(width, height);
+        for y in 0..height {
+            let mut row = [0; 16];
+            row[16 - width / 8..].copy_from_slice(reader.bytes(width / 8)?);
+            display_buffer.set_row(y, u128::from_be_bytes(row));
+        }
+
+        reader.finish()?;
+
+        self.compatibility = compatibility;
+        self.cycles_per_frame = cycles_per_frame;
+        self.pc = pc;
+        self.i_reg = i_reg;
+        self.v_reg = v_reg;
+        self.keypad = keypad;
+        self.sound_timer = sound_timer;
+        self.pitch = pitch;
+        self.audio_pattern = audio_pattern;
+        self.stack = stack;
+        self.memory = memory;
+        self.display_buffer = display_buffer;
+
+        Ok(())
+    }
+
+    pub fn save_state_file(&self, path: &str) -> Result<(), String> {
+        std::fs::write(path, self.save_state())
+            .map_err(|e| format!("Failed to write save state {}: {}", path, e))
+    }
+
+    pub fn load_state_file(&mut self, path: &str) -> Result<(), String> {
+        let state = std::fs::read(path)
+            .map_err(|e| format!("Failed to read save state {}: {}", path, e))?;
+        self.load_state(&state)
+    }
+}
+
+/// 64 bit FNV-1a hash, used to identify ROMs and compare machine states
+pub(super) fn fnv1a(data: &[u8]) -> u64 {
+    fnv1a_continue(0xcbf29ce484222325, data)
+}
+
+/// Continues an FNV-1a hash with more data
+pub(super) fn fnv1a_continue(hash: u64, data: &[u8]) -> u64 {
+    data.iter().fold(hash, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Reads the little endian values of the binary file formats
+pub(super) struct ByteReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    /// Name of the format used in error messages
+    what: &'static str,
+}
+
+impl<'a> ByteReader<'a> {
+    pub(super) fn new(data: &'a [u8], what: &'static str) -> Self {
+        ByteReader { data, pos: 0, what }
+    }
+
+    pub(super) fn bytes(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + len)
+            .ok_or(format!("{} is truncated", self.what))?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    pub(super) fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    pub(super) fn u16(&mut self) -> Result<u16, String> {
+        Ok(u16::from_le_bytes(self.bytes(2)?.try_into().unwrap()))
+    }
+
+    pub(super) fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    pub(super) fn u64(&mut self) -> Result<u64, String> {
+        Ok(u64::from_le_bytes(self.bytes(8)?.try_into().unwrap()))
+    }
+
+    /// Fails if anything is left after the last value
+    pub(super) fn finish(&self) -> Result<(), String> {
+        if self.pos != self.data.len() {
+            return Err(format!(
+                "Unexpected data at the end of the {}",
+                self.what.to_lowercase()
+            ));
+        }
+        Ok(())
+    }
+
+    fn addr(&mut self) -> Result<U12, String> {
+        match self.u16()? {
+            addr @ 0..=0xfff => Ok(u12![addr]),
+            addr => Err(format!("Invalid address: {:#06x}", addr)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::test_display::TestDisplay;
+    use rstest::*;
+
+    #[rstest]
+    fn test_save_and_load_state() {
+        let mut chip8 = get_test_chip8();
+        chip8.compatibility = Compatibility::Chip48;
+        chip8.cycles_per_frame = 7;
+        chip8.pc = u12![0x234];
+        chip8.i_reg = 0x345;
+        chip8.v_reg[0x3] = 0x42;
+        chip8.v_reg[0xf] = 1;
+        chip8.keypad = 0b1000_0000_0000_0010;
+        chip8.sound_timer = 9;
+        chip8.pitch = 80;
+        chip8.audio_pattern = Some([0x5a; 16]);
+        chip8.stack = vec![u12![0x202], u12![0x2f0]];
+        chip8.memory[0x300] = 0xab;
+        chip8.display_buffer.draw_sprite(60, 31, &[0xff]);
+
+        let state = chip8.save_state();
+        let mut restored = get_test_chip8();
+        restored.load_state(&state).unwrap();
+
+        assert_eq!(restored.compatibility, Compatibility::Chip48);
+        assert_eq!(restored.cycles_per_frame, 7);
+        assert_eq!(restored.pc, u12![0x234]);
+        assert_eq!(restored.i_reg, 0x345);
+        assert_eq!(restored.v_reg, chip8.v_reg);
+        assert_eq!(restored.keypad, chip8.keypad);
+        assert_eq!(restored.sound_timer, 9);
+        assert_eq!(restored.pitch, 80);
+        assert_eq!(restored.audio_pattern, Some([0x5a; 16]));
+        assert_eq!(restored.stack, chip8.stack);
+        assert_eq!(restored.memory, chip8.memory);
+        assert_eq!(restored.display_buffer, chip8.display_buffer);
+    }
+
+    #[rstest]
+    #[case::beyond_16_levels(17)]
+    #[case::beyond_a_byte(300)]
+    fn test_save_and_load_deep_stack(#[case] depth: usize) {
+        let mut chip8 = get_test_chip8();
+        chip8.memory[0x200..0x202].copy_from_slice(&[0x22, 0x00]); // call 0x200
+        chip8.pc = u12![0x200];
+        for _ in 0..depth {
+            chip8.step().unwrap();
+        }
+        chip8.memory[0x300] = 0xab;
+
+        let mut restored = get_test_chip8();
+        restored.load_state(&chip8.save_state()).unwrap();
+
+        assert_eq!(restored.stack.len(), depth);
+        assert_eq!(restored.stack, chip8.stack);
+        assert_eq!(restored.memory, chip8.memory);
+    }
+
+    #[rstest]
+    fn test_load_state_for_different_rom() {
+        let chip8 = get_test_chip8();
+        let state = chip8.save_state();
+
+        let mut other = get_test_chip8();
+        other.rom_hash = fnv1a(&[0x12, 0x00]);
+
+        assert_eq!(
+            other.load_state(&state),
+            Err("Save state was made for a different ROM".to_string())
+        );
+    }
+
+    #[rstest]
+    #[case::bad_magic(0, 0x00, "Not a save state file")]
+    #[case::bad_version(4, 0x01, "Unsupported save state version: 1")]
+    #[case::bad_compatibility(14, 0x05, "Invalid compatibility mode: 5")]
+    #[case::pc_out_of_memory(20, 0x10, "Invalid address: 0x1000")]
+    #[case::i_out_of_memory(22, 0x10, "Invalid address: 0x1000")]
+    #[case::bad_audio_pattern(43, 0x02, "Invalid audio pattern flag: 2")]
+    fn test_load_invalid_state(#[case] offset: usize, #[case] value: u8, #[case] error: &str) {
+        let mut chip8 = get_test_chip8();
+        let mut state = chip8.save_state();
+        state[offset] = value;
+
+        assert_eq!(chip8.load_state(&state), Err(error.to_string()));
+    }
+
+    #[rstest]
+    fn test_load_truncated_state_leaves_machine_untouched() {
+        let mut chip8 = get_test_chip8();
+        let mut state = chip8.save_state();
+        state.pop();
+        chip8.v_reg[0] = 0x12;
+
+        assert_eq!(
+            chip8.load_state(&state),
+            Err("Save state is truncated".to_string())
+        );
+        assert_eq!(chip8.v_reg[0], 0x12);
+    }
+
+    fn get_test_chip8() -> Chip8<TestDisplay> {
+        Chip8::new(TestDisplay::new(), Compatibility::Cosmac)
+    }
+}
@@ -0,0 +1,486 @@
+use std::{collections::VecDeque, fmt};
+
+use crate::{
+    chip8::{json::Json, trace::Record, Chip8},
+    display::Display,
+};
+
+/// First point where two executions of a program part ways
+#[derive(Debug, PartialEq)]
+pub struct Divergence {
+    cycle: u64,
+    /// What differs, e.g. `v1: 04 != 02`
+    reason: String,
+    /// Label of each side and its trace lines around the difference
+    sides: [(String, Vec<String>); 2],
+    /// Index of the differing line in both sides
+    at: usize,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "First difference at cycle {}: {}",
+            self.cycle, self.reason
+        )?;
+        for ((label, lines), marker) in self.sides.iter().zip(["---", "+++"]) {
+            writeln!(f, "{} {}", marker, label)?;
+            for (n, line) in lines.iter().enumerate() {
+                let prefix = if n == self.at { ">" } else { " " };
+                writeln!(f, "{} {}", prefix, line)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Instruction read back from a trace file
+#[derive(Debug, PartialEq)]
+struct Traced {
+    cycle: u64,
+    pc: u16,
+    opcode: u16,
+    i_reg: u16,
+    /// Registers the instruction changed, with their old and new value
+    changes: Vec<(usize, u8, u8)>,
+    line: String,
+}
+
+impl Traced {
+    /// Parses a record in either trace format
+    fn parse(line: &str) -> Option<Self> {
+        if line.starts_with('{') {
+            Self::parse_json(line)
+        } else {
+            Self::parse_text(line)
+        }
+    }
+
+    fn parse_text(line: &str) -> Option<Self> {
+        let mut fields = line.split_whitespace();
+        let cycle = fields.next()?.parse().ok()?;
+        let pc = u16::from_str_radix(fields.next()?, 16).ok()?;
+        let opcode = u16::from_str_radix(fields.next()?, 16).ok()?;
+        // The mnemonic spans several fields, I follows it
+        let i_reg =
+            u16::from_str_radix(fields.find_map(|field| field.strip_prefix("i="))?, 16).ok()?;
+        let changes = fields
+            .map(|field| {
+                let (x, values) = field.strip_prefix('v')?.split_once('=')?;
+                let (before, after) = values.split_once("->")?;
+                Some((
+                    usize::from_str_radix(x, 16).ok()?,
+                    u8::from_str_radix(before, 16).ok()?,
+                    u8::from_str_radix(after, 16).ok()?,
+                ))
+            })
+            .collect::<Option<_>>()?;
+
+        Some(Traced {
+            cycle,
+            pc,
+            opcode,
+            i_reg,
+            changes,
+            line: line.to_string(),
+        })
+    }
+
+    fn parse_json(line: &str) -> Option<Self> {
+        let json = Json::parse(line).ok()?;
+        let number = |name| match json.get(name) {
+            Some(Json::Number(number)) => Some(*number as u64),
+            _ => None,
+        };
+        let Some(Json::Object(changes)) = json.get("changes") else {
+            return None;
+        };
+        let changes = changes
+            .iter()
+            .map(|(x, values)| match values {
+                Json::Array(values) => match values.as_slice() {
+                    [Json::Number(before), Json::Number(after)] => Some((
+                        usize::from_str_radix(x.strip_prefix('v')?, 16).ok()?,
+                        *before as u8,
+                        *after as u8,
+                    )),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .collect::<Option<_>>()?;
+
+        Some(Traced {
+            cycle: number("cycle")?,
+            pc: number("pc")? as u16,
+            opcode: number("opcode")? as u16,
+            i_reg: number("i")? as u16,
+            changes,
+            line: line.to_string(),
+        })
+    }
+
+    fn change(&self, x: usize) -> Option<(u8, u8)> {
+        self.changes
+            .iter()
+            .find(|(reg, _, _)| *reg == x)
+            .map(|(_, before, after)| (*before, *after))
+    }
+
+    fn difference(&self, other: &Traced) -> Option<String> {
+        if self.cycle != other.cycle {
+            return Some(format!("cycle: {} != {}", self.cycle, other.cycle));
+        }
+        if self.pc != other.pc {
+            return Some(format!("pc: {:03x} != {:03x}", self.pc, other.pc));
+        }
+        if self.opcode != other.opcode {
+            return Some(format!(
+                "opcode: {:04x} != {:04x}",
+                self.opcode, other.opcode
+            ));
+        }
+        if self.i_reg != other.i_reg {
+            return Some(format!("i: {:03x} != {:03x}", self.i_reg, other.i_reg));
+        }
+        let describe = |change: Option<(u8, u8)>| match change {
+            Some((before, after)) => format!("{:02x}->{:02x}", before, after),
+            None => "unchanged".to_string(),
+        };
+        (0..16).find_map(|x| {
+            let (a, b) = (self.change(x), other.change(x));
+            (a != b).then(|| format!("v{:x}: {} != {}", x, describe(a), describe(b)))
+        })
+    }
+}
+
+fn parse_trace(text: &str) -> Result<Vec<Traced>, String> {
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(n, line)| {
+            Traced::parse(line).ok_or_else(|| format!("Line {}: Invalid trace record", n + 1))
+        })
+        .collect()
+}
+
+/// Compares two trace files record by record.
+///
+/// Traces only hold the registers, so memory differences show up once they
+/// reach a register or the control flow.
+pub fn diff_files(paths: [&str; 2], context: usize) -> Result<Option<Divergence>, String> {
+    let mut texts = [String::new(), String::new()];
+    for (text, path) in texts.iter_mut().zip(paths) {
+        *text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read trace {}: {}", path, e))?;
+    }
+    diff_traces([&texts[0], &texts[1]], paths, context)
+}
+
+fn diff_traces(
+    texts: [&str; 2],
+    labels: [&str; 2],
+    context: usize,
+) -> Result<Option<Divergence>, String> {
+    let [a, b] =
+        [0, 1].map(|side| parse_trace(texts[side]).map_err(|e| format!("{}: {}", labels[side], e)));
+    let (a, b) = (a?, b?);
+
+    let Some((index, reason)) = (0..a.len().max(b.len())).find_map(|n| {
+        match (a.get(n), b.get(n)) {
+            (Some(a), Some(b)) => a.difference(b),
+            (_, None) => Some(format!("{} ends", labels[1])),
+            (None, _) => Some(format!("{} ends", labels[0])),
+        }
+        .map(|reason| (n, reason))
+    }) else {
+        return Ok(None);
+    };
+
+    let start = index.saturating_sub(context);
+    let lines = |trace: &[Traced]| {
+        trace
+            .iter()
+            .skip(start)
+            .take(index - start + context + 1)
+            .map(|traced| traced.line.clone())
+            .collect()
+    };
+    Ok(Some(Divergence {
+        cycle: a
+            .get(index)
+            .or(b.get(index))
+            .map_or(0, |traced| traced.cycle),
+        reason,
+        sides: [
+            (labels[0].to_string(), lines(&a)),
+            (labels[1].to_string(), lines(&b)),
+        ],
+        at: index - start,
+    }))
+}
+
+/// Runs two machines in lockstep for up to `steps` instructions and compares
+/// pc, registers, I, the stack and memory after every instruction
+pub fn diff_runs<D: Display>(
+    mut machines: [Chip8<D>; 2],
+    labels: [String; 2],
+    steps: u64,
+    context: usize,
+) -> Option<Divergence> {
+    let mut history = VecDeque::new();
+    for cycle in 0..steps {
+        let [(line_a, result_a), (line_b, result_b)] =
+            machines.each_mut().map(|chip8| chip8.traced_step(cycle));
+        let reason = match (&result_a, &result_b) {
+            (Err(e), Ok(())) => Some(format!("{} stopped: {}", labels[0], e)),
+            (Ok(()), Err(e)) => Some(format!("{} stopped: {}", labels[1], e)),
+            _ => machines[0].difference(&machines[1]),
+        };
+        history.push_back([line_a, line_b]);
+
+        let Some(reason) = reason else {
+            // Both stopped on the same instruction in the same state
+            if result_a.is_err() {
+                return None;
+            }
+            if history.len() > context {
+                history.pop_front();
+            }
+            continue;
+        };
+
+        let mut sides = [0, 1].map(|side| {
+            history
+                .iter()
+                .map(|lines: &[String; 2]| lines[side].clone())
+                .collect::<Vec<_>>()
+        });
+        for ((chip8, lines), result) in machines
+            .iter_mut()
+            .zip(&mut sides)
+            .zip([result_a, result_b])
+        {
+            let mut running = result.is_ok();
+            for cycle in cycle + 1..=cycle + context as u64 {
+                if !running {
+                    break;
+                }
+                let (line, result) = chip8.traced_step(cycle);
+                lines.push(line);
+                running = result.is_ok();
+            }
+        }
+
+        let [a, b] = sides;
+        let [label_a, label_b] = labels;
+        return Some(Divergence {
+            cycle,
+            reason,
+            sides: [(label_a, a), (label_b, b)],
+            at: history.len() - 1,
+        });
+    }
+    None
+}
+
+impl<D> Chip8<D>
+where
+    D: Display,
+{
+    /// Steps once, returning the instruction's line in the text trace format
+    fn traced_step(&mut self, cycle: u64) -> (String, Result<(), String>) {
+        let pc = u16::from(self.pc);
+        let opcode = u16::from_be_bytes([
+            self.memory[pc as usize],
+            self.memory[(pc as usize + 1) % self.memory.len()],
+        ]);
+        let before = self.v_reg.clone();
+
+        match self.step() {
+            Ok(instruction) => {
+                let record = Record {
+                    cycle,
+                    pc,
+                    opcode,
+                    instruction,
+                    before: &before,
+                    after: &self.v_reg,
+                    i_reg: self.i_reg,
+                };
+                (record.to_string(), Ok(()))
+            }
+            Err(e) => (
+                format!("{:>8}  {:03x}  {:04x}  {}", cycle, pc, opcode, e),
+                Err(e),
+            ),
+        }
+    }
+
+    /// First part of the state that differs from `other`
+    fn difference(&self, other: &Self) -> Option<String> {
+        if self.pc != other.pc {
+            return Some(format!(
+                "pc: {:03x} != {:03x}",
+                u16::from(self.pc),
+                u16::from(other.pc)
+            ));
+        }
+        if let Some(x) = (0..self.v_reg.len()).find(|x| self.v_reg[*x] != other.v_reg[*x]) {
+            return Some(format!(
+                "v{:x}: {:02x} != {:02x}",
+                x, self.v_reg[x], other.v_reg[x]
+            ));
+        }
+        if self.i_reg != other.i_reg {
+            return Some(format!("i: {:03x} != {:03x}", self.i_reg, other.i_reg));
+        }
+        if self.stack != other.stack {
+            return Some(format!(
+                "stack: {:03x?} != {:03x?}",
+                self.stack(),
+                other.stack()
+            ));
+        }
+        (0..self.memory.len())
+            .find(|addr| self.memory[*addr] != other.memory[*addr])
+            .map(|addr| {
+                format!(
+                    "memory {:03x}: {:02x} != {:02x}",
+                    addr, self.memory[addr], other.memory[addr]
+                )
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{chip8::compat::Compatibility, display::test_display::TestDisplay};
+    use rstest::*;
+    use twelve_bit::u12::*;
+
+    static TRACE: &str = "       0  200  61ff  LD V1, #ff        i=000  v1=00->ff
+       1  202  7201  ADD V2, #01       i=000  v2=00->01
+       2  204  8214  ADD V2, V1        i=000  v2=01->00  vf=00->01
+       3  206  1200  JP #200           i=000
+";
+
+    static JSON_TRACE: &str = r##"{"cycle":0,"pc":512,"opcode":25087,"instruction":"LD V1, #ff","i":0,"changes":{"v1":[0,255]}}
+{"cycle":1,"pc":514,"opcode":29185,"instruction":"ADD V2, #01","i":0,"changes":{"v2":[0,1]}}
+{"cycle":2,"pc":516,"opcode":33300,"instruction":"ADD V2, V1","i":0,"changes":{"v2":[1,0],"vf":[0,1]}}
+{"cycle":3,"pc":518,"opcode":4608,"instruction":"JP #200","i":0,"changes":{}}
+"##;
+
+    #[rstest]
+    fn test_parse_formats() {
+        let text = parse_trace(TRACE).unwrap();
+        let json = parse_trace(JSON_TRACE).unwrap();
+        assert_eq!(text.len(), 4);
+        assert_eq!(text[2].changes, vec![(2, 1, 0), (0xf, 0, 1)]);
+        for (text, json) in text.iter().zip(&json) {
+            assert_eq!(text.difference(json), None);
+        }
+    }
+
+    #[rstest]
+    fn test_parse_error() {
+        assert_eq!(
+            diff_traces([TRACE, "\nnot a trace"], ["a", "b"], 1),
+            Err("b: Line 2: Invalid trace record".to_string())
+        );
+    }
+
+    #[rstest]
+    #[case::same(TRACE, None)]
+    #[case::json(JSON_TRACE, None)]
+    #[case::register(
+        &TRACE.replace("v2=01->00  vf=00->01", "v2=01->00"),
+        Some((2, "vf: 00->01 != unchanged"))
+    )]
+    #[case::pc(&TRACE.replace("3  206", "3  208"), Some((3, "pc: 206 != 208")))]
+    #[case::shorter(&TRACE[..TRACE.len() - 45], Some((3, "b ends")))]
+    fn test_diff_traces(#[case] other: &str, #[case] expected: Option<(u64, &str)>) {
+        let divergence = diff_traces([TRACE, other], ["a", "b"], 1).unwrap();
+        assert_eq!(
+            divergence.map(|d| (d.cycle, d.reason)),
+            expected.map(|(cycle, reason)| (cycle, reason.to_string()))
+        );
+    }
+
+    #[rstest]
+    fn test_report() {
+        let other = TRACE.replace("i=000  v2=00->01", "i=000  v2=00->02");
+        let divergence = diff_traces([TRACE, &other], ["a.trace", "b.trace"], 1)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            divergence.to_string(),
+            "First difference at cycle 1: v2: 00->01 != 00->02
+--- a.trace
+         0  200  61ff  LD V1, #ff        i=000  v1=00->ff
+>        1  202  7201  ADD V2, #01       i=000  v2=00->01
+         2  204  8214  ADD V2, V1        i=000  v2=01->00  vf=00->01
++++ b.trace
+         0  200  61ff  LD V1, #ff        i=000  v1=00->ff
+>        1  202  7201  ADD V2, #01       i=000  v2=00->02
+         2  204  8214  ADD V2, V1        i=000  v2=01->00  vf=00->01
+"
+        );
+    }
+
+    fn machine(compatibility: Compatibility, program: &[u8]) -> Chip8<TestDisplay> {
+        let mut chip8 = Chip8::new(TestDisplay::new(), compatibility);
+        chip8.memory[0x200..0x200 + program.len()].copy_from_slice(program);
+        chip8.pc = u12![0x200];
+        chip8
+    }
+
+    /// Shifts v2 into v1, which only CHIP-48 does in place
+    static SHIFT: [u8; 8] = [
+        0x61, 0x05, // 0x200: v1 = 5
+        0x62, 0x08, // 0x202: v2 = 8
+        0x81, 0x26, // 0x204: v1 >>= 1, from v2 on COSMAC
+        0x12, 0x06, // 0x206: loop
+    ];
+
+    #[rstest]
+    fn test_diff_runs() {
+        let divergence = diff_runs(
+            [
+                machine(Compatibility::Cosmac, &SHIFT),
+                machine(Compatibility::Chip48, &SHIFT),
+            ],
+            ["cosmac".to_string(), "chip48".to_string()],
+            100,
+            1,
+        )
+        .unwrap();
+
+        assert_eq!(divergence.cycle, 2);
+        assert_eq!(divergence.reason, "v1: 04 != 02");
+        assert_eq!(divergence.at, 1);
+        assert_eq!(divergence.sides[0].1.len(), 3);
+        assert!(divergence.sides[1].1[1].ends_with("v1=05->02  vf=00->01"));
+    }
+
+    #[rstest]
+    fn test_diff_runs_same() {
+        let machines = [Compatibility::Cosmac, Compatibility::Cosmac].map(|c| machine(c, &SHIFT));
+        assert_eq!(
+            diff_runs(machines, ["a".to_string(), "b".to_string()], 100, 3),
+            None
+        );
+    }
+
+    #[rstest]
+    fn test_diff_runs_both_stopped() {
+        let machines =
+            [Compatibility::Cosmac, Compatibility::Chip48].map(|c| machine(c, &[0xff, 0xff]));
+        assert_eq!(
+            diff_runs(machines, ["a".to_string(), "b".to_string()], 100, 3),
+            None
+        );
+    }
+}
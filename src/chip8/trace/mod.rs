@@ -0,0 +1,276 @@
+pub mod diff;
+
+use std::{
+    fmt,
+    fs::File,
+    io::{BufWriter, Write},
+    ops::RangeInclusive,
+};
+
+use crate::{
+    chip8::{instruction::Chip8Instruction, Chip8},
+    disasm::Style,
+    display::Display,
+};
+
+/// Layout of trace records
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TraceFormat {
+    /// One aligned line per instruction
+    Text,
+    /// One JSON object per line
+    JsonLines,
+}
+
+/// Writes one record per executed instruction within the address and cycle ranges
+pub struct Tracer {
+    output: Box<dyn Write>,
+    format: TraceFormat,
+    addresses: RangeInclusive<u16>,
+    cycles: RangeInclusive<u64>,
+    /// Instructions executed since tracing started
+    cycle: u64,
+}
+
+/// Executed instruction with the registers before and after it
+struct Record<'a> {
+    cycle: u64,
+    pc: u16,
+    opcode: u16,
+    instruction: Chip8Instruction,
+    before: &'a [u8],
+    after: &'a [u8],
+    i_reg: u16,
+}
+
+impl Record<'_> {
+    /// Registers the instruction changed, with their old and new value
+    fn changes(&self) -> impl Iterator<Item = (usize, u8, u8)> + '_ {
+        self.before
+            .iter()
+            .zip(self.after)
+            .enumerate()
+            .filter(|(_, (before, after))| before != after)
+            .map(|(x, (before, after))| (x, *before, *after))
+    }
+
+    fn mnemonic(&self) -> String {
+        Style::Cowgod.instruction(self.instruction, |_| None)
+    }
+}
+
+/// The text format's line, without the newline
+impl fmt::Display for Record<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:>8}  {:03x}  {:04x}  {:<16}  i={:03x}",
+            self.cycle,
+            self.pc,
+            self.opcode,
+            self.mnemonic(),
+            self.i_reg
+        )?;
+        for (x, before, after) in self.changes() {
+            write!(f, "  v{:x}={:02x}->{:02x}", x, before, after)?;
+        }
+        Ok(())
+    }
+}
+
+impl Tracer {
+    pub fn new(
+        output: Box<dyn Write>,
+        format: TraceFormat,
+        addresses: RangeInclusive<u16>,
+        cycles: RangeInclusive<u64>,
+    ) -> Self {
+        Tracer {
+            output,
+            format,
+            addresses,
+            cycles,
+            cycle: 0,
+        }
+    }
+
+    /// Traces to a new file at `path`, buffered
+    pub fn create(
+        path: &str,
+        format: TraceFormat,
+        addresses: RangeInclusive<u16>,
+        cycles: RangeInclusive<u64>,
+    ) -> Result<Self, String> {
+        let file =
+            File::create(path).map_err(|e| format!("Failed to create trace {}: {}", path, e))?;
+        Ok(Tracer::new(
+            Box::new(BufWriter::new(file)),
+            format,
+            addresses,
+            cycles,
+        ))
+    }
+
+    /// Counts the instruction about to run at `pc`, returning its cycle when it is traced
+    fn begin(&mut self, pc: u16) -> Option<u64> {
+        let cycle = self.cycle;
+        self.cycle += 1;
+        (self.cycles.contains(&cycle) && self.addresses.contains(&pc)).then_some(cycle)
+    }
+
+    fn write(&mut self, record: &Record) -> std::io::Result<()> {
+        match self.format {
+            TraceFormat::Text => writeln!(self.output, "{}", record),
+            TraceFormat::JsonLines => {
+                let changes = record
+                    .changes()
+                    .map(|(x, before, after)| format!("\"v{:x}\":[{},{}]", x, before, after))
+                    .collect::<Vec<_>>()
+                    .join(",");
+                writeln!(
+                    self.output,
+                    "{{\"cycle\":{},\"pc\":{},\"opcode\":{},\"instruction\":\"{}\",\"i\":{},\"changes\":{{{}}}}}",
+                    record.cycle, record.pc, record.opcode, record.mnemonic(), record.i_reg, changes
+                )
+            }
+        }
+    }
+}
+
+impl<D> Chip8<D>
+where
+    D: Display,
+{
+    /// Traces every instruction executed from now on
+    pub fn set_tracer(&mut self, tracer: Tracer) {
+        self.tracer = Some(tracer);
+    }
+
+    /// Counts the instruction about to run at `pc` and snapshots the registers if it is traced
+    pub(super) fn begin_trace(&mut self, pc: u16) -> Option<(u64, Vec<u8>)> {
+        let cycle = self.tracer.as_mut()?.begin(pc)?;
+        Some((cycle, self.v_reg.clone()))
+    }
+
+    /// Writes the record of an executed instruction, stopping the trace if writing fails
+    pub(super) fn end_trace(
+        &mut self,
+        (cycle, before): (u64, Vec<u8>),
+        pc: u16,
+        opcode: u16,
+        instruction: Chip8Instruction,
+    ) {
+        let Some(tracer) = &mut self.tracer else {
+            return;
+        };
+        let record = Record {
+            cycle,
+            pc,
+            opcode,
+            instruction,
+            before: &before,
+            after: &self.v_reg,
+            i_reg: self.i_reg,
+        };
+
+        if let Err(e) = tracer.write(&record) {
+            eprintln!("Failed to write trace, tracing stopped: {}", e);
+            self.tracer = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use super::*;
+    use crate::{chip8::compat::Compatibility, display::test_display::TestDisplay};
+    use rstest::*;
+    use twelve_bit::u12::*;
+
+    /// Output shared with the test after the tracer takes ownership
+    #[derive(Clone, Default)]
+    struct SharedOutput(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedOutput {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Sets v1, adds v1 to v2 with a carry and loops
+    static PROGRAM: [u8; 8] = [
+        0x61, 0xff, // 0x200: v1 = 0xff
+        0x72, 0x01, // 0x202: v2 += 1
+        0x82, 0x14, // 0x204: v2 += v1
+        0x12, 0x00, // 0x206: jump to 0x200
+    ];
+
+    fn trace(
+        format: TraceFormat,
+        addresses: RangeInclusive<u16>,
+        cycles: RangeInclusive<u64>,
+        steps: usize,
+    ) -> String {
+        let output = SharedOutput::default();
+        let mut chip8 = Chip8::new(TestDisplay::new(), Compatibility::Cosmac);
+        chip8.memory[0x200..0x208].copy_from_slice(&PROGRAM);
+        chip8.pc = u12![0x200];
+        chip8.set_tracer(Tracer::new(
+            Box::new(output.clone()),
+            format,
+            addresses,
+            cycles,
+        ));
+
+        for _ in 0..steps {
+            chip8.step().unwrap();
+        }
+
+        String::from_utf8(output.0.take()).unwrap()
+    }
+
+    #[rstest]
+    fn test_text_format() {
+        assert_eq!(
+            trace(TraceFormat::Text, 0..=0xfff, 0..=u64::MAX, 4),
+            "       0  200  61ff  LD V1, #ff        i=000  v1=00->ff
+       1  202  7201  ADD V2, #01       i=000  v2=00->01
+       2  204  8214  ADD V2, V1        i=000  v2=01->00  vf=00->01
+       3  206  1200  JP #200           i=000
+"
+        );
+    }
+
+    #[rstest]
+    fn test_json_lines_format() {
+        assert_eq!(
+            trace(TraceFormat::JsonLines, 0..=0xfff, 0..=1, 4),
+            r##"{"cycle":0,"pc":512,"opcode":25087,"instruction":"LD V1, #ff","i":0,"changes":{"v1":[0,255]}}
+{"cycle":1,"pc":514,"opcode":29185,"instruction":"ADD V2, #01","i":0,"changes":{"v2":[0,1]}}
+"##
+        );
+    }
+
+    #[rstest]
+    #[case::addresses(0x204..=0x206, 0..=u64::MAX, &[2, 3, 6, 7])]
+    #[case::cycles(0..=0xfff, 3..=5, &[3, 4, 5])]
+    #[case::both(0x200..=0x200, 2..=8, &[4, 8])]
+    fn test_filters(
+        #[case] addresses: RangeInclusive<u16>,
+        #[case] cycles: RangeInclusive<u64>,
+        #[case] traced: &[u64],
+    ) {
+        let text = trace(TraceFormat::Text, addresses, cycles, 10);
+        let cycles = text
+            .lines()
+            .map(|line| line.split_whitespace().next().unwrap().parse().unwrap())
+            .collect::<Vec<u64>>();
+        assert_eq!(cycles, traced);
+    }
+}
@@ -0,0 +1,239 @@
+use crate::{chip8::Chip8, display::Display};
+
+/// Kind of memory access a watchpoint stops on
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+    ReadWrite,
+}
+
+/// Memory range watched for accesses made by instructions
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub start: u16,
+    pub len: u16,
+    pub access: Access,
+}
+
+/// Access that tripped a watchpoint
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WatchHit {
+    pub watchpoint: Watchpoint,
+    /// Address of the instruction that made the access
+    pub pc: u16,
+    /// First watched address accessed
+    pub addr: u16,
+    /// Either [`Access::Read`] or [`Access::Write`]
+    pub access: Access,
+}
+
+impl Watchpoint {
+    fn matches(&self, addr: u16, len: u16, access: Access) -> Option<u16> {
+        if self.access != Access::ReadWrite && self.access != access {
+            return None;
+        }
+
+        let first = addr.max(self.start);
+        (first < (addr + len).min(self.start + self.len)).then_some(first)
+    }
+}
+
+impl<D> Chip8<D>
+where
+    D: Display,
+{
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) {
+        self.watchpoints.push(watchpoint);
+    }
+
+    /// Removes the watchpoints starting at `start`, returns false if there were none
+    pub fn remove_watchpoints(&mut self, start: u16) -> bool {
+        let count = self.watchpoints.len();
+        self.watchpoints
+            .retain(|watchpoint| watchpoint.start != start);
+        self.watchpoints.len() != count
+    }
+
+    /// Removes the watchpoints equal to `watchpoint`, returns false if there were none
+    pub fn remove_watchpoint(&mut self, watchpoint: Watchpoint) -> bool {
+        let count = self.watchpoints.len();
+        self.watchpoints.retain(|other| *other != watchpoint);
+        self.watchpoints.len() != count
+    }
+
+    /// Returns and clears the first watchpoint hit since the last call
+    pub fn take_watch_hit(&mut self) -> Option<WatchHit> {
+        self.watch_hit.take()
+    }
+
+    /// Records an access of `len` bytes at `addr` made by the executing instruction.
+    ///
+    /// Called on every instruction memory access, so the common case of no
+    /// watchpoints is kept to a single check.
+    #[inline]
+    pub(super) fn watch_memory(&mut self, addr: u16, len: u16, access: Access) {
+        if access == Access::Write {
+            self.track_write(addr, len);
+        }
+        if self.watchpoints.is_empty() || self.watch_hit.is_some() {
+            return;
+        }
+
+        for watchpoint in &self.watchpoints {
+            if let Some(first) = watchpoint.matches(addr, len, access) {
+                self.watch_hit = Some(WatchHit {
+                    watchpoint: *watchpoint,
+                    // pc has already moved past the executing instruction
+                    pc: u16::from(self.pc) - 2,
+                    addr: first,
+                    access,
+                });
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        chip8::{compat::Compatibility, instruction::Chip8Instruction},
+        display::test_display::TestDisplay,
+    };
+    use rstest::*;
+    use twelve_bit::u12::*;
+
+    #[rstest]
+    #[case::inside(0x300, 4, Access::Read, Some(0x300))]
+    #[case::overlapping_start(0x2fe, 4, Access::Read, Some(0x300))]
+    #[case::overlapping_end(0x30f, 4, Access::Read, Some(0x30f))]
+    #[case::before(0x2fc, 4, Access::Read, None)]
+    #[case::after(0x310, 1, Access::Read, None)]
+    #[case::other_access(0x300, 4, Access::Write, None)]
+    fn test_watchpoint_matches(
+        #[case] addr: u16,
+        #[case] len: u16,
+        #[case] access: Access,
+        #[case] first: Option<u16>,
+    ) {
+        let watchpoint = Watchpoint {
+            start: 0x300,
+            len: 0x10,
+            access: Access::Read,
+        };
+
+        assert_eq!(watchpoint.matches(addr, len, access), first);
+    }
+
+    #[rstest]
+    #[case::read(Access::Read)]
+    #[case::read_write(Access::ReadWrite)]
+    fn test_sprite_read_trips_watchpoint(#[case] access: Access) {
+        let mut chip8 = Chip8::new(TestDisplay::new(), Compatibility::Cosmac);
+        chip8.pc = u12![0x202];
+        let watchpoint = Watchpoint {
+            start: 0x302,
+            len: 1,
+            access,
+        };
+        chip8.add_watchpoint(watchpoint);
+
+        chip8.execute(Chip8Instruction::SetIRegister(0x300));
+        chip8.execute(Chip8Instruction::Draw(0, 0, 2));
+        assert_eq!(chip8.take_watch_hit(), None);
+
+        chip8.execute(Chip8Instruction::Draw(0, 0, 3));
+        assert_eq!(
+            chip8.take_watch_hit(),
+            Some(WatchHit {
+                watchpoint,
+                pc: 0x200,
+                addr: 0x302,
+                access: Access::Read,
+            })
+        );
+        assert_eq!(chip8.take_watch_hit(), None);
+    }
+
+    #[rstest]
+    #[case::write(Access::Write)]
+    #[case::read_write(Access::ReadWrite)]
+    fn test_store_trips_watchpoint(#[case] access: Access) {
+        let mut chip8 = Chip8::new(TestDisplay::new(), Compatibility::Cosmac);
+        chip8.memory[0x200..0x204].copy_from_slice(&[
+            0xa3, 0x00, // 0x200: i = 0x300
+            0xf2, 0x55, // 0x202: save v2
+        ]);
+        chip8.pc = u12![0x200];
+        let watchpoint = Watchpoint {
+            start: 0x302,
+            len: 4,
+            access,
+        };
+        chip8.add_watchpoint(watchpoint);
+
+        chip8.step().unwrap();
+        assert_eq!(chip8.take_watch_hit(), None);
+
+        chip8.step().unwrap();
+        assert_eq!(
+            chip8.take_watch_hit(),
+            Some(WatchHit {
+                watchpoint,
+                pc: 0x202,
+                addr: 0x302,
+                access: Access::Write,
+            })
+        );
+    }
+
+    #[rstest]
+    fn test_write_watchpoint_ignores_reads() {
+        let mut chip8 = Chip8::new(TestDisplay::new(), Compatibility::Cosmac);
+        chip8.pc = u12![0x202];
+        chip8.add_watchpoint(Watchpoint {
+            start: 0x300,
+            len: 1,
+            access: Access::Write,
+        });
+
+        chip8.execute(Chip8Instruction::SetIRegister(0x300));
+        chip8.execute(Chip8Instruction::Draw(0, 0, 1));
+
+        assert_eq!(chip8.take_watch_hit(), None);
+    }
+
+    #[rstest]
+    fn test_remove_watchpoints() {
+        let mut chip8 = Chip8::new(TestDisplay::new(), Compatibility::Cosmac);
+        for len in [1, 2] {
+            chip8.add_watchpoint(Watchpoint {
+                start: 0x300,
+                len,
+                access: Access::Read,
+            });
+        }
+
+        assert!(chip8.remove_watchpoints(0x300));
+        assert!(!chip8.remove_watchpoints(0x300));
+        assert!(chip8.watchpoints.is_empty());
+    }
+
+    #[rstest]
+    fn test_remove_watchpoint_of_one_kind() {
+        let mut chip8 = Chip8::new(TestDisplay::new(), Compatibility::Cosmac);
+        let watchpoint = |access| Watchpoint {
+            start: 0x300,
+            len: 1,
+            access,
+        };
+        chip8.add_watchpoint(watchpoint(Access::Read));
+        chip8.add_watchpoint(watchpoint(Access::Write));
+
+        assert!(chip8.remove_watchpoint(watchpoint(Access::Write)));
+        assert!(!chip8.remove_watchpoint(watchpoint(Access::ReadWrite)));
+        assert_eq!(chip8.watchpoints, vec![watchpoint(Access::Read)]);
+    }
+}
@@ -0,0 +1,261 @@
+use crate::{
+    chip8::watch::{Access, Watchpoint},
+    debugger::expr::Condition,
+};
+
+/// A debugger command typed at the prompt
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Command {
+    /// Lists breakpoints, watchpoints and register breakpoints
+    ListBreakpoints,
+    Break(u16, Option<Condition>),
+    /// Stops on any instruction after which the condition holds
+    BreakIf(Condition),
+    Delete(u16),
+    /// Removes the breakpoints that have a condition but no address
+    DeleteConditions,
+    Watch(Watchpoint, Option<Condition>),
+    /// Removes the watchpoints starting at an address
+    Unwatch(u16),
+    /// Stops when a register changes to the given value
+    RegisterBreak(Register, u16),
+    RegisterUnbreak(Register),
+    /// Executes the given number of instructions
+    Step(usize),
+    /// Steps over subroutine calls
+    Next,
+    /// Runs until the current subroutine returns
+    Finish,
+    Continue,
+    Registers,
+    Stack,
+    /// Disassembles `count` instructions, around pc without an address
+    Disassemble(Option<u16>, usize),
+    ReadMemory(u16, usize),
+    WriteMemory(u16, Vec<u8>),
+    Help,
+    Quit,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Register {
+    V(u8),
+    I,
+}
+
+impl std::fmt::Display for Register {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Register::V(x) => write!(f, "v{:x}", x),
+            Register::I => write!(f, "i"),
+        }
+    }
+}
+
+pub static HELP: &str = "\
+break, b [addr] [if cond]  set a breakpoint, list all stop conditions without arguments
+delete <addr>              remove a breakpoint
+delete if                  remove the breakpoints without an address
+watch <addr> [len]         stop when an instruction writes memory, 1 byte by default
+rwatch <addr> [len]        stop when an instruction reads memory, such as a sprite
+awatch <addr> [len]        stop when an instruction reads or writes memory
+unwatch <addr>             remove the watchpoints starting at addr
+regbreak <reg> <value>     stop when v0-vf or i changes to value
+unregbreak <reg>           remove the register breakpoints on reg
+step, s [count]            execute count instructions, 1 by default
+next, n                    step over subroutine calls
+finish                     run until the current subroutine returns
+continue, c                run until a breakpoint, press enter to interrupt
+regs, r                    print the v registers, i, pc and stack pointer
+stack, bt                  print the return addresses, innermost first
+disasm, d [addr] [count]   disassemble count instructions, around pc by default
+x <addr> [len]             dump len bytes of memory, 16 by default
+write, w <addr> <byte>...  write bytes to memory
+help, h                    show this help
+quit, q                    stop debugging and exit
+Numbers are decimal unless prefixed with 0x. An empty line repeats the last command.
+
+Breakpoints and watchpoints stop only while their condition holds, for example
+  break 0x2a4 if v3 == 0x10 && i > 0x300
+  break if mem[0x3f0] != 0 || stack.depth > 8
+  rwatch 0x300 8 if hitcount > 5
+Conditions use v0-vf, i, pc, stack.depth, hitcount (times reached), mem[addr],
+numbers, parentheses and the operators || && == != < <= > >= | ^ & + - !";
+
+impl Command {
+    pub fn parse(line: &str) -> Result<Command, String> {
+        let (line, mut condition) = match line.split_once(" if ") {
+            Some((line, condition)) => (line, Some(Condition::parse(condition.trim())?)),
+            None => (line, None),
+        };
+
+        let mut words = line.split_whitespace();
+        let Some(name) = words.next() else {
+            return Err("Empty command".to_string());
+        };
+        let args: Vec<&str> = words.collect();
+
+        let command = match (name, args.as_slice()) {
+            ("break" | "b", []) => match condition.take() {
+                Some(condition) => Command::BreakIf(condition),
+                None => Command::ListBreakpoints,
+            },
+            ("break" | "b", [addr]) => Command::Break(parse_addr(addr)?, condition.take()),
+            ("delete", ["if"]) => Command::DeleteConditions,
+            ("delete", [addr]) => Command::Delete(parse_addr(addr)?),
+            ("watch" | "rwatch" | "awatch", [addr, len @ ..]) if len.len() <= 1 => {
+                let start = parse_addr(addr)?;
+                let len = match len {
+                    [len] => parse_number(len)?,
+                    _ => 1,
+                };
+                if len == 0 || start as usize + len as usize > 0x1000 {
+                    return Err(format!("Invalid watch range: {}", line.trim()));
+                }
+
+                let watchpoint = Watchpoint {
+                    start,
+                    len,
+                    access: match name {
+                        "watch" => Access::Write,
+                        "rwatch" => Access::Read,
+                        _ => Access::ReadWrite,
+                    },
+                };
+                Command::Watch(watchpoint, condition.take())
+            }
+            ("unwatch", [addr]) => Command::Unwatch(parse_addr(addr)?),
+            ("regbreak", [register, value]) => {
+                let register = parse_register(register)?;
+                let value = match register {
+                    Register::V(_) => parse_number::<u8>(value)? as u16,
+                    Register::I => parse_number(value)?,
+                };
+                Command::RegisterBreak(register, value)
+            }
+            ("unregbreak", [register]) => Command::RegisterUnbreak(parse_register(register)?),
+            ("step" | "s", []) => Command::Step(1),
+            ("step" | "s", [count]) => Command::Step(parse_number(count)?),
+            ("next" | "n", []) => Command::Next,
+            ("finish", []) => Command::Finish,
+            ("continue" | "c", []) => Command::Continue,
+            ("regs" | "r", []) => Command::Registers,
+            ("stack" | "bt", []) => Command::Stack,
+            ("disasm" | "d", []) => Command::Disassemble(None, 10),
+            ("disasm" | "d", [addr]) => Command::Disassemble(Some(parse_addr(addr)?), 10),
+            ("disasm" | "d", [addr, count]) => {
+                Command::Disassemble(Some(parse_addr(addr)?), parse_number(count)?)
+            }
+            ("x", [addr]) => Command::ReadMemory(parse_addr(addr)?, 16),
+            ("x", [addr, len]) => Command::ReadMemory(parse_addr(addr)?, parse_number(len)?),
+            ("write" | "w", [addr, bytes @ ..]) if !bytes.is_empty() => Command::WriteMemory(
+                parse_addr(addr)?,
+                bytes
+                    .iter()
+                    .map(|byte| parse_number(byte))
+                    .collect::<Result<_, _>>()?,
+            ),
+            ("help" | "h", []) => Command::Help,
+            ("quit" | "q", []) => Command::Quit,
+            _ => {
+                return Err(format!(
+                    "Invalid command: {}, type 'help' for commands",
+                    line.trim()
+                ))
+            }
+        };
+
+        if condition.is_some() {
+            return Err("Only break and watch commands take a condition".to_string());
+        }
+
+        Ok(command)
+    }
+}
+
+/// Parses a decimal or 0x prefixed hexadecimal number
+fn parse_number<T>(number: &str) -> Result<T, String>
+where
+    T: TryFrom<u64>,
+{
+    let value = match number.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => number.parse(),
+    };
+
+    value
+        .ok()
+        .and_then(|value| T::try_from(value).ok())
+        .ok_or(format!("Invalid number: {}", number))
+}
+
+fn parse_addr(addr: &str) -> Result<u16, String> {
+    match parse_number(addr)? {
+        addr @ 0..=0xfff => Ok(addr),
+        _ => Err(format!("Address out of range: {}", addr)),
+    }
+}
+
+fn parse_register(register: &str) -> Result<Register, String> {
+    match register.strip_prefix('v') {
+        _ if register == "i" => Ok(Register::I),
+        Some(x) if x.len() == 1 => u8::from_str_radix(x, 16)
+            .map(Register::V)
+            .map_err(|_| format!("Invalid register: {}", register)),
+        _ => Err(format!("Invalid register: {}", register)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    #[case::list_breakpoints("b", Command::ListBreakpoints)]
+    #[case::hex_breakpoint("break 0x20a", Command::Break(0x20a, None))]
+    #[case::decimal_breakpoint("b 522", Command::Break(0x20a, None))]
+    #[case::conditional_breakpoint("b 0x20a if v3 == 1", Command::Break(0x20a, Some(Condition::parse("v3 == 1").unwrap())))]
+    #[case::break_if("break if mem[0x3f0] != 0", Command::BreakIf(Condition::parse("mem[0x3f0] != 0").unwrap()))]
+    #[case::delete_conditions("delete if", Command::DeleteConditions)]
+    #[case::delete("delete 0x200", Command::Delete(0x200))]
+    #[case::step("s", Command::Step(1))]
+    #[case::step_count("step 10", Command::Step(10))]
+    #[case::next("n", Command::Next)]
+    #[case::finish("finish", Command::Finish)]
+    #[case::disassemble_around_pc("d", Command::Disassemble(None, 10))]
+    #[case::disassemble("disasm 0x300 4", Command::Disassemble(Some(0x300), 4))]
+    #[case::read_memory("x 0x300", Command::ReadMemory(0x300, 16))]
+    #[case::write_memory("w 0x300 0xff 1", Command::WriteMemory(0x300, vec![0xff, 1]))]
+    #[case::extra_whitespace("  c  ", Command::Continue)]
+    #[case::write_watch("watch 0x300", Command::Watch(Watchpoint { start: 0x300, len: 1, access: Access::Write }, None))]
+    #[case::read_watch("rwatch 0x300 16", Command::Watch(Watchpoint { start: 0x300, len: 16, access: Access::Read }, None))]
+    #[case::access_watch("awatch 0x300 2 if hitcount > 5", Command::Watch(Watchpoint { start: 0x300, len: 2, access: Access::ReadWrite }, Some(Condition::parse("hitcount > 5").unwrap())))]
+    #[case::unwatch("unwatch 0x300", Command::Unwatch(0x300))]
+    #[case::v_register_break("regbreak va 0x10", Command::RegisterBreak(Register::V(0xa), 0x10))]
+    #[case::i_register_break("regbreak i 0x300", Command::RegisterBreak(Register::I, 0x300))]
+    #[case::register_unbreak("unregbreak v3", Command::RegisterUnbreak(Register::V(3)))]
+    fn test_parse(#[case] line: &str, #[case] command: Command) {
+        assert_eq!(Command::parse(line), Ok(command));
+    }
+
+    #[rstest]
+    #[case::unknown("jump 0x200", "Invalid command: jump 0x200, type 'help' for commands")]
+    #[case::missing_argument("delete", "Invalid command: delete, type 'help' for commands")]
+    #[case::bad_number("b 0xzz", "Invalid number: 0xzz")]
+    #[case::address_out_of_range("b 0x1000", "Address out of range: 0x1000")]
+    #[case::byte_out_of_range("w 0x200 256", "Invalid number: 256")]
+    #[case::nothing_to_write("w 0x200", "Invalid command: w 0x200, type 'help' for commands")]
+    #[case::empty_watch("rwatch 0x300 0", "Invalid watch range: rwatch 0x300 0")]
+    #[case::watch_past_memory("watch 0xfff 2", "Invalid watch range: watch 0xfff 2")]
+    #[case::bad_register("regbreak vg 1", "Invalid register: vg")]
+    #[case::v_register_overflow("regbreak v0 256", "Invalid number: 256")]
+    #[case::bad_condition(
+        "b 0x200 if v3 ==",
+        "Invalid condition 'v3 ==': expected a value at column 6, found the end of the condition"
+    )]
+    #[case::unexpected_condition("step if v0", "Only break and watch commands take a condition")]
+    fn test_parse_invalid(#[case] line: &str, #[case] error: &str) {
+        assert_eq!(Command::parse(line), Err(error.to_string()));
+    }
+}
@@ -0,0 +1,419 @@
+use crate::{chip8::Chip8, display::Display};
+
+/// Breakpoint or watchpoint condition, true when it evaluates to non-zero.
+///
+/// Operators, loosest binding first:
+///
+///   ||
+///   &&
+///   == != < <= > >=
+///   |
+///   ^
+///   &
+///   + -
+///   ! - (unary)
+///
+/// Values are numbers, `v0`-`vf`, `i`, `pc`, `stack.depth`, `hitcount` and
+/// `mem[addr]`, which reads a byte of memory.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Expr {
+    Number(i64),
+    Variable(Variable),
+    Memory(Box<Expr>),
+    Not(Box<Expr>),
+    Negate(Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+}
+
+/// Parsed condition together with its source, for listing
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Condition {
+    pub source: String,
+    expr: Expr,
+}
+
+impl Condition {
+    pub fn parse(source: &str) -> Result<Condition, String> {
+        Ok(Condition {
+            source: source.to_string(),
+            expr: Expr::parse(source)?,
+        })
+    }
+
+    pub fn holds<D>(&self, chip8: &Chip8<D>, hitcount: u64) -> bool
+    where
+        D: Display,
+    {
+        self.expr.eval(chip8, hitcount) != 0
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Variable {
+    V(u8),
+    I,
+    Pc,
+    StackDepth,
+    /// Number of times the breakpoint or watchpoint was reached, including this one
+    HitCount,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BinaryOp {
+    Or,
+    And,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    BitOr,
+    BitXor,
+    BitAnd,
+    Add,
+    Sub,
+}
+
+/// Binary operators by precedence level, loosest binding first
+static PRECEDENCE: [&[(&str, BinaryOp)]; 7] = [
+    &[("||", BinaryOp::Or)],
+    &[("&&", BinaryOp::And)],
+    &[
+        ("==", BinaryOp::Equal),
+        ("!=", BinaryOp::NotEqual),
+        ("<=", BinaryOp::LessEqual),
+        (">=", BinaryOp::GreaterEqual),
+        ("<", BinaryOp::Less),
+        (">", BinaryOp::Greater),
+    ],
+    &[("|", BinaryOp::BitOr)],
+    &[("^", BinaryOp::BitXor)],
+    &[("&", BinaryOp::BitAnd)],
+    &[("+", BinaryOp::Add), ("-", BinaryOp::Sub)],
+];
+
+/// Operators and punctuation, longest first so `<=` isn't read as `<`
+static SYMBOLS: [&str; 18] = [
+    "||", "&&", "==", "!=", "<=", ">=", "<", ">", "|", "^", "&", "+", "-", "!", "(", ")", "[", "]",
+];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Token {
+    Number(i64),
+    Name(String),
+    Symbol(&'static str),
+}
+
+impl Expr {
+    pub fn parse(source: &str) -> Result<Expr, String> {
+        let tokens =
+            tokenize(source).map_err(|e| format!("Invalid condition '{}': {}", source, e))?;
+        let mut parser = Parser {
+            tokens,
+            pos: 0,
+            end: source.len() + 1,
+        };
+
+        parser
+            .parse_complete()
+            .map_err(|e| format!("Invalid condition '{}': {}", source, e))
+    }
+
+    pub fn eval<D>(&self, chip8: &Chip8<D>, hitcount: u64) -> i64
+    where
+        D: Display,
+    {
+        match self {
+            Expr::Number(value) => *value,
+            Expr::Variable(variable) => match variable {
+                Variable::V(x) => chip8.v_reg()[*x as usize] as i64,
+                Variable::I => chip8.i_reg() as i64,
+                Variable::Pc => chip8.pc() as i64,
+                Variable::StackDepth => chip8.stack_depth() as i64,
+                Variable::HitCount => hitcount as i64,
+            },
+            Expr::Memory(addr) => {
+                let addr = addr.eval(chip8, hitcount);
+                usize::try_from(addr)
+                    .ok()
+                    .and_then(|addr| chip8.memory().get(addr))
+                    .map_or(0, |byte| *byte as i64)
+            }
+            Expr::Not(expr) => (expr.eval(chip8, hitcount) == 0) as i64,
+            Expr::Negate(expr) => expr.eval(chip8, hitcount).wrapping_neg(),
+            Expr::Binary(op, left, right) => {
+                let left = left.eval(chip8, hitcount);
+                // || and && short-circuit like they read
+                match op {
+                    BinaryOp::Or if left != 0 => return 1,
+                    BinaryOp::And if left == 0 => return 0,
+                    _ => {}
+                }
+                let right = right.eval(chip8, hitcount);
+
+                match op {
+                    BinaryOp::Or | BinaryOp::And => (right != 0) as i64,
+                    BinaryOp::Equal => (left == right) as i64,
+                    BinaryOp::NotEqual => (left != right) as i64,
+                    BinaryOp::Less => (left < right) as i64,
+                    BinaryOp::LessEqual => (left <= right) as i64,
+                    BinaryOp::Greater => (left > right) as i64,
+                    BinaryOp::GreaterEqual => (left >= right) as i64,
+                    BinaryOp::BitOr => left | right,
+                    BinaryOp::BitXor => left ^ right,
+                    BinaryOp::BitAnd => left & right,
+                    BinaryOp::Add => left.wrapping_add(right),
+                    BinaryOp::Sub => left.wrapping_sub(right),
+                }
+            }
+        }
+    }
+}
+
+/// Splits a condition into tokens, each with its 1-based column
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>, String> {
+    let mut tokens = vec![];
+    let mut rest = source;
+
+    while let Some(start) = rest.find(|c: char| !c.is_whitespace()) {
+        rest = &rest[start..];
+        let column = source.len() - rest.len() + 1;
+
+        let len = if let Some(symbol) = SYMBOLS.iter().find(|s| rest.starts_with(**s)) {
+            tokens.push((Token::Symbol(symbol), column));
+            symbol.len()
+        } else if rest.starts_with(|c: char| c.is_ascii_alphanumeric()) {
+            let len = rest
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '.' && c != '_')
+                .unwrap_or(rest.len());
+            let word = &rest[..len];
+
+            let token = if word.starts_with(|c: char| c.is_ascii_digit()) {
+                let value = match word.strip_prefix("0x") {
+                    Some(hex) => i64::from_str_radix(hex, 16),
+                    None => word.parse(),
+                };
+                Token::Number(
+                    value.map_err(|_| format!("invalid number '{}' at column {}", word, column))?,
+                )
+            } else {
+                Token::Name(word.to_string())
+            };
+            tokens.push((token, column));
+            len
+        } else {
+            let c = rest.chars().next().unwrap();
+            return Err(format!("unexpected '{}' at column {}", c, column));
+        };
+
+        rest = &rest[len..];
+    }
+
+    Ok(tokens)
+}
+
+/// Precedence climbing parser over the tokens of a condition
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    /// Column reported for errors at the end of the condition
+    end: usize,
+}
+
+impl Parser {
+    fn parse_complete(&mut self) -> Result<Expr, String> {
+        let expr = self.parse_binary(0)?;
+        match self.tokens.get(self.pos) {
+            None => Ok(expr),
+            Some((token, column)) => Err(format!(
+                "unexpected {} at column {}",
+                describe(token),
+                column
+            )),
+        }
+    }
+
+    fn parse_binary(&mut self, level: usize) -> Result<Expr, String> {
+        if level == PRECEDENCE.len() {
+            return self.parse_unary();
+        }
+
+        let mut left = self.parse_binary(level + 1)?;
+        while let Some(op) = self.peek_operator(level) {
+            self.pos += 1;
+            let right = self.parse_binary(level + 1)?;
+            left = Expr::Binary(op, Box::new(left), Box::new(right));
+
+            // Comparisons don't chain, `a < b < c` is almost certainly a mistake
+            if level == 2 && self.peek_operator(level).is_some() {
+                let (token, column) = &self.tokens[self.pos];
+                return Err(format!(
+                    "comparisons can't be chained, unexpected {} at column {}",
+                    describe(token),
+                    column
+                ));
+            }
+        }
+
+        Ok(left)
+    }
+
+    fn peek_operator(&self, level: usize) -> Option<BinaryOp> {
+        let Some((Token::Symbol(symbol), _)) = self.tokens.get(self.pos) else {
+            return None;
+        };
+        PRECEDENCE[level]
+            .iter()
+            .find(|(name, _)| name == symbol)
+            .map(|(_, op)| *op)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, String> {
+        let (token, column) = self.next("a value")?;
+
+        match token {
+            Token::Number(value) => Ok(Expr::Number(value)),
+            Token::Symbol("!") => Ok(Expr::Not(Box::new(self.parse_unary()?))),
+            Token::Symbol("-") => Ok(Expr::Negate(Box::new(self.parse_unary()?))),
+            Token::Symbol("(") => {
+                let expr = self.parse_binary(0)?;
+                self.expect(")")?;
+                Ok(expr)
+            }
+            Token::Name(name) if name == "mem" => {
+                self.expect("[")?;
+                let addr = self.parse_binary(0)?;
+                self.expect("]")?;
+                Ok(Expr::Memory(Box::new(addr)))
+            }
+            Token::Name(name) => match parse_variable(&name) {
+                Some(variable) => Ok(Expr::Variable(variable)),
+                None => Err(format!("unknown name '{}' at column {}", name, column)),
+            },
+            token => Err(format!(
+                "expected a value, found {} at column {}",
+                describe(&token),
+                column
+            )),
+        }
+    }
+
+    fn next(&mut self, expected: &str) -> Result<(Token, usize), String> {
+        match self.tokens.get(self.pos) {
+            Some(token) => {
+                self.pos += 1;
+                Ok(token.clone())
+            }
+            None => Err(format!(
+                "expected {} at column {}, found the end of the condition",
+                expected, self.end
+            )),
+        }
+    }
+
+    fn expect(&mut self, symbol: &'static str) -> Result<(), String> {
+        match self.next(&format!("'{}'", symbol))? {
+            (Token::Symbol(found), _) if found == symbol => Ok(()),
+            (token, column) => Err(format!(
+                "expected '{}', found {} at column {}",
+                symbol,
+                describe(&token),
+                column
+            )),
+        }
+    }
+}
+
+fn parse_variable(name: &str) -> Option<Variable> {
+    match name {
+        "i" => Some(Variable::I),
+        "pc" => Some(Variable::Pc),
+        "stack.depth" => Some(Variable::StackDepth),
+        "hitcount" => Some(Variable::HitCount),
+        _ => {
+            let x = name.strip_prefix('v').filter(|x| x.len() == 1)?;
+            u8::from_str_radix(x, 16).ok().map(Variable::V)
+        }
+    }
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Number(value) => format!("number {}", value),
+        Token::Name(name) => format!("'{}'", name),
+        Token::Symbol(symbol) => format!("'{}'", symbol),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{chip8::compat::Compatibility, display::test_display::TestDisplay};
+    use rstest::*;
+
+    #[rstest]
+    #[case::register_and_i("v3 == 0x10 && i > 0x300", 1)]
+    #[case::register_mismatch("v3 == 0x11 && i > 0x300", 0)]
+    #[case::memory("mem[0x3F0] != 0", 1)]
+    #[case::memory_expression("mem[i + 0xe0] == 0xab", 1)]
+    #[case::memory_out_of_range("mem[0x1000]", 0)]
+    #[case::hitcount("hitcount > 5", 1)]
+    #[case::stack_depth("stack.depth > 8", 0)]
+    #[case::stack_depth_one("stack.depth == 1", 1)]
+    #[case::pc("pc == 0x208", 1)]
+    #[case::precedence("1 + 2 == 3 || 0", 1)]
+    #[case::bitwise("v3 & 0xf0 | 1 ^ 3", 0x12)]
+    #[case::parentheses("(1 + 2) - (3 - 1)", 1)]
+    #[case::unary("!v3 + -1", -1)]
+    #[case::short_circuit("0 && mem[0x3f0]", 0)]
+    fn test_eval(#[case] source: &str, #[case] value: i64) {
+        let chip8 = get_test_chip8();
+
+        assert_eq!(Expr::parse(source).unwrap().eval(&chip8, 6), value);
+    }
+
+    #[rstest]
+    #[case::empty("", "expected a value at column 1, found the end of the condition")]
+    #[case::missing_operand(
+        "v3 ==",
+        "expected a value at column 6, found the end of the condition"
+    )]
+    #[case::unknown_name("v3 == foo", "unknown name 'foo' at column 7")]
+    #[case::bad_register("vg == 1", "unknown name 'vg' at column 1")]
+    #[case::bad_number("v3 == 0xzz", "invalid number '0xzz' at column 7")]
+    #[case::bad_character("v3 = 1", "unexpected '=' at column 4")]
+    #[case::unclosed_paren("(v3 == 1", "expected ')' at column 9, found the end of the condition")]
+    #[case::unclosed_memory(
+        "mem[1 == 1",
+        "expected ']' at column 11, found the end of the condition"
+    )]
+    #[case::missing_bracket("mem 1", "expected '[', found number 1 at column 5")]
+    #[case::trailing("v3 1", "unexpected number 1 at column 4")]
+    #[case::chained(
+        "1 < v3 < 3",
+        "comparisons can't be chained, unexpected '<' at column 8"
+    )]
+    fn test_parse_errors(#[case] source: &str, #[case] error: &str) {
+        assert_eq!(
+            Expr::parse(source),
+            Err(format!("Invalid condition '{}': {}", source, error))
+        );
+    }
+
+    /// Machine with v3 = 0x10, i = 0x310, mem[0x3f0] = 0xab and one call on the stack
+    fn get_test_chip8() -> Chip8<TestDisplay> {
+        let mut chip8 = Chip8::new(TestDisplay::new(), Compatibility::Cosmac);
+        let program = [
+            0x63, 0x10, // 0x000: v3 = 0x10
+            0xa3, 0x10, // 0x002: i = 0x310
+            0x22, 0x08, // 0x004: call 0x208
+        ];
+        chip8.write_memory(0x000, &program).unwrap();
+        chip8.write_memory(0x3f0, &[0xab]).unwrap();
+        for _ in 0..3 {
+            chip8.step().unwrap();
+        }
+        chip8
+    }
+}
@@ -0,0 +1,684 @@
+use std::{
+    collections::BTreeSet,
+    io::{ErrorKind, Read, Write},
+    net::{TcpListener, TcpStream},
+    time::Instant,
+};
+
+use crate::{
+    chip8::{
+        wait_for_next_frame,
+        watch::{Access, Watchpoint},
+        Chip8,
+    },
+    display::Display,
+};
+
+/// Register numbers used by `p` and `P` packets, in the order of `g` replies
+const REG_I: usize = 16;
+const REG_PC: usize = 17;
+const REG_SP: usize = 18;
+
+/// Largest packet sent or accepted, in bytes
+const PACKET_SIZE: usize = 0x1000;
+
+/// Describes the registers to GDB, sizes in bits
+static TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <architecture>chip8</architecture>
+  <feature name="org.chip8.core">
+    <reg name="v0" bitsize="8" type="uint8" regnum="0"/>
+    <reg name="v1" bitsize="8" type="uint8"/>
+    <reg name="v2" bitsize="8" type="uint8"/>
+    <reg name="v3" bitsize="8" type="uint8"/>
+    <reg name="v4" bitsize="8" type="uint8"/>
+    <reg name="v5" bitsize="8" type="uint8"/>
+    <reg name="v6" bitsize="8" type="uint8"/>
+    <reg name="v7" bitsize="8" type="uint8"/>
+    <reg name="v8" bitsize="8" type="uint8"/>
+    <reg name="v9" bitsize="8" type="uint8"/>
+    <reg name="va" bitsize="8" type="uint8"/>
+    <reg name="vb" bitsize="8" type="uint8"/>
+    <reg name="vc" bitsize="8" type="uint8"/>
+    <reg name="vd" bitsize="8" type="uint8"/>
+    <reg name="ve" bitsize="8" type="uint8"/>
+    <reg name="vf" bitsize="8" type="uint8"/>
+    <reg name="i" bitsize="16" type="data_ptr"/>
+    <reg name="pc" bitsize="16" type="code_ptr"/>
+    <reg name="sp" bitsize="8" type="uint8"/>
+  </feature>
+</target>
+"#;
+
+/// Server for the GDB remote serial protocol, so existing debugger frontends
+/// can drive the machine over a local TCP port.
+///
+/// Registers are sent little endian: v0-vf, i, pc and sp, the stack depth.
+/// Like the terminal debugger, the display keeps being presented while
+/// waiting for packets.
+pub struct GdbStub<D>
+where
+    D: Display,
+{
+    chip8: Chip8<D>,
+    breakpoints: BTreeSet<u16>,
+}
+
+/// What a packet asks the stub to do
+enum Action {
+    Reply(String),
+    Step,
+    Continue,
+    Detach,
+    Kill,
+}
+
+impl<D> GdbStub<D>
+where
+    D: Display,
+{
+    pub fn new(chip8: Chip8<D>) -> Self {
+        GdbStub {
+            chip8,
+            breakpoints: BTreeSet::new(),
+        }
+    }
+
+    /// Waits for GDB on a local port and serves it until it detaches or the window closes
+    pub fn listen(&mut self, port: u16) -> Result<(), String> {
+        let listener = TcpListener::bind(("127.0.0.1", port))
+            .map_err(|e| format!("Failed to listen on port {}: {}", port, e))?;
+        println!("Waiting for GDB on 127.0.0.1:{}", port);
+
+        let Some(stream) = self.accept(&listener)? else {
+            return Ok(());
+        };
+        self.serve(stream)
+    }
+
+    /// Accepts one connection, None if the window was closed first
+    fn accept(&mut self, listener: &TcpListener) -> Result<Option<TcpStream>, String> {
+        listener.set_nonblocking(true).map_err(|e| e.to_string())?;
+
+        while self.chip8.is_open() {
+            let frame_start = Instant::now();
+            match listener.accept() {
+                Ok((stream, _)) => return Ok(Some(stream)),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {}
+                Err(e) => return Err(format!("Failed to accept GDB connection: {}", e)),
+            }
+
+            self.chip8.sync_display();
+            wait_for_next_frame(frame_start);
+        }
+
+        Ok(None)
+    }
+
+    fn serve(&mut self, stream: TcpStream) -> Result<(), String> {
+        let mut connection = Connection::new(stream)?;
+
+        while self.chip8.is_open() {
+            let frame_start = Instant::now();
+            let packet = match connection.poll()? {
+                Some(Received::Packet(packet)) => packet,
+                // Interrupts only matter while running
+                Some(Received::Interrupt) => continue,
+                None if connection.closed => return Ok(()),
+                None => {
+                    self.chip8.sync_display();
+                    wait_for_next_frame(frame_start);
+                    continue;
+                }
+            };
+
+            match self.handle(&packet) {
+                Action::Reply(reply) => connection.send(&reply)?,
+                Action::Step => {
+                    let reply = self.step().unwrap_or_else(|| "S05".to_string());
+                    connection.send(&reply)?;
+                }
+                Action::Continue => {
+                    let reply = self.resume(&mut connection)?;
+                    connection.send(&reply)?;
+                }
+                Action::Detach => {
+                    connection.send("OK")?;
+                    return Ok(());
+                }
+                Action::Kill => return Ok(()),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn handle(&mut self, packet: &str) -> Action {
+        let (command, args) = packet.split_at(1.min(packet.len()));
+
+        let reply = match command {
+            "?" => "S05".to_string(),
+            "g" => self.read_registers(),
+            "G" => self.write_registers(args),
+            "p" => parse_hex(args)
+                .and_then(|reg| self.read_register(reg as usize))
+                .unwrap_or_else(|| "E01".to_string()),
+            "P" => args
+                .split_once('=')
+                .and_then(|(reg, value)| self.write_register(parse_hex(reg)? as usize, value))
+                .map_or("E01".to_string(), |()| "OK".to_string()),
+            "m" => self.read_memory(args).unwrap_or_else(|| "E01".to_string()),
+            "M" => self
+                .write_memory(args)
+                .map_or("E01".to_string(), |()| "OK".to_string()),
+            "Z" | "z" => self
+                .set_breakpoint(args, command == "Z")
+                .unwrap_or_default(),
+            "s" => return Action::Step,
+            "c" => return Action::Continue,
+            "D" => return Action::Detach,
+            "k" => return Action::Kill,
+            "H" => "OK".to_string(),
+            "q" => self.query(packet),
+            // Everything else is reported as unsupported
+            _ => String::new(),
+        };
+
+        Action::Reply(reply)
+    }
+
+    fn query(&self, packet: &str) -> String {
+        if packet.starts_with("qSupported") {
+            return format!(
+                "PacketSize={:x};qXfer:features:read+;QStartNoAckMode+",
+                PACKET_SIZE
+            );
+        }
+        if let Some(range) = packet.strip_prefix("qXfer:features:read:target.xml:") {
+            return read_chunk(TARGET_XML, range).unwrap_or_else(|| "E01".to_string());
+        }
+
+        match packet {
+            "qAttached" => "1".to_string(),
+            "qC" => "QC1".to_string(),
+            "qfThreadInfo" => "m1".to_string(),
+            "qsThreadInfo" => "l".to_string(),
+            _ => String::new(),
+        }
+    }
+
+    /// Executes one instruction, returns a stop reply for anything but a plain stop
+    fn step(&mut self) -> Option<String> {
+        if self.chip8.step().is_err() {
+            // Illegal instruction
+            return Some("S04".to_string());
+        }
+
+        self.chip8.take_watch_hit().map(|hit| {
+            let kind = match hit.watchpoint.access {
+                Access::Write => "watch",
+                Access::Read => "rwatch",
+                Access::ReadWrite => "awatch",
+            };
+            format!("T05{}:{:x};", kind, hit.addr)
+        })
+    }
+
+    /// Runs at normal speed until a breakpoint, a watchpoint or an interrupt from GDB
+    fn resume(&mut self, connection: &mut Connection) -> Result<String, String> {
+        while self.chip8.is_open() {
+            let frame_start = Instant::now();
+
+            for _ in 0..self.chip8.cycles_per_frame() {
+                if let Some(reply) = self.step() {
+                    return Ok(reply);
+                }
+                if self.breakpoints.contains(&self.chip8.pc()) {
+                    return Ok("S05".to_string());
+                }
+            }
+
+            self.chip8.sync_display();
+            match connection.poll()? {
+                Some(Received::Interrupt) => return Ok("S02".to_string()),
+                None if connection.closed => return Err("GDB disconnected".to_string()),
+                _ => {}
+            }
+            wait_for_next_frame(frame_start);
+        }
+
+        // The window was closed, report the program as exited
+        Ok("W00".to_string())
+    }
+
+    fn read_register(&self, reg: usize) -> Option<String> {
+        let value = match reg {
+            0..=15 => return Some(format!("{:02x}", self.chip8.v_reg()[reg])),
+            REG_I => self.chip8.i_reg(),
+            REG_PC => self.chip8.pc(),
+            REG_SP => return Some(format!("{:02x}", self.chip8.stack_depth())),
+            _ => return None,
+        };
+        Some(encode_hex(&value.to_le_bytes()))
+    }
+
+    fn read_registers(&self) -> String {
+        (0..=REG_SP)
+            .filter_map(|reg| self.read_register(reg))
+            .collect()
+    }
+
+    /// Writes one register from its little endian hex value
+    fn write_register(&mut self, reg: usize, value: &str) -> Option<()> {
+        let bytes = decode_hex(value)?;
+        match (reg, bytes.as_slice()) {
+            (0..=15, &[value]) => self.chip8.set_v_reg(reg, value),
+            (REG_I, &[low, high]) => self.chip8.set_i_reg(u16::from_le_bytes([low, high])).ok()?,
+            (REG_PC, &[low, high]) => self.chip8.set_pc(u16::from_le_bytes([low, high])).ok()?,
+            // The stack depth can't be changed, but GDB writes it back unchanged with G
+            (REG_SP, &[depth]) if depth as usize == self.chip8.stack_depth() => {}
+            _ => return None,
+        }
+        Some(())
+    }
+
+    fn write_registers(&mut self, values: &str) -> String {
+        let mut values = values;
+        for reg in 0..=REG_SP {
+            let len = if reg == REG_I || reg == REG_PC { 4 } else { 2 };
+            if values.len() < len || self.write_register(reg, &values[..len]).is_none() {
+                return "E01".to_string();
+            }
+            values = &values[len..];
+        }
+        "OK".to_string()
+    }
+
+    /// Handles `addr,len`, reading up to the end of memory or as much as fits in a packet
+    fn read_memory(&self, args: &str) -> Option<String> {
+        let (addr, len) = args.split_once(',')?;
+        let (addr, len) = (parse_hex(addr)?, parse_hex(len)?);
+        let memory = self.chip8.memory();
+        if addr >= memory.len() as u64 {
+            return None;
+        }
+
+        // Every byte is sent as two hex digits
+        let end = addr
+            .saturating_add(len)
+            .min(memory.len() as u64)
+            .min(addr + PACKET_SIZE as u64 / 2);
+        Some(encode_hex(&memory[addr as usize..end as usize]))
+    }
+
+    /// Handles `addr,len:bytes`
+    fn write_memory(&mut self, args: &str) -> Option<()> {
+        let (range, bytes) = args.split_once(':')?;
+        let (addr, len) = range.split_once(',')?;
+        let bytes = decode_hex(bytes)?;
+        if parse_hex(len)? as usize != bytes.len() {
+            return None;
+        }
+
+        self.chip8
+            .write_memory(u16::try_from(parse_hex(addr)?).ok()?, &bytes)
+            .ok()
+    }
+
+    /// Handles `type,addr,kind` of `Z` and `z` packets, None for unsupported types.
+    ///
+    /// Watchpoints must lie in memory and are only removed by a packet of the same type.
+    fn set_breakpoint(&mut self, args: &str, insert: bool) -> Option<String> {
+        let mut fields = args.split(',');
+        let kind = fields.next()?;
+        let addr = u16::try_from(parse_hex(fields.next()?)?).ok()?;
+        let len = fields.next().and_then(parse_hex).unwrap_or(1);
+
+        let access = match kind {
+            // Software and hardware breakpoints are the same thing here
+            "0" | "1" => {
+                if insert {
+                    self.breakpoints.insert(addr);
+                } else {
+                    self.breakpoints.remove(&addr);
+                }
+                return Some("OK".to_string());
+            }
+            "2" => Access::Write,
+            "3" => Access::Read,
+            "4" => Access::ReadWrite,
+            _ => return None,
+        };
+
+        if len == 0 || addr as u64 + len > 0x1000 {
+            return Some("E01".to_string());
+        }
+        let watchpoint = Watchpoint {
+            start: addr,
+            len: len as u16,
+            access,
+        };
+        if insert {
+            self.chip8.add_watchpoint(watchpoint);
+        } else {
+            self.chip8.remove_watchpoint(watchpoint);
+        }
+        Some("OK".to_string())
+    }
+}
+
+enum Received {
+    Packet(String),
+    /// Ctrl-C, GDB asks to halt the running program
+    Interrupt,
+}
+
+/// Packet framing over a non-blocking TCP stream
+struct Connection {
+    stream: TcpStream,
+    /// Bytes received but not parsed yet
+    buffer: Vec<u8>,
+    /// Last packet sent, resent when GDB reports a transmission error
+    last_sent: Vec<u8>,
+    acks: bool,
+    closed: bool,
+}
+
+impl Connection {
+    fn new(stream: TcpStream) -> Result<Self, String> {
+        stream.set_nonblocking(true).map_err(|e| e.to_string())?;
+        stream.set_nodelay(true).map_err(|e| e.to_string())?;
+
+        Ok(Connection {
+            stream,
+            buffer: vec![],
+            last_sent: vec![],
+            acks: true,
+            closed: false,
+        })
+    }
+
+    /// Returns the next packet or interrupt without blocking
+    fn poll(&mut self) -> Result<Option<Received>, String> {
+        let mut chunk = [0; 1024];
+        loop {
+            match self.stream.read(&mut chunk) {
+                Ok(0) => {
+                    self.closed = true;
+                    break;
+                }
+                Ok(len) => self.buffer.extend_from_slice(&chunk[..len]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) => return Err(format!("Failed to read from GDB: {}", e)),
+            }
+        }
+
+        while let Some(&byte) = self.buffer.first() {
+            match byte {
+                b'$' => {
+                    let Some(end) = self.buffer.iter().position(|&b| b == b'#') else {
+                        return Ok(None);
+                    };
+                    if self.buffer.len() < end + 3 {
+                        return Ok(None);
+                    }
+
+                    let packet: Vec<u8> = self.buffer.drain(..end + 3).collect();
+                    let data = &packet[1..end];
+                    let checksum = std::str::from_utf8(&packet[end + 1..])
+                        .ok()
+                        .and_then(|checksum| u8::from_str_radix(checksum, 16).ok());
+
+                    if checksum != Some(checksum_of(data)) {
+                        self.write(b"-")?;
+                        continue;
+                    }
+                    if self.acks {
+                        self.write(b"+")?;
+                    }
+
+                    let data = String::from_utf8_lossy(data).to_string();
+                    if data == "QStartNoAckMode" {
+                        self.send("OK")?;
+                        self.acks = false;
+                        continue;
+                    }
+                    return Ok(Some(Received::Packet(data)));
+                }
+                0x03 => {
+                    self.buffer.remove(0);
+                    return Ok(Some(Received::Interrupt));
+                }
+                b'-' => {
+                    self.buffer.remove(0);
+                    let last_sent = self.last_sent.clone();
+                    self.write(&last_sent)?;
+                }
+                // Acknowledgements and noise between packets
+                _ => {
+                    self.buffer.remove(0);
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn send(&mut self, data: &str) -> Result<(), String> {
+        let packet = format!("${}#{:02x}", data, checksum_of(data.as_bytes()));
+        self.last_sent = packet.into_bytes();
+        let packet = self.last_sent.clone();
+        self.write(&packet)
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> Result<(), String> {
+        // The stream is non-blocking, so retry until everything is written
+        let mut bytes = bytes;
+        while !bytes.is_empty() {
+            match self.stream.write(bytes) {
+                Ok(len) => bytes = &bytes[len..],
+                Err(e) if e.kind() == ErrorKind::WouldBlock => std::thread::yield_now(),
+                Err(e) => return Err(format!("Failed to write to GDB: {}", e)),
+            }
+        }
+        Ok(())
+    }
+}
+
+fn checksum_of(data: &[u8]) -> u8 {
+    data.iter().fold(0, |sum: u8, byte| sum.wrapping_add(*byte))
+}
+
+fn parse_hex(hex: &str) -> Option<u64> {
+    u64::from_str_radix(hex, 16).ok()
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Handles the `offset,length` of a `qXfer` read
+fn read_chunk(document: &str, range: &str) -> Option<String> {
+    let (offset, len) = range.split_once(',')?;
+    let (offset, len) = (parse_hex(offset)? as usize, parse_hex(len)? as usize);
+    let rest = document.get(offset.min(document.len())..)?;
+
+    Some(if rest.len() > len {
+        format!("m{}", &rest[..len])
+    } else {
+        format!("l{}", rest)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{chip8::compat::Compatibility, display::test_display::TestDisplay};
+    use rstest::*;
+    use std::thread;
+
+    /// Calls a subroutine at 0x208 that draws a sprite from 0x300, then loops forever
+    static PROGRAM: [u8; 14] = [
+        0x60, 0x01, // 0x200: v0 = 1
+        0x22, 0x08, // 0x202: call 0x208
+        0x70, 0x01, // 0x204: v0 += 1
+        0x12, 0x04, // 0x206: jump to 0x204
+        0xa3, 0x00, // 0x208: i = 0x300
+        0xd0, 0x01, // 0x20A: draw 1 row at (v0, v0)
+        0x00, 0xee, // 0x20C: return
+    ];
+
+    #[rstest]
+    #[case::halt_reason(&[("?", "S05")])]
+    #[case::registers(&[("g", "000000000000000000000000000000000000000200"), ("p11", "0002")])]
+    #[case::write_registers(&[("P3=2a", "OK"), ("P10=3402", "OK"), ("p3", "2a"), ("p10", "3402")])]
+    #[case::write_all_registers(&[("G0102030405060708090a0b0c0d0e0f103402020200", "OK"), ("g", "0102030405060708090a0b0c0d0e0f103402020200")])]
+    #[case::write_registers_too_short(&[("G0102", "E01")])]
+    #[case::stack_depth_is_read_only(&[("P12=01", "E01")])]
+    #[case::i_out_of_memory(&[("P10=ffff", "E01"), ("G0102030405060708090a0b0c0d0e0f100010020200", "E01"), ("p10", "0000")])]
+    #[case::read_memory(&[("m200,4", "60012208")])]
+    #[case::read_memory_clipped(&[("mffe,4", "0000")])]
+    #[case::read_memory_huge_length(&[("mfff,ffffffffffffffff", "00")])]
+    #[case::write_memory(&[("M300,2:abcd", "OK"), ("m300,2", "abcd")])]
+    #[case::write_memory_length_mismatch(&[("M300,3:abcd", "E01")])]
+    #[case::step(&[("s", "S05"), ("s", "S05"), ("p11", "0802"), ("p12", "01")])]
+    #[case::breakpoint(&[("Z0,204,2", "OK"), ("c", "S05"), ("p11", "0402"), ("z0,204,2", "OK")])]
+    #[case::read_watchpoint(&[("Z3,300,1", "OK"), ("c", "T05rwatch:300;"), ("p11", "0c02")])]
+    #[case::write_watchpoint(&[("M20a,2:f033", "OK"), ("Z2,301,1", "OK"), ("c", "T05watch:301;"), ("p11", "0c02")])]
+    #[case::watchpoint_outside_memory(&[("Z3,fff,2", "E01"), ("Z3,300,0", "E01"), ("Z4,ffff,ffff", "E01")])]
+    #[case::remove_watchpoint_of_same_kind(&[("Z3,300,1", "OK"), ("Z2,300,1", "OK"), ("z2,300,1", "OK"), ("c", "T05rwatch:300;")])]
+    #[case::illegal_instruction(&[("M200,2:ffff", "OK"), ("s", "S04"), ("p11", "0002")])]
+    #[case::target_description(&[("qXfer:features:read:target.xml:0,5", "m<?xml")])]
+    #[case::unsupported(&[("vMustReplyEmpty", "")])]
+    fn test_packets(#[case] exchanges: &[(&str, &str)]) {
+        let replies = run_stub(
+            exchanges
+                .iter()
+                .map(|(packet, _)| packet.to_string())
+                .collect(),
+        );
+        let expected: Vec<&str> = exchanges.iter().map(|(_, reply)| *reply).collect();
+
+        assert_eq!(replies, expected);
+    }
+
+    #[rstest]
+    fn test_read_memory_fits_in_a_packet() {
+        let replies = run_stub(vec!["m0,1000".to_string()]);
+        // Half the memory, two hex digits per byte
+        assert_eq!(replies[0].len(), PACKET_SIZE);
+    }
+
+    #[rstest]
+    fn test_interrupt_halts_running_program() {
+        let mut stub = get_test_stub();
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let client = thread::spawn(move || {
+            let mut stream = connect(port);
+            stream.write_all(&packet("c")).unwrap();
+            thread::sleep(std::time::Duration::from_millis(50));
+            stream.write_all(&[0x03]).unwrap();
+            let reply = read_reply(&mut stream);
+            stream.write_all(&packet("D")).unwrap();
+            read_reply(&mut stream);
+            reply
+        });
+
+        let (stream, _) = listener.accept().unwrap();
+        stub.serve(stream).unwrap();
+        assert_eq!(client.join().unwrap(), "S02");
+    }
+
+    #[rstest]
+    fn test_bad_checksum_is_rejected() {
+        let mut stub = get_test_stub();
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let client = thread::spawn(move || {
+            let mut stream = connect(port);
+            stream.write_all(b"$?#00").unwrap();
+            let mut nack = [0];
+            stream.read_exact(&mut nack).unwrap();
+            stream.write_all(&packet("k")).unwrap();
+            nack[0]
+        });
+
+        let (stream, _) = listener.accept().unwrap();
+        stub.serve(stream).unwrap();
+        assert_eq!(client.join().unwrap(), b'-');
+    }
+
+    /// Sends every packet to a stub for [`PROGRAM`] and returns the replies
+    fn run_stub(packets: Vec<String>) -> Vec<String> {
+        let mut stub = get_test_stub();
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let client = thread::spawn(move || {
+            let mut stream = connect(port);
+            let replies: Vec<String> = packets
+                .iter()
+                .map(|data| {
+                    stream.write_all(&packet(data)).unwrap();
+                    read_reply(&mut stream)
+                })
+                .collect();
+
+            stream.write_all(&packet("D")).unwrap();
+            assert_eq!(read_reply(&mut stream), "OK");
+            replies
+        });
+
+        let (stream, _) = listener.accept().unwrap();
+        stub.serve(stream).unwrap();
+        client.join().unwrap()
+    }
+
+    fn get_test_stub() -> GdbStub<TestDisplay> {
+        let mut chip8 = Chip8::new(TestDisplay::new(), Compatibility::Cosmac);
+        chip8.write_memory(0x200, &PROGRAM).unwrap();
+        chip8.set_pc(0x200).unwrap();
+        GdbStub::new(chip8)
+    }
+
+    fn connect(port: u16) -> TcpStream {
+        let stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        stream.set_nodelay(true).unwrap();
+        stream
+    }
+
+    fn packet(data: &str) -> Vec<u8> {
+        format!("${}#{:02x}", data, checksum_of(data.as_bytes())).into_bytes()
+    }
+
+    /// Reads the acknowledgement and the reply packet, then acknowledges it
+    fn read_reply(stream: &mut TcpStream) -> String {
+        let mut received = vec![];
+        let mut byte = [0];
+        loop {
+            stream.read_exact(&mut byte).unwrap();
+            received.push(byte[0]);
+            if received.len() > 3 && received[received.len() - 3] == b'#' {
+                break;
+            }
+        }
+        stream.write_all(b"+").unwrap();
+
+        let start = received.iter().position(|&b| b == b'$').unwrap();
+        String::from_utf8(received[start + 1..received.len() - 3].to_vec()).unwrap()
+    }
+}
@@ -0,0 +1,549 @@
+mod command;
+mod expr;
+pub mod gdb;
+
+use std::{
+    collections::BTreeMap,
+    io::{BufRead, Write},
+    sync::mpsc::{channel, Receiver, TryRecvError},
+    thread,
+    time::Instant,
+};
+
+use crate::{
+    chip8::{
+        instruction::Chip8Instruction,
+        wait_for_next_frame,
+        watch::{Access, Watchpoint},
+        Chip8,
+    },
+    debugger::{
+        command::{Command, Register, HELP},
+        expr::Condition,
+    },
+    display::Display,
+};
+
+/// Interactive debugger reading commands from the terminal.
+///
+/// Commands are read on a separate thread so the display keeps being
+/// presented, and the window stays responsive, while waiting at the prompt.
+pub struct Debugger<D>
+where
+    D: Display,
+{
+    chip8: Chip8<D>,
+    breakpoints: BTreeMap<u16, Trigger>,
+    /// Breakpoints without an address, checked after every instruction
+    conditional_breaks: Vec<Trigger>,
+    /// Conditions of the watchpoints armed in `chip8`
+    watch_triggers: Vec<(Watchpoint, Trigger)>,
+    register_breaks: Vec<(Register, u16)>,
+
+    /// Lines typed at the prompt
+    input: Receiver<String>,
+    /// Repeated when an empty line is entered
+    last_command: Option<Command>,
+}
+
+impl<D> Debugger<D>
+where
+    D: Display,
+{
+    pub fn new(chip8: Chip8<D>) -> Self {
+        let (sender, input) = channel();
+        thread::spawn(move || {
+            for line in std::io::stdin().lock().lines() {
+                let Ok(line) = line else { break };
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Debugger::with_input(chip8, input)
+    }
+
+    fn with_input(mut chip8: Chip8<D>, input: Receiver<String>) -> Self {
+        chip8.track_code_writes();
+        Debugger {
+            chip8,
+            breakpoints: BTreeMap::new(),
+            conditional_breaks: vec![],
+            watch_triggers: vec![],
+            register_breaks: vec![],
+            input,
+            last_command: None,
+        }
+    }
+
+    /// Runs the prompt until the user quits, the window is closed or stdin ends
+    pub fn run(&mut self) {
+        println!("Type 'help' for commands");
+        println!("{}", self.location());
+
+        while let Some(line) = self.read_line() {
+            let command = if line.trim().is_empty() {
+                match self.last_command.clone() {
+                    Some(command) => command,
+                    None => continue,
+                }
+            } else {
+                match Command::parse(&line) {
+                    Ok(command) => command,
+                    Err(e) => {
+                        println!("{}", e);
+                        continue;
+                    }
+                }
+            };
+
+            self.last_command = Some(command.clone());
+            if command == Command::Quit {
+                break;
+            }
+            self.execute(command);
+        }
+    }
+
+    /// Waits for the next line at the prompt while presenting frames
+    fn read_line(&mut self) -> Option<String> {
+        print!("(chip8) ");
+        std::io::stdout().flush().ok();
+
+        loop {
+            let frame_start = Instant::now();
+            match self.input.try_recv() {
+                Ok(line) => return Some(line),
+                Err(TryRecvError::Disconnected) => return None,
+                Err(TryRecvError::Empty) => {}
+            }
+            if !self.chip8.is_open() {
+                return None;
+            }
+
+            self.chip8.sync_display();
+            wait_for_next_frame(frame_start);
+        }
+    }
+
+    fn execute(&mut self, command: Command) {
+        match command {
+            Command::ListBreakpoints => println!("{}", self.stop_conditions()),
+            Command::Break(addr, condition) => {
+                let trigger = Trigger::new(condition);
+                println!("Breakpoint at {:#06x}{}", addr, trigger);
+                self.breakpoints.insert(addr, trigger);
+            }
+            Command::BreakIf(condition) => {
+                let trigger = Trigger::new(Some(condition));
+                println!("Break{}", trigger);
+                self.conditional_breaks.push(trigger);
+            }
+            Command::Delete(addr) => {
+                if self.breakpoints.remove(&addr).is_none() {
+                    println!("No breakpoint at {:#06x}", addr);
+                }
+            }
+            Command::DeleteConditions => self.conditional_breaks.clear(),
+            Command::Watch(watchpoint, condition) => {
+                let trigger = Trigger::new(condition);
+                println!("{}{}", describe_watchpoint(watchpoint), trigger);
+                self.chip8.add_watchpoint(watchpoint);
+                self.watch_triggers.push((watchpoint, trigger));
+            }
+            Command::Unwatch(addr) => {
+                self.watch_triggers
+                    .retain(|(watchpoint, _)| watchpoint.start != addr);
+                if !self.chip8.remove_watchpoints(addr) {
+                    println!("No watchpoint at {:#06x}", addr);
+                }
+            }
+            Command::RegisterBreak(register, value) => {
+                self.register_breaks.push((register, value));
+                println!("Break when {} changes to {:#x}", register, value);
+            }
+            Command::RegisterUnbreak(register) => {
+                let count = self.register_breaks.len();
+                self.register_breaks.retain(|(other, _)| *other != register);
+                if self.register_breaks.len() == count {
+                    println!("No register breakpoint on {}", register);
+                }
+            }
+            Command::Step(count) => {
+                for _ in 0..count {
+                    if let Some(reason) = self.step_checked() {
+                        println!("{}", reason);
+                        break;
+                    }
+                }
+                println!("{}", self.location());
+            }
+            Command::Next => self.next(),
+            Command::Finish => self.finish(),
+            Command::Continue => self.run_until(|_| false),
+            Command::Registers => println!("{}", self.registers()),
+            Command::Stack => println!("{}", self.stack()),
+            Command::Disassemble(addr, count) => {
+                // Centre the listing on pc, keeping it aligned with pc
+                let start = addr.unwrap_or(self.chip8.pc().saturating_sub(8));
+                println!("{}", self.disassemble(start, count));
+            }
+            Command::ReadMemory(addr, len) => println!("{}", self.dump_memory(addr, len)),
+            Command::WriteMemory(addr, bytes) => {
+                if let Err(e) = self.chip8.write_memory(addr, &bytes) {
+                    println!("{}", e);
+                }
+            }
+            Command::Help => println!("{}", HELP),
+            Command::Quit => {}
+        }
+    }
+
+    /// Steps over a call by running until it returns to the next instruction
+    fn next(&mut self) {
+        let Some(Chip8Instruction::Call(_)) = self.chip8.instruction_at(self.chip8.pc()) else {
+            self.execute(Command::Step(1));
+            return;
+        };
+
+        let return_addr = self.chip8.pc() + 2;
+        let depth = self.chip8.stack_depth();
+        self.run_until(|chip8| chip8.pc() == return_addr && chip8.stack_depth() == depth);
+    }
+
+    fn finish(&mut self) {
+        let depth = self.chip8.stack_depth();
+        if depth == 0 {
+            println!("Not in a subroutine");
+            return;
+        }
+
+        self.run_until(|chip8| chip8.stack_depth() < depth);
+    }
+
+    /// Runs at normal speed until `stop` holds, a breakpoint is hit or enter is pressed
+    fn run_until(&mut self, mut stop: impl FnMut(&Chip8<D>) -> bool) {
+        while self.chip8.is_open() {
+            let frame_start = Instant::now();
+
+            for _ in 0..self.chip8.cycles_per_frame() {
+                if let Some(reason) = self.step_checked() {
+                    println!("{}\n{}", reason, self.location());
+                    return;
+                }
+                if stop(&self.chip8) {
+                    println!("{}", self.location());
+                    return;
+                }
+            }
+
+            self.chip8.sync_display();
+            if self.input.try_recv().is_ok() {
+                println!("Interrupted\n{}", self.location());
+                return;
+            }
+            wait_for_next_frame(frame_start);
+        }
+    }
+
+    /// Executes one instruction and returns why execution should stop, if it should
+    fn step_checked(&mut self) -> Option<String> {
+        let before: Vec<u16> = self
+            .register_breaks
+            .iter()
+            .map(|(register, _)| self.register_value(*register))
+            .collect();
+
+        if let Err(e) = self.chip8.step() {
+            return Some(e);
+        }
+        for write in self.chip8.take_code_writes() {
+            println!("{}", write);
+        }
+
+        if let Some(hit) = self.chip8.take_watch_hit() {
+            let fired = self
+                .watch_triggers
+                .iter_mut()
+                .find(|(watchpoint, _)| *watchpoint == hit.watchpoint)
+                .is_none_or(|(_, trigger)| trigger.fire(&self.chip8));
+
+            if fired {
+                let access = match hit.access {
+                    Access::Write => "Write",
+                    _ => "Read",
+                };
+                return Some(format!(
+                    "{} of {:#06x} by {:#06x}, {}",
+                    access,
+                    hit.addr,
+                    hit.pc,
+                    describe_watchpoint(hit.watchpoint)
+                ));
+            }
+        }
+
+        for (&(register, value), before) in self.register_breaks.iter().zip(before) {
+            if before != value && self.register_value(register) == value {
+                return Some(format!("{} changed to {:#x}", register, value));
+            }
+        }
+
+        if let Some(trigger) = self.breakpoints.get_mut(&self.chip8.pc()) {
+            if trigger.fire(&self.chip8) {
+                return Some(format!("Breakpoint hit{}", trigger));
+            }
+        }
+
+        for trigger in &mut self.conditional_breaks {
+            if trigger.fire(&self.chip8) {
+                return Some(format!("Break{}", trigger));
+            }
+        }
+
+        None
+    }
+
+    fn register_value(&self, register: Register) -> u16 {
+        match register {
+            Register::V(x) => self.chip8.v_reg()[x as usize] as u16,
+            Register::I => self.chip8.i_reg(),
+        }
+    }
+
+    /// Lists the breakpoints, watchpoints and register breakpoints
+    fn stop_conditions(&self) -> String {
+        let breakpoints = self
+            .breakpoints
+            .iter()
+            .map(|(addr, trigger)| format!("Breakpoint at {:#06x}{}", addr, trigger));
+        let conditional_breaks = self
+            .conditional_breaks
+            .iter()
+            .map(|trigger| format!("Break{}", trigger));
+        let watchpoints = self.watch_triggers.iter().map(|(watchpoint, trigger)| {
+            format!("{}{}", describe_watchpoint(*watchpoint), trigger)
+        });
+        let register_breaks = self
+            .register_breaks
+            .iter()
+            .map(|(register, value)| format!("Break when {} changes to {:#x}", register, value));
+
+        let conditions: Vec<String> = breakpoints
+            .chain(conditional_breaks)
+            .chain(watchpoints)
+            .chain(register_breaks)
+            .collect();
+        if conditions.is_empty() {
+            return "No breakpoints".to_string();
+        }
+        conditions.join("\n")
+    }
+
+    /// The instruction at pc
+    fn location(&self) -> String {
+        self.disassemble(self.chip8.pc(), 1)
+    }
+
+    fn disassemble(&self, start: u16, count: usize) -> String {
+        let memory = self.chip8.memory();
+
+        (start as usize..memory.len() - 1)
+            .step_by(2)
+            .take(count)
+            .map(|addr| {
+                let marker = match (
+                    addr as u16 == self.chip8.pc(),
+                    self.breakpoints.contains_key(&(addr as u16)),
+                ) {
+                    (true, _) => "=>",
+                    (false, true) => " *",
+                    (false, false) => "  ",
+                };
+                let instruction = match self.chip8.instruction_at(addr as u16) {
+                    Some(instruction) => instruction.to_string(),
+                    None => "??".to_string(),
+                };
+                format!(
+                    "{} {:#06x}: {:02x} {:02x}  {}",
+                    marker,
+                    addr,
+                    memory[addr],
+                    memory[addr + 1],
+                    instruction
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn registers(&self) -> String {
+        let v_reg = self.chip8.v_reg();
+        let line = |range: std::ops::Range<usize>| {
+            range
+                .map(|x| format!("v{:x}: {:02x}", x, v_reg[x]))
+                .collect::<Vec<_>>()
+                .join("  ")
+        };
+
+        format!(
+            "{}\n{}\ni: {:#06x}  pc: {:#06x}  sp: {}",
+            line(0..8),
+            line(8..16),
+            self.chip8.i_reg(),
+            self.chip8.pc(),
+            self.chip8.stack_depth()
+        )
+    }
+
+    fn stack(&self) -> String {
+        let stack = self.chip8.stack();
+        if stack.is_empty() {
+            return "Stack is empty".to_string();
+        }
+
+        stack
+            .iter()
+            .rev()
+            .enumerate()
+            .map(|(frame, addr)| format!("#{} {:#06x}", frame, addr))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn dump_memory(&self, addr: u16, len: usize) -> String {
+        let memory = self.chip8.memory();
+        let end = (addr as usize + len).min(memory.len());
+
+        memory[addr as usize..end]
+            .chunks(16)
+            .enumerate()
+            .map(|(line, bytes)| {
+                let bytes: Vec<String> = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+                format!("{:#06x}: {}", addr as usize + line * 16, bytes.join(" "))
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// Optional condition of a breakpoint or watchpoint and the number of times it was reached
+struct Trigger {
+    condition: Option<Condition>,
+    hits: u64,
+}
+
+impl Trigger {
+    fn new(condition: Option<Condition>) -> Self {
+        Trigger { condition, hits: 0 }
+    }
+
+    /// Counts a hit and returns whether execution should stop
+    fn fire<D>(&mut self, chip8: &Chip8<D>) -> bool
+    where
+        D: Display,
+    {
+        self.hits += 1;
+        self.condition
+            .as_ref()
+            .is_none_or(|condition| condition.holds(chip8, self.hits))
+    }
+}
+
+/// Formats the condition as a suffix of the breakpoint description
+impl std::fmt::Display for Trigger {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.condition {
+            Some(condition) => write!(f, " if {}", condition.source),
+            None => Ok(()),
+        }
+    }
+}
+
+fn describe_watchpoint(watchpoint: Watchpoint) -> String {
+    let access = match watchpoint.access {
+        Access::Read => "Read",
+        Access::Write => "Write",
+        Access::ReadWrite => "Access",
+    };
+    format!(
+        "{} watchpoint at {:#06x}, {} bytes",
+        access, watchpoint.start, watchpoint.len
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{chip8::compat::Compatibility, display::test_display::TestDisplay};
+    use rstest::*;
+
+    /// Calls a subroutine at 0x208 that sets v1, then loops forever
+    static PROGRAM: [u8; 12] = [
+        0x60, 0x01, // 0x200: v0 = 1
+        0x22, 0x08, // 0x202: call 0x208
+        0x70, 0x01, // 0x204: v0 += 1
+        0x12, 0x04, // 0x206: jump to 0x204
+        0x61, 0x02, // 0x208: v1 = 2
+        0x00, 0xee, // 0x20A: return
+    ];
+
+    #[rstest]
+    #[case::step(&["s 2"], 0x208, 1)]
+    #[case::next_steps_over_call(&["s", "n"], 0x204, 0)]
+    #[case::finish(&["s 3", "finish"], 0x204, 0)]
+    #[case::continue_to_breakpoint(&["b 0x20a", "c"], 0x20a, 1)]
+    #[case::empty_line_repeats(&["s", ""], 0x208, 1)]
+    #[case::step_count_repeats(&["s 2", ""], 0x204, 0)]
+    #[case::register_break(&["regbreak v1 2", "c"], 0x20a, 1)]
+    #[case::register_break_ignores_current_value(&["s", "regbreak v0 1", "b 0x20a", "c"], 0x20a, 1)]
+    #[case::conditional_breakpoint(&["b 0x204 if v0 == 3", "c"], 0x204, 0)]
+    #[case::break_if(&["b if stack.depth == 1 && v1 == 2", "c"], 0x20a, 1)]
+    #[case::hitcount(&["b 0x206 if hitcount > 2", "c"], 0x206, 0)]
+    #[case::read_watchpoint(&["w 0x206 0xd0 0x01", "rwatch 0x000 2", "c"], 0x208, 0)]
+    #[case::write_watchpoint(&["w 0x208 0xf0 0x55", "watch 0x000", "c"], 0x20a, 1)]
+    fn test_commands_move_pc(#[case] lines: &[&str], #[case] pc: u16, #[case] stack_depth: usize) {
+        let debugger = run_debugger(lines);
+
+        assert_eq!(debugger.chip8.pc(), pc);
+        assert_eq!(debugger.chip8.stack_depth(), stack_depth);
+    }
+
+    #[rstest]
+    fn test_write_memory() {
+        let debugger = run_debugger(&["w 0x300 0xab 0xcd"]);
+
+        assert_eq!(debugger.dump_memory(0x300, 3), "0x0300: ab cd 00");
+    }
+
+    #[rstest]
+    fn test_disassemble_marks_pc_and_breakpoints() {
+        let debugger = run_debugger(&["b 0x202"]);
+
+        assert_eq!(
+            debugger.disassemble(0x200, 2),
+            "=> 0x0200: 60 01  0x6XNN - Set v0 to 01\n \
+             * 0x0202: 22 08  0x2NNN - Call subroutine at address 520"
+        );
+    }
+
+    /// Feeds `lines` to a debugger for [`PROGRAM`] until it runs out of input
+    fn run_debugger(lines: &[&str]) -> Debugger<TestDisplay> {
+        let mut chip8 = Chip8::new(TestDisplay::new(), Compatibility::Cosmac);
+        chip8.write_memory(0x200, &PROGRAM).unwrap();
+        // Jump from the reset address to the program like a loaded ROM would start
+        chip8.write_memory(0x000, &[0x12, 0x00]).unwrap();
+
+        let (sender, input) = channel();
+        sender.send("s".to_string()).unwrap();
+        for line in lines {
+            sender.send(line.to_string()).unwrap();
+        }
+        drop(sender);
+
+        let mut debugger = Debugger::with_input(chip8, input);
+        debugger.run();
+        debugger
+    }
+}
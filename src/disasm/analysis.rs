@@ -0,0 +1,636 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{
+    chip8::instruction::Chip8Instruction,
+    disasm::{LabelKind, Style, ENTRY_POINT},
+};
+
+/// Levels of the CHIP-8 call stack
+pub const STACK_LIMIT: usize = 16;
+
+/// How execution leaves an instruction
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Flow {
+    Next,
+    /// Continues with the next instruction or the one after
+    Skip,
+    Jump(u16),
+    Call(u16),
+    Return,
+    /// `BNNN`, a jump to NNN + V0
+    Indirect(u16),
+    /// SUPER-CHIP's `exit`
+    Exit,
+}
+
+/// Edge leaving a basic block
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Edge {
+    Next(u16),
+    Skip(u16),
+    Jump(u16),
+    Call(u16),
+}
+
+impl Edge {
+    fn target(&self) -> u16 {
+        match *self {
+            Edge::Next(addr) | Edge::Skip(addr) | Edge::Jump(addr) | Edge::Call(addr) => addr,
+        }
+    }
+}
+
+/// Straight-line run of instructions, only entered at its first one
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Block {
+    pub start: u16,
+    /// Address of the last instruction
+    pub last: u16,
+    pub edges: Vec<Edge>,
+}
+
+/// Something that limits or defeats static analysis, or that is worth a look before running
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Finding {
+    /// Jump to itself, the usual way to halt
+    Halt(u16),
+    /// `BNNN` at the address, jumping relative to a base
+    IndirectJump(u16, u16),
+    /// Write through I at `pc` covering `start..end`, which holds code
+    SelfModifying { pc: u16, start: u16, end: u16 },
+    /// Write through I where I isn't known within the block
+    UnresolvedWrite(u16),
+    /// Opcode no CHIP-8 variant defines
+    InvalidInstruction(u16, u16),
+    /// Control flow at the address continues outside the ROM
+    OutsideRom(u16, u16),
+    /// Subroutine that can call itself again, making the call depth unbounded
+    Recursion(u16),
+    /// Maximum call depth exceeding the stack
+    StackOverflow(usize),
+}
+
+impl std::fmt::Display for Finding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Finding::Halt(addr) => write!(f, "{:#05x}: jumps to itself, halting", addr),
+            Finding::IndirectJump(addr, base) => write!(
+                f,
+                "{:#05x}: indirect jump to {:#05x} + v0 can't be followed",
+                addr, base
+            ),
+            Finding::SelfModifying { pc, start, end } => write!(
+                f,
+                "{:#05x}: writes {:#05x}..{:#05x}, which holds code",
+                pc, start, end
+            ),
+            Finding::UnresolvedWrite(pc) => {
+                write!(f, "{:#05x}: writes memory at an unknown I", pc)
+            }
+            Finding::InvalidInstruction(addr, opcode) => {
+                write!(f, "{:#05x}: invalid instruction {:#06x}", addr, opcode)
+            }
+            Finding::OutsideRom(addr, target) => write!(
+                f,
+                "{:#05x}: continues at {:#05x}, outside the ROM",
+                addr, target
+            ),
+            Finding::Recursion(addr) => write!(
+                f,
+                "{:#05x}: subroutine is recursive, call depth is unbounded",
+                addr
+            ),
+            Finding::StackOverflow(depth) => write!(
+                f,
+                "call depth {} exceeds the {} level stack",
+                depth, STACK_LIMIT
+            ),
+        }
+    }
+}
+
+/// Control-flow graph of the code reachable from the entry point
+pub struct Analysis {
+    /// Opcodes of reachable instructions by address
+    opcodes: BTreeMap<u16, u16>,
+    blocks: BTreeMap<u16, Block>,
+    labels: BTreeMap<u16, LabelKind>,
+    /// Deepest call chain from the entry point, None when recursion makes it unbounded
+    max_call_depth: Option<usize>,
+    findings: BTreeSet<Finding>,
+}
+
+impl Analysis {
+    pub fn new(rom: &[u8]) -> Self {
+        let mut analysis = Analysis {
+            opcodes: BTreeMap::new(),
+            blocks: BTreeMap::new(),
+            labels: BTreeMap::new(),
+            max_call_depth: Some(0),
+            findings: BTreeSet::new(),
+        };
+
+        analysis.find_instructions(rom);
+        analysis.build_blocks();
+        analysis.find_writes();
+        analysis.max_call_depth = analysis.call_depth();
+        match analysis.max_call_depth {
+            Some(depth) if depth > STACK_LIMIT => {
+                analysis.findings.insert(Finding::StackOverflow(depth));
+            }
+            _ => {}
+        }
+
+        analysis
+    }
+
+    /// Summary, findings and the blocks with their edges
+    pub fn report(&self) -> String {
+        let mut lines = vec![
+            format!("blocks: {}", self.blocks.len()),
+            match self.max_call_depth {
+                Some(depth) => format!("call depth: {} of {}", depth, STACK_LIMIT),
+                None => format!("call depth: unbounded of {}", STACK_LIMIT),
+            },
+        ];
+        lines.extend(self.findings.iter().map(Finding::to_string));
+        lines.push(String::new());
+
+        for block in self.blocks.values() {
+            let edges = block
+                .edges
+                .iter()
+                .map(|edge| match edge {
+                    Edge::Next(addr) => format!("next {:#05x}", addr),
+                    Edge::Skip(addr) => format!("skip {:#05x}", addr),
+                    Edge::Jump(addr) => format!("jump {:#05x}", addr),
+                    Edge::Call(addr) => format!("call {:#05x}", addr),
+                })
+                .collect::<Vec<_>>();
+            lines.push(format!(
+                "block {:#05x}..={:#05x}: {}",
+                block.start,
+                block.last,
+                if edges.is_empty() {
+                    "end".to_string()
+                } else {
+                    edges.join(", ")
+                }
+            ));
+        }
+
+        let mut report = lines.join("\n");
+        report.push('\n');
+        report
+    }
+
+    /// Graphviz DOT graph with one node per block listing its instructions
+    pub fn dot(&self) -> String {
+        let flagged = self
+            .findings
+            .iter()
+            .filter_map(|finding| match *finding {
+                Finding::Halt(addr)
+                | Finding::IndirectJump(addr, _)
+                | Finding::SelfModifying { pc: addr, .. }
+                | Finding::UnresolvedWrite(addr)
+                | Finding::InvalidInstruction(addr, _)
+                | Finding::OutsideRom(addr, _) => Some(addr),
+                _ => None,
+            })
+            .collect::<BTreeSet<_>>();
+
+        let mut lines = vec![
+            "digraph rom {".to_string(),
+            "    node [shape=box, fontname=\"monospace\"];".to_string(),
+        ];
+        for block in self.blocks.values() {
+            let mut label = match self.label(block.start) {
+                Some(name) => format!("{}:\\l", name),
+                None => String::new(),
+            };
+            for (addr, opcode) in self.opcodes.range(block.start..=block.last) {
+                let text = match Chip8Instruction::decode(*opcode) {
+                    Some(instruction) => {
+                        Style::Cowgod.instruction(instruction, |addr| self.label(addr))
+                    }
+                    None => format!("#{:04x}", opcode),
+                };
+                label.push_str(&format!("{:03x}  {}\\l", addr, text.replace('"', "\\\"")));
+            }
+
+            let color = if flagged.range(block.start..=block.last).next().is_some() {
+                ", color=red"
+            } else {
+                ""
+            };
+            lines.push(format!(
+                "    b{:03x} [label=\"{}\"{}];",
+                block.start, label, color
+            ));
+            for edge in &block.edges {
+                let style = match edge {
+                    Edge::Next(_) => "",
+                    Edge::Skip(_) => " [label=\"skip\"]",
+                    Edge::Jump(_) => " [style=bold]",
+                    Edge::Call(_) => " [style=dashed, label=\"call\"]",
+                };
+                lines.push(format!(
+                    "    b{:03x} -> b{:03x}{};",
+                    block.start,
+                    edge.target(),
+                    style
+                ));
+            }
+        }
+        lines.push("}".to_string());
+
+        let mut dot = lines.join("\n");
+        dot.push('\n');
+        dot
+    }
+
+    fn label(&self, addr: u16) -> Option<String> {
+        self.labels.get(&addr).map(|kind| kind.name(addr))
+    }
+
+    /// Walks every path from the entry point, recording the opcode at each reachable address
+    fn find_instructions(&mut self, rom: &[u8]) {
+        let opcode_at = |addr: u16| {
+            let offset = addr.checked_sub(ENTRY_POINT)? as usize;
+            Some(u16::from_be_bytes([
+                *rom.get(offset)?,
+                *rom.get(offset + 1)?,
+            ]))
+        };
+
+        let mut pending = vec![(ENTRY_POINT, ENTRY_POINT)];
+        while let Some((from, addr)) = pending.pop() {
+            if self.opcodes.contains_key(&addr) {
+                continue;
+            }
+            let Some(opcode) = opcode_at(addr) else {
+                self.findings.insert(Finding::OutsideRom(from, addr));
+                continue;
+            };
+            let Some(flow) = flow(opcode) else {
+                self.findings
+                    .insert(Finding::InvalidInstruction(addr, opcode));
+                continue;
+            };
+
+            self.opcodes.insert(addr, opcode);
+            let next = addr.wrapping_add(2);
+            match flow {
+                Flow::Next => pending.push((addr, next)),
+                Flow::Skip => pending.extend([(addr, next), (addr, addr.wrapping_add(4))]),
+                Flow::Jump(target) if target == addr => {
+                    self.findings.insert(Finding::Halt(addr));
+                }
+                Flow::Jump(target) => pending.push((addr, target)),
+                Flow::Call(target) => pending.extend([(addr, next), (addr, target)]),
+                Flow::Indirect(base) => {
+                    self.findings.insert(Finding::IndirectJump(addr, base));
+                }
+                Flow::Return | Flow::Exit => {}
+            }
+        }
+    }
+
+    /// Splits the reachable instructions into blocks at every jump target and after every branch
+    fn build_blocks(&mut self) {
+        let mut leaders = BTreeSet::from([ENTRY_POINT]);
+        self.labels.insert(ENTRY_POINT, LabelKind::Entry);
+        for (addr, opcode) in &self.opcodes {
+            let next = addr.wrapping_add(2);
+            match flow(*opcode) {
+                Some(Flow::Skip) => leaders.extend([next, addr.wrapping_add(4)]),
+                Some(Flow::Jump(target)) => {
+                    leaders.extend([target, next]);
+                    self.labels.entry(target).or_insert(LabelKind::Jump);
+                }
+                Some(Flow::Call(target)) => {
+                    leaders.extend([target, next]);
+                    let label = self.labels.entry(target).or_insert(LabelKind::Subroutine);
+                    *label = (*label).min(LabelKind::Subroutine);
+                }
+                Some(Flow::Next) | None => {}
+                Some(Flow::Return | Flow::Indirect(_) | Flow::Exit) => {
+                    leaders.insert(next);
+                }
+            }
+        }
+
+        for start in leaders
+            .iter()
+            .filter(|addr| self.opcodes.contains_key(addr))
+        {
+            let mut last = *start;
+            loop {
+                let next = last.wrapping_add(2);
+                let continues = flow(self.opcodes[&last]) == Some(Flow::Next)
+                    && self.opcodes.contains_key(&next)
+                    && !leaders.contains(&next);
+                if !continues {
+                    break;
+                }
+                last = next;
+            }
+
+            let next = last.wrapping_add(2);
+            let edges = match flow(self.opcodes[&last]) {
+                Some(Flow::Next) => vec![Edge::Next(next)],
+                Some(Flow::Skip) => vec![Edge::Next(next), Edge::Skip(last.wrapping_add(4))],
+                Some(Flow::Jump(target)) => vec![Edge::Jump(target)],
+                Some(Flow::Call(target)) => vec![Edge::Call(target), Edge::Next(next)],
+                _ => vec![],
+            };
+            let edges = edges
+                .into_iter()
+                .filter(|edge| self.opcodes.contains_key(&edge.target()))
+                .collect();
+
+            self.blocks.insert(
+                *start,
+                Block {
+                    start: *start,
+                    last,
+                    edges,
+                },
+            );
+        }
+    }
+
+    /// Flags `FX33` and `FX55` writes landing on code, following I within each block
+    fn find_writes(&mut self) {
+        let code = self
+            .opcodes
+            .keys()
+            .flat_map(|addr| [*addr, addr.wrapping_add(1)])
+            .collect::<BTreeSet<_>>();
+
+        for block in self.blocks.values() {
+            let mut i_reg = None;
+            for (addr, opcode) in self.opcodes.range(block.start..=block.last) {
+                let x = opcode >> 8 & 0xf;
+                let len = match opcode & 0xf0ff {
+                    0xf033 => 3,
+                    0xf055 => x + 1,
+                    _ => {
+                        i_reg = match opcode & 0xf0ff {
+                            0xf01e | 0xf065 => None,
+                            _ if opcode & 0xf000 == 0xa000 => Some(opcode & 0x0fff),
+                            _ => i_reg,
+                        };
+                        continue;
+                    }
+                };
+
+                match i_reg {
+                    Some(start) => {
+                        let end = start + len;
+                        if code.range(start..end).next().is_some() {
+                            self.findings.insert(Finding::SelfModifying {
+                                pc: *addr,
+                                start,
+                                end,
+                            });
+                        }
+                    }
+                    None => {
+                        self.findings.insert(Finding::UnresolvedWrite(*addr));
+                    }
+                }
+                // COSMAC advances I past the registers it stores
+                if opcode & 0xf0ff == 0xf055 {
+                    i_reg = None;
+                }
+            }
+        }
+    }
+
+    /// Deepest call chain from the entry point, flagging recursive subroutines
+    fn call_depth(&mut self) -> Option<usize> {
+        let mut depths = BTreeMap::new();
+        self.function_depth(ENTRY_POINT, &mut depths, &mut vec![])
+    }
+
+    /// Stack levels used by calls made from the function starting at `entry`
+    fn function_depth(
+        &mut self,
+        entry: u16,
+        depths: &mut BTreeMap<u16, Option<usize>>,
+        active: &mut Vec<u16>,
+    ) -> Option<usize> {
+        if let Some(depth) = depths.get(&entry) {
+            return *depth;
+        }
+        if active.contains(&entry) {
+            self.findings.insert(Finding::Recursion(entry));
+            return None;
+        }
+        active.push(entry);
+
+        // Blocks of the function, following everything but calls
+        let mut callees = BTreeSet::new();
+        let mut visited = BTreeSet::new();
+        let mut pending = vec![entry];
+        while let Some(start) = pending.pop() {
+            if !visited.insert(start) {
+                continue;
+            }
+            let Some(block) = self.blocks.get(&start) else {
+                continue;
+            };
+            for edge in &block.edges {
+                match edge {
+                    Edge::Call(target) => {
+                        callees.insert(*target);
+                    }
+                    edge => pending.push(edge.target()),
+                }
+            }
+        }
+
+        let mut depth = Some(0);
+        for callee in callees {
+            let callee_depth = self.function_depth(callee, depths, active).map(|d| d + 1);
+            depth = depth.zip(callee_depth).map(|(a, b)| a.max(b));
+        }
+
+        active.pop();
+        depths.insert(entry, depth);
+        depth
+    }
+}
+
+/// How execution leaves `opcode`, covering the opcodes of CHIP-8 and SUPER-CHIP.
+/// None for opcodes that are invalid everywhere.
+fn flow(opcode: u16) -> Option<Flow> {
+    if let Some(instruction) = Chip8Instruction::decode(opcode) {
+        return Some(match instruction {
+            Chip8Instruction::Jump(target) => Flow::Jump(target.into()),
+            Chip8Instruction::Call(target) => Flow::Call(target.into()),
+            Chip8Instruction::Return() => Flow::Return,
+            Chip8Instruction::SkipIfEqual(..)
+            | Chip8Instruction::SkipIfNotEqual(..)
+            | Chip8Instruction::SkipIfEqualXY(..)
+            | Chip8Instruction::SkipIfNotEqualXY(..) => Flow::Skip,
+            _ => Flow::Next,
+        });
+    }
+
+    // Opcodes the interpreter doesn't execute, but other interpreters do
+    match (opcode & 0xf000, opcode & 0x00ff) {
+        (0x0000, 0xfd) if opcode & 0x0f00 == 0 => Some(Flow::Exit),
+        (0x0000, 0xc0..=0xcf | 0xfb | 0xfc | 0xfe | 0xff) if opcode & 0x0f00 == 0 => {
+            Some(Flow::Next)
+        }
+        (0xb000, _) => Some(Flow::Indirect(opcode & 0x0fff)),
+        (0xc000, _) => Some(Flow::Next),
+        (0xe000, 0x9e | 0xa1) => Some(Flow::Skip),
+        (0xf000, 0x07 | 0x0a | 0x15 | 0x1e | 0x29 | 0x30 | 0x65 | 0x75 | 0x85) => Some(Flow::Next),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    /// Calls a subroutine, loops until a key is pressed, then halts
+    static ROM: [u8; 20] = [
+        0x22, 0x0c, // 0x200: call 0x20C
+        0xe0, 0x9e, // 0x202: skip if key v0 is pressed
+        0x12, 0x02, // 0x204: jump to 0x202
+        0x12, 0x06, // 0x206: halt
+        0xa2, 0x08, // 0x208: unreachable
+        0xff, 0xff, // 0x20A: unreachable
+        0xa3, 0x00, // 0x20C: i = 0x300
+        0xf2, 0x33, // 0x20E: bcd v2
+        0x00, 0xee, // 0x210: return
+        0x00, 0x00, // 0x212: padding
+    ];
+
+    #[rstest]
+    fn test_blocks() {
+        let analysis = Analysis::new(&ROM);
+
+        assert_eq!(
+            analysis.blocks.values().cloned().collect::<Vec<_>>(),
+            vec![
+                Block {
+                    start: 0x200,
+                    last: 0x200,
+                    edges: vec![Edge::Call(0x20c), Edge::Next(0x202)],
+                },
+                Block {
+                    start: 0x202,
+                    last: 0x202,
+                    edges: vec![Edge::Next(0x204), Edge::Skip(0x206)],
+                },
+                Block {
+                    start: 0x204,
+                    last: 0x204,
+                    edges: vec![Edge::Jump(0x202)],
+                },
+                Block {
+                    start: 0x206,
+                    last: 0x206,
+                    edges: vec![Edge::Jump(0x206)],
+                },
+                Block {
+                    start: 0x20c,
+                    last: 0x210,
+                    edges: vec![],
+                },
+            ]
+        );
+        assert_eq!(analysis.max_call_depth, Some(1));
+        assert_eq!(
+            analysis.findings.iter().copied().collect::<Vec<_>>(),
+            vec![Finding::Halt(0x206)]
+        );
+    }
+
+    #[rstest]
+    #[case::indirect_jump(&[0xb3, 0x00], Finding::IndirectJump(0x200, 0x300))]
+    #[case::self_modifying(
+        &[0xa2, 0x00, 0xf1, 0x55, 0x12, 0x04],
+        Finding::SelfModifying { pc: 0x202, start: 0x200, end: 0x202 }
+    )]
+    #[case::unresolved_write(&[0xf0, 0x1e, 0xf0, 0x33, 0x12, 0x04], Finding::UnresolvedWrite(0x202))]
+    #[case::invalid_instruction(&[0x01, 0x23], Finding::InvalidInstruction(0x200, 0x0123))]
+    #[case::outside_rom(&[0x13, 0x00], Finding::OutsideRom(0x200, 0x300))]
+    #[case::recursion(&[0x22, 0x02, 0x22, 0x02], Finding::Recursion(0x202))]
+    fn test_findings(#[case] rom: &[u8], #[case] finding: Finding) {
+        let analysis = Analysis::new(rom);
+        assert!(
+            analysis.findings.contains(&finding),
+            "{:?}",
+            analysis.findings
+        );
+    }
+
+    #[rstest]
+    #[case::within_limit(16, Some(16), false)]
+    #[case::overflow(17, Some(17), true)]
+    fn test_call_depth(#[case] levels: u16, #[case] depth: Option<usize>, #[case] overflow: bool) {
+        // Each subroutine calls the next one, the last one returns
+        let mut rom = (1..=levels)
+            .flat_map(|level| (0x2000 | (0x200 + level * 2)).to_be_bytes())
+            .collect::<Vec<_>>();
+        rom.extend([0x00, 0xee]);
+
+        let analysis = Analysis::new(&rom);
+        assert_eq!(analysis.max_call_depth, depth);
+        assert_eq!(
+            analysis
+                .findings
+                .contains(&Finding::StackOverflow(levels as usize)),
+            overflow
+        );
+    }
+
+    #[rstest]
+    fn test_report() {
+        assert_eq!(
+            Analysis::new(&ROM).report(),
+            "\
+blocks: 5
+call depth: 1 of 16
+0x206: jumps to itself, halting
+
+block 0x200..=0x200: call 0x20c, next 0x202
+block 0x202..=0x202: next 0x204, skip 0x206
+block 0x204..=0x204: jump 0x202
+block 0x206..=0x206: jump 0x206
+block 0x20c..=0x210: end
+"
+        );
+    }
+
+    #[rstest]
+    fn test_dot() {
+        assert_eq!(
+            Analysis::new(&ROM).dot(),
+            "\
+digraph rom {
+    node [shape=box, fontname=\"monospace\"];
+    b200 [label=\"main:\\l200  CALL sub_20c\\l\"];
+    b200 -> b20c [style=dashed, label=\"call\"];
+    b200 -> b202;
+    b202 [label=\"label_202:\\l202  #e09e\\l\"];
+    b202 -> b204;
+    b202 -> b206 [label=\"skip\"];
+    b204 [label=\"204  JP label_202\\l\"];
+    b204 -> b202 [style=bold];
+    b206 [label=\"label_206:\\l206  JP label_206\\l\", color=red];
+    b206 -> b206 [style=bold];
+    b20c [label=\"sub_20c:\\l20c  LD I, #300\\l20e  LD B, V2\\l210  RET\\l\"];
+}
+"
+        );
+    }
+}
@@ -0,0 +1,331 @@
+pub mod analysis;
+mod syntax;
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::chip8::instruction::Chip8Instruction;
+
+pub use syntax::Style;
+
+/// Address ROMs are loaded at and start executing from
+pub const ENTRY_POINT: u16 = 0x200;
+
+/// Data bytes emitted per directive line
+const DATA_BYTES_PER_LINE: usize = 8;
+
+/// What a label marks, in order of precedence when an address is several things
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LabelKind {
+    Entry,
+    Subroutine,
+    Jump,
+    Data,
+}
+
+impl LabelKind {
+    /// Generated name of a label of this kind at `addr`
+    pub fn name(&self, addr: u16) -> String {
+        match self {
+            LabelKind::Entry => "main".to_string(),
+            LabelKind::Subroutine => format!("sub_{:03x}", addr),
+            LabelKind::Jump => format!("label_{:03x}", addr),
+            LabelKind::Data => format!("data_{:03x}", addr),
+        }
+    }
+}
+
+/// ROM split into reachable instructions and data, with labels for every referenced address
+pub struct Disassembly<'a> {
+    rom: &'a [u8],
+    /// Reachable instructions by address
+    instructions: BTreeMap<u16, Chip8Instruction>,
+    labels: BTreeMap<u16, LabelKind>,
+}
+
+impl<'a> Disassembly<'a> {
+    /// Follows control flow from the entry point through jumps, calls and skips
+    pub fn new(rom: &'a [u8]) -> Self {
+        let mut disassembly = Disassembly {
+            rom,
+            instructions: BTreeMap::new(),
+            labels: BTreeMap::new(),
+        };
+
+        let mut pending = vec![ENTRY_POINT];
+        let mut claimed = BTreeSet::new();
+        while let Some(addr) = pending.pop() {
+            if disassembly.instructions.contains_key(&addr)
+                || claimed.contains(&addr)
+                || claimed.contains(&(addr + 1))
+            {
+                continue;
+            }
+            let Some(instruction) = disassembly.decode(addr) else {
+                continue;
+            };
+
+            claimed.extend([addr, addr + 1]);
+            disassembly.instructions.insert(addr, instruction);
+            pending.extend(successors(addr, instruction));
+        }
+
+        disassembly.add_label(ENTRY_POINT, LabelKind::Entry);
+        for instruction in disassembly
+            .instructions
+            .values()
+            .copied()
+            .collect::<Vec<_>>()
+        {
+            match instruction {
+                Chip8Instruction::Jump(addr) => disassembly.add_label(addr.into(), LabelKind::Jump),
+                Chip8Instruction::Call(addr) => {
+                    disassembly.add_label(addr.into(), LabelKind::Subroutine)
+                }
+                Chip8Instruction::SetIRegister(addr) => {
+                    disassembly.add_label(addr, LabelKind::Data)
+                }
+                _ => {}
+            }
+        }
+
+        disassembly
+    }
+
+    /// Generated name of the label at `addr`, None if nothing refers to it
+    pub fn label(&self, addr: u16) -> Option<String> {
+        self.labels.get(&addr).map(|kind| kind.name(addr))
+    }
+
+    /// Reachable instructions in address order
+    pub fn instructions(&self) -> impl Iterator<Item = (u16, Chip8Instruction)> + '_ {
+        self.instructions
+            .iter()
+            .map(|(addr, instruction)| (*addr, *instruction))
+    }
+
+    /// Renders the whole ROM, reachable code as mnemonics and everything else as data
+    pub fn listing(&self, style: Style) -> String {
+        self.annotated_listing(style, 0, |_| None)
+    }
+
+    /// Renders the listing with a `width` wide column in front of every line,
+    /// holding the annotation of the instruction at that address
+    pub fn annotated_listing(
+        &self,
+        style: Style,
+        width: usize,
+        annotation: impl Fn(u16) -> Option<String>,
+    ) -> String {
+        let mut lines = vec![];
+        let end = ENTRY_POINT as usize + self.rom.len();
+        let mut addr = ENTRY_POINT as usize;
+
+        // A target in the middle of an instruction has no line to put a label
+        // on, it is defined after the entry label as Octo needs `main` first
+        let mut constants: Vec<String> = self
+            .labels
+            .iter()
+            .filter(|(target, _)| self.instructions.contains_key(&(*target - 1)))
+            .map(|(target, kind)| {
+                let constant = style.constant(&kind.name(*target), *target);
+                format!("{:width$}{}", "", constant)
+            })
+            .collect();
+
+        while addr < end {
+            if let Some(label) = self.label(addr as u16) {
+                lines.push(format!("{:width$}{}", "", style.label(&label)));
+            }
+            if addr == ENTRY_POINT as usize {
+                lines.append(&mut constants);
+            }
+
+            if let Some(instruction) = self.instructions.get(&(addr as u16)) {
+                lines.push(format!(
+                    "{:>width$}    {}",
+                    annotation(addr as u16).unwrap_or_default(),
+                    style.instruction(*instruction, |addr| self.label(addr))
+                ));
+                addr += 2;
+                continue;
+            }
+
+            // Data runs until the next instruction or label
+            let mut data_end = addr + 1;
+            while data_end < end
+                && data_end - addr < DATA_BYTES_PER_LINE
+                && !self.instructions.contains_key(&(data_end as u16))
+                && !self.labels.contains_key(&(data_end as u16))
+            {
+                data_end += 1;
+            }
+            let bytes = &self.rom[addr - ENTRY_POINT as usize..data_end - ENTRY_POINT as usize];
+            lines.push(format!("{:width$}    {}", "", style.data(bytes)));
+            addr = data_end;
+        }
+
+        let mut listing = lines.join("\n");
+        listing.push('\n');
+        listing
+    }
+
+    fn add_label(&mut self, addr: u16, kind: LabelKind) {
+        if !self.contains(addr) {
+            return;
+        }
+        let label = self.labels.entry(addr).or_insert(kind);
+        *label = (*label).min(kind);
+    }
+
+    fn contains(&self, addr: u16) -> bool {
+        (ENTRY_POINT as usize..ENTRY_POINT as usize + self.rom.len()).contains(&(addr as usize))
+    }
+
+    fn decode(&self, addr: u16) -> Option<Chip8Instruction> {
+        let offset = addr.checked_sub(ENTRY_POINT)? as usize;
+        let code = u16::from_be_bytes([*self.rom.get(offset)?, *self.rom.get(offset + 1)?]);
+        Chip8Instruction::decode_exact(code)
+    }
+}
+
+/// Addresses execution can continue at after the instruction at `addr`
+pub fn successors(addr: u16, instruction: Chip8Instruction) -> Vec<u16> {
+    match instruction {
+        Chip8Instruction::Jump(target) => vec![target.into()],
+        Chip8Instruction::Call(target) => vec![target.into(), addr + 2],
+        Chip8Instruction::Return() => vec![],
+        Chip8Instruction::SkipIfEqual(..)
+        | Chip8Instruction::SkipIfNotEqual(..)
+        | Chip8Instruction::SkipIfEqualXY(..)
+        | Chip8Instruction::SkipIfNotEqualXY(..) => vec![addr + 2, addr + 4],
+        _ => vec![addr + 2],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    /// Calls a subroutine, skips over an instruction and loops, followed by a sprite
+    static ROM: [u8; 19] = [
+        0x22, 0x0a, // 0x200: call 0x20A
+        0x30, 0x01, // 0x202: skip if v0 == 1
+        0x70, 0x01, // 0x204: v0 += 1
+        0x12, 0x02, // 0x206: jump to 0x202
+        0xff, 0xff, // 0x208: unreachable
+        0xa2, 0x10, // 0x20A: i = 0x210
+        0xd0, 0x13, // 0x20C: draw 3 rows
+        0x00, 0xee, // 0x20E: return
+        0x80, 0x40, 0xe0, // 0x210: sprite
+    ];
+
+    #[rstest]
+    fn test_follows_control_flow() {
+        let disassembly = Disassembly::new(&ROM);
+
+        assert_eq!(
+            disassembly.instructions.keys().copied().collect::<Vec<_>>(),
+            vec![0x200, 0x202, 0x204, 0x206, 0x20a, 0x20c, 0x20e]
+        );
+    }
+
+    #[rstest]
+    #[case::entry(0x200, Some("main"))]
+    #[case::subroutine(0x20a, Some("sub_20a"))]
+    #[case::jump(0x202, Some("label_202"))]
+    #[case::data(0x210, Some("data_210"))]
+    #[case::unreferenced(0x204, None)]
+    fn test_labels(#[case] addr: u16, #[case] label: Option<&str>) {
+        assert_eq!(Disassembly::new(&ROM).label(addr).as_deref(), label);
+    }
+
+    #[rstest]
+    fn test_targets_outside_rom_have_no_label() {
+        let rom = [0xa0, 0x50, 0x13, 0x00]; // i = 0x050, jump to 0x300
+        let disassembly = Disassembly::new(&rom);
+
+        assert_eq!(disassembly.label(0x050), None);
+        assert_eq!(disassembly.label(0x300), None);
+    }
+
+    #[rstest]
+    fn test_octo_listing() {
+        assert_eq!(
+            Disassembly::new(&ROM).listing(Style::Octo),
+            "\
+: main
+    sub_20a
+: label_202
+    if v0 != 0x01 then
+    v0 += 0x01
+    jump label_202
+    0xff 0xff
+: sub_20a
+    i := data_210
+    sprite v0 v1 3
+    return
+: data_210
+    0x80 0x40 0xe0
+"
+        );
+    }
+
+    /// Points i at the second byte of its own instruction
+    static INNER_TARGET: [u8; 6] = [
+        0x60, 0x01, // 0x200: v0 = 1
+        0xa2, 0x01, // 0x202: i = 0x201
+        0x12, 0x04, // 0x204: jump to 0x204
+    ];
+
+    #[rstest]
+    fn test_inner_target_is_a_constant() {
+        assert_eq!(
+            Disassembly::new(&INNER_TARGET).listing(Style::Octo),
+            "\
+: main
+:const data_201 0x201
+    v0 := 0x01
+    i := data_201
+: label_204
+    jump label_204
+"
+        );
+    }
+
+    #[rstest]
+    #[case::octo(Style::Octo)]
+    #[case::cowgod(Style::Cowgod)]
+    fn test_listing_assembles_back(#[case] style: Style) {
+        for rom in [&ROM[..], &INNER_TARGET] {
+            let listing = Disassembly::new(rom).listing(style);
+            let assembled = match style {
+                Style::Octo => crate::asm::octo::compile(&listing),
+                Style::Cowgod => crate::asm::assemble(&listing).map(|assembly| assembly.rom),
+            };
+            assert_eq!(assembled.as_deref(), Ok(rom), "{}", listing);
+        }
+    }
+
+    #[rstest]
+    fn test_cowgod_listing() {
+        assert_eq!(
+            Disassembly::new(&ROM).listing(Style::Cowgod),
+            "\
+main:
+    CALL sub_20a
+label_202:
+    SE V0, #01
+    ADD V0, #01
+    JP label_202
+    DB #ff, #ff
+sub_20a:
+    LD I, data_210
+    DRW V0, V1, 3
+    RET
+data_210:
+    DB #80, #40, #e0
+"
+        );
+    }
+}
@@ -0,0 +1,127 @@
+use crate::chip8::instruction::Chip8Instruction;
+
+/// Assembly syntax of a listing
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Style {
+    /// Syntax of the Octo assembler
+    Octo,
+    /// Mnemonics of Cowgod's Chip-8 technical reference
+    Cowgod,
+}
+
+impl Style {
+    pub fn label(&self, name: &str) -> String {
+        match self {
+            Style::Octo => format!(": {}", name),
+            Style::Cowgod => format!("{}:", name),
+        }
+    }
+
+    /// Defines `name` as `addr`, for labels with no line of their own
+    pub fn constant(&self, name: &str, addr: u16) -> String {
+        match self {
+            Style::Octo => format!(":const {} 0x{:03x}", name, addr),
+            Style::Cowgod => format!("{} EQU #{:03x}", name, addr),
+        }
+    }
+
+    pub fn data(&self, bytes: &[u8]) -> String {
+        match self {
+            Style::Octo => bytes
+                .iter()
+                .map(|byte| format!("0x{:02x}", byte))
+                .collect::<Vec<_>>()
+                .join(" "),
+            Style::Cowgod => format!(
+                "DB {}",
+                bytes
+                    .iter()
+                    .map(|byte| format!("#{:02x}", byte))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        }
+    }
+
+    /// Renders `instruction`, naming addresses with `label` where it has a name
+    pub fn instruction(
+        &self,
+        instruction: Chip8Instruction,
+        label: impl Fn(u16) -> Option<String>,
+    ) -> String {
+        let addr = |addr: u16| {
+            label(addr).unwrap_or_else(|| match self {
+                Style::Octo => format!("0x{:03x}", addr),
+                Style::Cowgod => format!("#{:03x}", addr),
+            })
+        };
+
+        match self {
+            Style::Octo => octo(instruction, addr),
+            Style::Cowgod => cowgod(instruction, addr),
+        }
+    }
+}
+
+fn octo(instruction: Chip8Instruction, addr: impl Fn(u16) -> String) -> String {
+    // Octo's conditionals skip the next instruction when they are false
+    match instruction {
+        Chip8Instruction::ClearScreen() => "clear".to_string(),
+        Chip8Instruction::Return() => "return".to_string(),
+        Chip8Instruction::Jump(nnn) => format!("jump {}", addr(nnn.into())),
+        Chip8Instruction::Call(nnn) => addr(nnn.into()),
+        Chip8Instruction::SkipIfEqual(x, nn) => format!("if v{:x} != 0x{:02x} then", x, nn),
+        Chip8Instruction::SkipIfNotEqual(x, nn) => format!("if v{:x} == 0x{:02x} then", x, nn),
+        Chip8Instruction::SkipIfEqualXY(x, y) => format!("if v{:x} != v{:x} then", x, y),
+        Chip8Instruction::SkipIfNotEqualXY(x, y) => format!("if v{:x} == v{:x} then", x, y),
+        Chip8Instruction::SetVX(x, nn) => format!("v{:x} := 0x{:02x}", x, nn),
+        Chip8Instruction::AddVX(x, nn) => format!("v{:x} += 0x{:02x}", x, nn),
+        Chip8Instruction::SetVXToVY(x, y) => format!("v{:x} := v{:x}", x, y),
+        Chip8Instruction::OrVXVY(x, y) => format!("v{:x} |= v{:x}", x, y),
+        Chip8Instruction::AndVXVY(x, y) => format!("v{:x} &= v{:x}", x, y),
+        Chip8Instruction::XorVXVY(x, y) => format!("v{:x} ^= v{:x}", x, y),
+        Chip8Instruction::AddVYRegisterToVX(x, y) => format!("v{:x} += v{:x}", x, y),
+        Chip8Instruction::SubVYFromVX(x, y) => format!("v{:x} -= v{:x}", x, y),
+        Chip8Instruction::ShiftVXRight(x, y) => format!("v{:x} >>= v{:x}", x, y),
+        Chip8Instruction::SubVXFromVY(x, y) => format!("v{:x} =- v{:x}", x, y),
+        Chip8Instruction::ShiftVXLeft(x, y) => format!("v{:x} <<= v{:x}", x, y),
+        Chip8Instruction::SetIRegister(nnn) => format!("i := {}", addr(nnn)),
+        Chip8Instruction::Draw(x, y, n) => format!("sprite v{:x} v{:x} {}", x, y, n),
+        Chip8Instruction::LoadAudioPattern() => "audio".to_string(),
+        Chip8Instruction::SetPitch(x) => format!("pitch := v{:x}", x),
+        Chip8Instruction::SetSoundTimer(x) => format!("buzzer := v{:x}", x),
+        Chip8Instruction::StoreBCD(x) => format!("bcd v{:x}", x),
+        Chip8Instruction::StoreRegisters(x) => format!("save v{:x}", x),
+    }
+}
+
+fn cowgod(instruction: Chip8Instruction, addr: impl Fn(u16) -> String) -> String {
+    match instruction {
+        Chip8Instruction::ClearScreen() => "CLS".to_string(),
+        Chip8Instruction::Return() => "RET".to_string(),
+        Chip8Instruction::Jump(nnn) => format!("JP {}", addr(nnn.into())),
+        Chip8Instruction::Call(nnn) => format!("CALL {}", addr(nnn.into())),
+        Chip8Instruction::SkipIfEqual(x, nn) => format!("SE V{:X}, #{:02x}", x, nn),
+        Chip8Instruction::SkipIfNotEqual(x, nn) => format!("SNE V{:X}, #{:02x}", x, nn),
+        Chip8Instruction::SkipIfEqualXY(x, y) => format!("SE V{:X}, V{:X}", x, y),
+        Chip8Instruction::SkipIfNotEqualXY(x, y) => format!("SNE V{:X}, V{:X}", x, y),
+        Chip8Instruction::SetVX(x, nn) => format!("LD V{:X}, #{:02x}", x, nn),
+        Chip8Instruction::AddVX(x, nn) => format!("ADD V{:X}, #{:02x}", x, nn),
+        Chip8Instruction::SetVXToVY(x, y) => format!("LD V{:X}, V{:X}", x, y),
+        Chip8Instruction::OrVXVY(x, y) => format!("OR V{:X}, V{:X}", x, y),
+        Chip8Instruction::AndVXVY(x, y) => format!("AND V{:X}, V{:X}", x, y),
+        Chip8Instruction::XorVXVY(x, y) => format!("XOR V{:X}, V{:X}", x, y),
+        Chip8Instruction::AddVYRegisterToVX(x, y) => format!("ADD V{:X}, V{:X}", x, y),
+        Chip8Instruction::SubVYFromVX(x, y) => format!("SUB V{:X}, V{:X}", x, y),
+        Chip8Instruction::ShiftVXRight(x, y) => format!("SHR V{:X}, V{:X}", x, y),
+        Chip8Instruction::SubVXFromVY(x, y) => format!("SUBN V{:X}, V{:X}", x, y),
+        Chip8Instruction::ShiftVXLeft(x, y) => format!("SHL V{:X}, V{:X}", x, y),
+        Chip8Instruction::SetIRegister(nnn) => format!("LD I, {}", addr(nnn)),
+        Chip8Instruction::Draw(x, y, n) => format!("DRW V{:X}, V{:X}, {}", x, y, n),
+        Chip8Instruction::LoadAudioPattern() => "AUDIO".to_string(),
+        Chip8Instruction::SetPitch(x) => format!("PITCH V{:X}", x),
+        Chip8Instruction::SetSoundTimer(x) => format!("LD ST, V{:X}", x),
+        Chip8Instruction::StoreBCD(x) => format!("LD B, V{:X}", x),
+        Chip8Instruction::StoreRegisters(x) => format!("LD [I], V{:X}", x),
+    }
+}
@@ -0,0 +1,314 @@
+use std::ops::{BitAnd, BitXorAssign, Shl, Shr};
+
+/// Monochrome framebuffer stored as one integer per row.
+///
+/// The leftmost pixel of a row is its most significant bit, so a sprite byte
+/// lines up with a row by shifting it into the top bits and right by `x`.
+///
+/// Rows changed since the last [`Framebuffer::clear_dirty`] are tracked so
+/// backends only need to present what was actually drawn.
+#[derive(Clone, Debug)]
+pub struct Framebuffer {
+    width: usize,
+    height: usize,
+    rows: Rows,
+
+    /// Bit `y` is set when row `y` changed since the last present
+    dirty_rows: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Rows {
+    /// 64 pixel wide rows
+    Lores(Vec<u64>),
+    /// 128 pixel wide rows
+    Hires(Vec<u128>),
+}
+
+impl Framebuffer {
+    /// Creates a blank framebuffer with every row marked dirty.
+    ///
+    /// `width` must be 64 or 128 pixels and `height` at most 64 rows.
+    pub fn new(width: usize, height: usize) -> Self {
+        assert!(
+            height <= u64::BITS as usize,
+            "Unsupported framebuffer height: {}",
+            height
+        );
+
+        let rows = match width {
+            64 => Rows::Lores(vec![0; height]),
+            128 => Rows::Hires(vec![0; height]),
+            _ => panic!("Unsupported framebuffer width: {}", width),
+        };
+
+        Framebuffer {
+            width,
+            height,
+            rows,
+            dirty_rows: Self::all_rows(height),
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        self.row(y) & (1 << (self.width - 1 - x)) != 0
+    }
+
+    /// Returns row `y` with the leftmost pixel in bit `width - 1`
+    pub fn row(&self, y: usize) -> u128 {
+        match &self.rows {
+            Rows::Lores(rows) => rows[y] as u128,
+            Rows::Hires(rows) => rows[y],
+        }
+    }
+
+    /// Replaces row `y`, the leftmost pixel being bit `width - 1` of `bits`
+    pub fn set_row(&mut self, y: usize, bits: u128) {
+        if self.row(y) == bits {
+            return;
+        }
+
+        match &mut self.rows {
+            Rows::Lores(rows) => rows[y] = bits as u64,
+            Rows::Hires(rows) => rows[y] = bits,
+        }
+        self.dirty_rows |= 1 << y;
+    }
+
+    pub fn clear(&mut self) {
+        self.dirty_rows |= match &mut self.rows {
+            Rows::Lores(rows) => clear(rows),
+            Rows::Hires(rows) => clear(rows),
+        };
+    }
+
+    /// Returns true if any row changed since the last present
+    pub fn is_dirty(&self) -> bool {
+        self.dirty_rows != 0
+    }
+
+    /// Returns a mask with bit `y` set for every row changed since the last present
+    pub fn dirty_rows(&self) -> u64 {
+        self.dirty_rows
+    }
+
+    /// Marks the rows set in `rows` as changed, for filters whose output
+    /// changes without any pixel changing
+    pub fn mark_dirty(&mut self, rows: u64) {
+        self.dirty_rows |= rows & Self::all_rows(self.height);
+    }
+
+    /// Marks the framebuffer as presented
+    pub fn clear_dirty(&mut self) {
+        self.dirty_rows = 0;
+    }
+
+    fn all_rows(height: usize) -> u64 {
+        u64::MAX.checked_shr(u64::BITS - height as u32).unwrap_or(0)
+    }
+
+    /// XORs `sprite` onto the framebuffer, one byte per row.
+    ///
+    /// The starting coordinates wrap around the screen, the sprite itself is
+    /// clipped at the right and bottom edges.
+    /// Returns true if any pixel was turned off.
+    pub fn draw_sprite(&mut self, x: usize, y: usize, sprite: &[u8]) -> bool {
+        let x = x % self.width;
+        let y = y % self.height;
+
+        let (collision, dirty_rows) = match &mut self.rows {
+            Rows::Lores(rows) => blit(rows, x, y, sprite),
+            Rows::Hires(rows) => blit(rows, x, y, sprite),
+        };
+        self.dirty_rows |= dirty_rows;
+
+        collision
+    }
+}
+
+/// Two framebuffers are equal when they hold the same image, regardless of what is dirty
+impl PartialEq for Framebuffer {
+    fn eq(&self, other: &Self) -> bool {
+        self.width == other.width && self.height == other.height && self.rows == other.rows
+    }
+}
+
+impl Eq for Framebuffer {}
+
+/// Integer type holding a single framebuffer row
+trait Row:
+    Copy
+    + Eq
+    + BitAnd<Output = Self>
+    + BitXorAssign
+    + Shl<usize, Output = Self>
+    + Shr<usize, Output = Self>
+    + From<u8>
+{
+    const BITS: usize;
+    const ZERO: Self;
+}
+
+impl Row for u64 {
+    const BITS: usize = 64;
+    const ZERO: Self = 0;
+}
+
+impl Row for u128 {
+    const BITS: usize = 128;
+    const ZERO: Self = 0;
+}
+
+/// Returns the collision flag and the mask of rows that changed
+#[inline]
+fn blit<R: Row>(rows: &mut [R], x: usize, y: usize, sprite: &[u8]) -> (bool, u64) {
+    let mut collision = false;
+    let mut dirty_rows = 0;
+
+    for (i, (row, &byte)) in rows[y..].iter_mut().zip(sprite).enumerate() {
+        // Bits shifted past the right edge are dropped, which clips the sprite
+        let sprite_row = (R::from(byte) << (R::BITS - 8)) >> x;
+        collision |= *row & sprite_row != R::ZERO;
+        *row ^= sprite_row;
+
+        if sprite_row != R::ZERO {
+            dirty_rows |= 1 << (y + i);
+        }
+    }
+
+    (collision, dirty_rows)
+}
+
+/// Zeroes every row and returns the mask of rows that had pixels set
+#[inline]
+fn clear<R: Row>(rows: &mut [R]) -> u64 {
+    let mut dirty_rows = 0;
+
+    for (y, row) in rows.iter_mut().enumerate() {
+        if *row != R::ZERO {
+            dirty_rows |= 1 << y;
+            *row = R::ZERO;
+        }
+    }
+
+    dirty_rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    #[case::lores(64, 32)]
+    #[case::hires(128, 64)]
+    fn test_draw_sprite(#[case] width: usize, #[case] height: usize) {
+        let mut framebuffer = Framebuffer::new(width, height);
+
+        let collision = framebuffer.draw_sprite(2, 1, &[0b10100000, 0b01000000]);
+
+        assert!(!collision);
+        assert!(framebuffer.get(2, 1));
+        assert!(!framebuffer.get(3, 1));
+        assert!(framebuffer.get(4, 1));
+        assert!(framebuffer.get(3, 2));
+        assert!(!framebuffer.get(2, 2));
+    }
+
+    #[rstest]
+    #[case::lores(64, 32)]
+    #[case::hires(128, 64)]
+    fn test_draw_sprite_collision(#[case] width: usize, #[case] height: usize) {
+        let mut framebuffer = Framebuffer::new(width, height);
+        framebuffer.draw_sprite(0, 0, &[0b11000000]);
+
+        let collision = framebuffer.draw_sprite(1, 0, &[0b11000000]);
+
+        assert!(collision);
+        assert!(framebuffer.get(0, 0));
+        assert!(!framebuffer.get(1, 0));
+        assert!(framebuffer.get(2, 0));
+    }
+
+    #[rstest]
+    #[case::lores(64, 32)]
+    #[case::hires(128, 64)]
+    fn test_draw_sprite_clips_at_edges(#[case] width: usize, #[case] height: usize) {
+        let mut framebuffer = Framebuffer::new(width, height);
+
+        framebuffer.draw_sprite(width - 4, height - 1, &[0xff, 0xff]);
+
+        for x in width - 4..width {
+            assert!(framebuffer.get(x, height - 1));
+        }
+        // Nothing wraps around to the left edge or the top row
+        for x in 0..4 {
+            assert!(!framebuffer.get(x, height - 1));
+            assert!(!framebuffer.get(x, 0));
+        }
+        assert!(!framebuffer.get(width - 4, 0));
+    }
+
+    #[rstest]
+    #[case::lores(64, 32)]
+    #[case::hires(128, 64)]
+    fn test_draw_sprite_wraps_start_position(#[case] width: usize, #[case] height: usize) {
+        let mut framebuffer = Framebuffer::new(width, height);
+
+        framebuffer.draw_sprite(width + 3, height + 2, &[0b10000000]);
+
+        assert!(framebuffer.get(3, 2));
+    }
+
+    #[rstest]
+    fn test_new_framebuffer_is_dirty() {
+        let framebuffer = Framebuffer::new(64, 32);
+
+        assert_eq!(framebuffer.dirty_rows(), 0xffff_ffff);
+    }
+
+    #[rstest]
+    #[case::lores(64, 32)]
+    #[case::hires(128, 64)]
+    fn test_draw_sprite_marks_rows_dirty(#[case] width: usize, #[case] height: usize) {
+        let mut framebuffer = Framebuffer::new(width, height);
+        framebuffer.clear_dirty();
+
+        // The empty middle row of the sprite doesn't change anything
+        framebuffer.draw_sprite(0, 4, &[0xff, 0x00, 0xff]);
+
+        assert_eq!(framebuffer.dirty_rows(), 0b101 << 4);
+    }
+
+    #[rstest]
+    fn test_clear_marks_only_drawn_rows_dirty() {
+        let mut framebuffer = Framebuffer::new(64, 32);
+        framebuffer.draw_sprite(0, 3, &[0xff]);
+        framebuffer.clear_dirty();
+
+        framebuffer.clear();
+        assert_eq!(framebuffer.dirty_rows(), 1 << 3);
+
+        framebuffer.clear_dirty();
+        framebuffer.clear();
+        assert!(!framebuffer.is_dirty());
+    }
+
+    #[rstest]
+    fn test_clear() {
+        let mut framebuffer = Framebuffer::new(64, 32);
+        framebuffer.draw_sprite(10, 10, &[0xff; 8]);
+
+        framebuffer.clear();
+
+        assert_eq!(framebuffer, Framebuffer::new(64, 32));
+    }
+}
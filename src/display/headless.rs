@@ -0,0 +1,154 @@
+use crate::display::{
+    framebuffer::Framebuffer,
+    palette::Palette,
+    phosphor::Phosphor,
+    render::{ppm, Renderer},
+    terminal::render_frame,
+    Display,
+};
+
+/// Runs without any output, optionally closing after a number of frames.
+///
+/// When the frame limit is reached the final frame is printed as text, so batch
+/// runs can still see where a ROM ended up.
+pub struct HeadlessDisplay {
+    width: usize,
+    height: usize,
+
+    /// Number of frames after which the display reports itself closed
+    frame_limit: Option<u64>,
+    frames: u64,
+
+    /// Image of the final frame, rendered as a window would show it
+    snapshot: Option<Snapshot>,
+}
+
+/// PPM image of the final frame, written by [`HeadlessDisplay`]
+pub struct Snapshot {
+    pub path: String,
+    pub renderer: Renderer,
+    /// Window size to letterbox the image into, the image's own size when absent
+    pub window_size: Option<(usize, usize)>,
+}
+
+impl Snapshot {
+    fn write(&self) {
+        let ppm = match self.window_size {
+            Some((width, height)) => {
+                let mut image = vec![0; width * height];
+                self.renderer.letterbox_into(width, height, &mut image);
+                ppm(&image, width)
+            }
+            None => ppm(self.renderer.image(), self.renderer.size().0),
+        };
+        if let Err(err) = std::fs::write(&self.path, ppm) {
+            panic!("Failed to write snapshot {}: {}", self.path, err);
+        }
+    }
+}
+
+impl HeadlessDisplay {
+    pub fn new(width: usize, height: usize, frame_limit: Option<u64>) -> Self {
+        HeadlessDisplay {
+            width,
+            height,
+            frame_limit,
+            frames: 0,
+            snapshot: None,
+        }
+    }
+
+    /// Also writes the final frame as an image, needs a frame limit
+    pub fn with_snapshot(mut self, snapshot: Snapshot) -> Self {
+        self.snapshot = Some(snapshot);
+        self
+    }
+
+    /// Counts a frame, returning true if it is the final one
+    fn is_final_frame(&mut self) -> bool {
+        self.frames += 1;
+        Some(self.frames) == self.frame_limit
+    }
+}
+
+impl Display for HeadlessDisplay {
+    fn update(&mut self, buffer: &Framebuffer) {
+        if self.is_final_frame() {
+            print!("{}", render_frame(buffer));
+            if let Some(snapshot) = &mut self.snapshot {
+                snapshot.renderer.render(u64::MAX, buffer);
+                snapshot.write();
+            }
+        }
+    }
+
+    fn update_phosphor(&mut self, phosphor: &Phosphor) {
+        if self.is_final_frame() {
+            print!("{}", render_frame(phosphor.framebuffer()));
+            if let Some(snapshot) = &mut self.snapshot {
+                snapshot.renderer.render_phosphor(u64::MAX, phosphor);
+                snapshot.write();
+            }
+        }
+    }
+
+    fn get_size(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    fn is_open(&self) -> bool {
+        match self.frame_limit {
+            Some(limit) => self.frames < limit,
+            None => true,
+        }
+    }
+
+    fn set_palette(&mut self, palette: Palette) {
+        if let Some(snapshot) = &mut self.snapshot {
+            snapshot.renderer.set_palette(palette);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::{render::RenderConfig, scaler::Scaler};
+    use rstest::*;
+
+    #[rstest]
+    fn test_snapshot() {
+        let path = std::env::temp_dir().join(format!("chip8-snapshot-{}.ppm", std::process::id()));
+        let config = RenderConfig {
+            scaling_factor: 2,
+            scaler: Scaler::Scanlines,
+            grid: false,
+            palette: Palette([0x000000, 0xffffff, 0, 0]),
+            ..RenderConfig::default()
+        };
+        let mut display = HeadlessDisplay::new(64, 32, Some(1)).with_snapshot(Snapshot {
+            path: path.to_str().unwrap().to_string(),
+            renderer: Renderer::new(64, 32, config).unwrap(),
+            window_size: Some((128, 128)),
+        });
+
+        let mut buffer = Framebuffer::new(64, 32);
+        buffer.set_row(0, 1 << 63);
+        display.update(&buffer);
+
+        let snapshot = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let header = b"P6\n128 128\n255\n";
+        assert_eq!(&snapshot[..header.len()], header);
+        let pixel = |x: usize, y: usize| {
+            let offset = header.len() + (y * 128 + x) * 3;
+            &snapshot[offset..offset + 3]
+        };
+        // The 128 by 64 image sits between 32 line high bars
+        assert_eq!(pixel(0, 31), [0, 0, 0]);
+        assert_eq!(pixel(0, 32), [0xff, 0xff, 0xff]);
+        assert_eq!(pixel(1, 33), [0x7f, 0x7f, 0x7f]);
+        assert_eq!(pixel(2, 32), [0, 0, 0]);
+    }
+}
@@ -1,108 +1,134 @@
-use minifb::{Window, WindowOptions};
+use minifb::{Key, KeyRepeat, Window, WindowOptions};
+
+use crate::display::{
+    framebuffer::Framebuffer,
+    palette::Palette,
+    phosphor::Phosphor,
+    render::{RenderConfig, Renderer},
+    Hotkey,
+};
 
 static TITLE: &str = "Chip-8";
-static WIDTH: usize = 64;
-static HEIGHT: usize = 32;
-static SCALING_FACTOR: usize = 32;
 
-static PIXEL_ON: u32 = 0x00ff00;
-static PIXEL_OFF: u32 = 0x000000;
+/// Host key for every CHIP-8 key, laid out as the left side of a QWERTY keyboard:
+///
+///   1 2 3 4      1 2 3 C
+///   Q W E R  ->  4 5 6 D
+///   A S D F      7 8 9 E
+///   Z X C V      A 0 B F
+static KEYPAD: [Key; 16] = [
+    Key::X,
+    Key::Key1,
+    Key::Key2,
+    Key::Key3,
+    Key::Q,
+    Key::W,
+    Key::E,
+    Key::A,
+    Key::S,
+    Key::D,
+    Key::Z,
+    Key::C,
+    Key::Key4,
+    Key::R,
+    Key::F,
+    Key::V,
+];
+
+static HOTKEYS: [(Key, Hotkey); 3] = [
+    (Key::F5, Hotkey::SaveState),
+    (Key::F9, Hotkey::LoadState),
+    (Key::Backspace, Hotkey::Rewind),
+];
 
 pub struct MinifbDisplay {
     window: Window,
+    renderer: Renderer,
 
     width: usize,
     height: usize,
+
+    /// Window size the last frame was presented at, a new size redraws the frame
+    window_size: (usize, usize),
+    /// Image letterboxed into a resized window, reallocated only when the size changes
+    letterboxed: Vec<u32>,
 }
 
 impl MinifbDisplay {
-    fn get_scaled_buffer(&self, buffer: Vec<bool>) -> Vec<u32> {
-        let mut scaled_buffer: Vec<u32> =
-            vec![0; self.width * SCALING_FACTOR * self.height * SCALING_FACTOR];
-
-        for y in 0..self.height {
-            for x in 0..self.width {
-                let original_pixel = buffer[y * self.width + x];
-
-                // Scale each pixel to a SCALING_FACTOR x SCALING_FACTOR block
-                for dy in 0..SCALING_FACTOR {
-                    for dx in 0..SCALING_FACTOR {
-                        let scaled_x = x * SCALING_FACTOR + dx;
-                        let scaled_y = y * SCALING_FACTOR + dy;
-                        let scaled_index = scaled_y * (self.width * SCALING_FACTOR) + scaled_x;
-                        scaled_buffer[scaled_index] =
-                            if original_pixel { PIXEL_ON } else { PIXEL_OFF };
-                    }
-                }
-            }
-        }
+    pub fn new(width: usize, height: usize, config: RenderConfig) -> Result<Self, String> {
+        let renderer = Renderer::new(width, height, config)?;
+        let (window_width, window_height) = renderer.size();
 
-        scaled_buffer
+        let window = Window::new(
+            TITLE,
+            window_width,
+            window_height,
+            WindowOptions {
+                resize: true,
+                ..Default::default()
+            },
+        )
+        .map_err(|err| err.to_string())?;
+        window.topmost(true);
+
+        Ok(MinifbDisplay {
+            window,
+            renderer,
+            width,
+            height,
+            window_size: (window_width, window_height),
+            letterboxed: vec![],
+        })
     }
 
-    fn set_grid(&self, scaled_buffer: &mut Vec<u32>) {
-        let scaled_width = self.width * SCALING_FACTOR;
-        let scaled_height = self.height * SCALING_FACTOR;
-        let grid_color = 0x404040; // Dark gray color for grid lines
-
-        // Draw vertical grid lines
-        for x in (0..scaled_width).step_by(SCALING_FACTOR) {
-            for y in 0..scaled_height {
-                let index = y * scaled_width + x;
-                if index < scaled_buffer.len() {
-                    scaled_buffer[index] = grid_color;
-                }
-            }
+    /// Returns false when there is nothing new to show, after processing window events
+    fn needs_redraw(&mut self, buffer: &Framebuffer) -> bool {
+        if buffer.is_dirty() || self.window.get_size() != self.window_size {
+            return true;
         }
+        self.window.update();
+        false
+    }
 
-        // Draw horizontal grid lines
-        for y in (0..scaled_height).step_by(SCALING_FACTOR) {
-            for x in 0..scaled_width {
-                let index = y * scaled_width + x;
-                if index < scaled_buffer.len() {
-                    scaled_buffer[index] = grid_color;
-                }
-            }
+    /// Uploads the rendered image, letterboxed when the window was resized
+    fn present(&mut self) {
+        let (width, height) = self.window.get_size();
+        self.window_size = (width, height);
+
+        let result = if (width, height) == self.renderer.size() {
+            self.window
+                .update_with_buffer(self.renderer.image(), width, height)
+        } else if width == 0 || height == 0 {
+            // Minimised
+            self.window.update();
+            Ok(())
+        } else {
+            self.letterboxed.resize(width * height, 0);
+            self.renderer
+                .letterbox_into(width, height, &mut self.letterboxed);
+            self.window
+                .update_with_buffer(&self.letterboxed, width, height)
+        };
+
+        if let Err(err) = result {
+            panic!("{}", err);
         }
     }
 }
 
 impl crate::display::Display for MinifbDisplay {
-    fn new() -> Self {
-        let window_result = Window::new(
-            TITLE,
-            WIDTH * SCALING_FACTOR,
-            HEIGHT * SCALING_FACTOR,
-            WindowOptions {
-                ..Default::default()
-            },
-        );
-        match window_result {
-            Ok(window) => {
-                window.topmost(true);
-
-                MinifbDisplay {
-                    window: window,
-                    width: WIDTH,
-                    height: HEIGHT,
-                }
-            }
-            Err(err) => panic!("{}", err),
+    fn update(&mut self, buffer: &Framebuffer) {
+        if self.needs_redraw(buffer) {
+            self.renderer.render(buffer.dirty_rows(), buffer);
+            self.present();
         }
     }
 
-    fn update(&mut self, buffer: &mut Vec<bool>) {
-        let mut scaled_buffer = self.get_scaled_buffer(buffer.to_vec());
-        self.set_grid(&mut scaled_buffer);
-        let result = self.window.update_with_buffer(
-            &scaled_buffer,
-            self.width * SCALING_FACTOR,
-            self.height * SCALING_FACTOR,
-        );
-
-        match result {
-            Ok(_) => return,
-            Err(err) => panic!("{}", err),
+    fn update_phosphor(&mut self, phosphor: &Phosphor) {
+        let buffer = phosphor.framebuffer();
+        if self.needs_redraw(buffer) {
+            self.renderer.render_phosphor(buffer.dirty_rows(), phosphor);
+            self.present();
         }
     }
 
@@ -113,4 +139,30 @@ impl crate::display::Display for MinifbDisplay {
     fn is_open(&self) -> bool {
         self.window.is_open()
     }
+
+    fn pressed_hotkeys(&self) -> Vec<Hotkey> {
+        HOTKEYS
+            .iter()
+            .filter(|(key, _)| self.window.is_key_pressed(*key, KeyRepeat::No))
+            .map(|(_, hotkey)| *hotkey)
+            .collect()
+    }
+
+    fn is_hotkey_down(&self, hotkey: Hotkey) -> bool {
+        HOTKEYS
+            .iter()
+            .any(|(key, bound)| *bound == hotkey && self.window.is_key_down(*key))
+    }
+
+    fn keypad(&self) -> u16 {
+        KEYPAD
+            .iter()
+            .enumerate()
+            .filter(|(_, key)| self.window.is_key_down(**key))
+            .fold(0, |keypad, (chip8_key, _)| keypad | 1 << chip8_key)
+    }
+
+    fn set_palette(&mut self, palette: Palette) {
+        self.renderer.set_palette(palette);
+    }
 }
@@ -1,9 +1,102 @@
+pub mod framebuffer;
+pub mod headless;
 pub mod minifb;
+pub mod null;
+pub mod palette;
+pub mod phosphor;
+pub mod render;
+pub mod scaler;
+pub mod terminal;
+#[cfg(test)]
 pub mod test_display;
 
+use crate::display::{framebuffer::Framebuffer, palette::Palette, phosphor::Phosphor};
+
+/// Native CHIP-8 screen width in pixels
+pub const WIDTH: usize = 64;
+/// Native CHIP-8 screen height in pixels
+pub const HEIGHT: usize = 32;
+
+/// Emulator controls bound to host keys
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Hotkey {
+    SaveState,
+    LoadState,
+    /// Held to play recent frames back in reverse
+    Rewind,
+}
+
+/// Output backend for the emulator.
+///
+/// Backends are constructed by their own `new` functions, which take whatever
+/// configuration the backend needs, so the trait stays usable as `dyn Display`.
 pub trait Display {
-    fn new() -> Self;
-    fn update(&mut self, buffer: &mut Vec<bool>);
+    /// Called once per frame. Backends should only redraw when
+    /// [`Framebuffer::is_dirty`] is set, limited to [`Framebuffer::dirty_rows`]
+    /// where they can.
+    fn update(&mut self, buffer: &Framebuffer);
+    /// Called instead of [`Display::update`] while a persistence filter is on.
+    /// Backends drawing brightness levels can use [`Phosphor::level`], the rest
+    /// get every pixel lit in a remembered frame.
+    fn update_phosphor(&mut self, phosphor: &Phosphor) {
+        self.update(phosphor.framebuffer())
+    }
+
     fn get_size(&self) -> (usize, usize);
     fn is_open(&self) -> bool;
+
+    /// Hotkeys pressed since the previous frame
+    fn pressed_hotkeys(&self) -> Vec<Hotkey> {
+        Vec::new()
+    }
+
+    /// Returns true while `hotkey` is held down
+    fn is_hotkey_down(&self, _hotkey: Hotkey) -> bool {
+        false
+    }
+
+    /// State of the CHIP-8 keypad, bit n is set while key n is held
+    fn keypad(&self) -> u16 {
+        0
+    }
+
+    /// Replaces the colours pixels are drawn in, ignored by monochrome backends
+    fn set_palette(&mut self, _palette: Palette) {}
+}
+
+impl<D> Display for Box<D>
+where
+    D: Display + ?Sized,
+{
+    fn update(&mut self, buffer: &Framebuffer) {
+        (**self).update(buffer)
+    }
+
+    fn update_phosphor(&mut self, phosphor: &Phosphor) {
+        (**self).update_phosphor(phosphor)
+    }
+
+    fn get_size(&self) -> (usize, usize) {
+        (**self).get_size()
+    }
+
+    fn is_open(&self) -> bool {
+        (**self).is_open()
+    }
+
+    fn pressed_hotkeys(&self) -> Vec<Hotkey> {
+        (**self).pressed_hotkeys()
+    }
+
+    fn is_hotkey_down(&self, hotkey: Hotkey) -> bool {
+        (**self).is_hotkey_down(hotkey)
+    }
+
+    fn keypad(&self) -> u16 {
+        (**self).keypad()
+    }
+
+    fn set_palette(&mut self, palette: Palette) {
+        (**self).set_palette(palette)
+    }
 }
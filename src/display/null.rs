@@ -0,0 +1,25 @@
+use crate::display::{framebuffer::Framebuffer, Display};
+
+/// Discards every frame and never closes
+pub struct NullDisplay {
+    width: usize,
+    height: usize,
+}
+
+impl NullDisplay {
+    pub fn new(width: usize, height: usize) -> Self {
+        NullDisplay { width, height }
+    }
+}
+
+impl Display for NullDisplay {
+    fn update(&mut self, _buffer: &Framebuffer) {}
+
+    fn get_size(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    fn is_open(&self) -> bool {
+        true
+    }
+}
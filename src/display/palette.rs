@@ -0,0 +1,162 @@
+/// Colour of a pixel for every combination of lit planes, indexed by the planes'
+/// bits: unlit, plane 1 only, plane 2 only and both planes, as XO-CHIP draws them.
+///
+/// The framebuffer only has the first plane so far, which is drawn with the first two colours.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Palette(pub [u32; 4]);
+
+/// Named palettes, selectable with `--palette`
+static PRESETS: [(&str, Palette); 4] = [
+    // COSMAC VIP on a black and white television
+    ("vip", Palette([0x000000, 0xffffff, 0xaaaaaa, 0x555555])),
+    // HP 48 style reflective LCD
+    ("lcd", Palette([0x9bbc0f, 0x0f380f, 0x8bac0f, 0x306230])),
+    ("amber", Palette([0x1a0f00, 0xffb000, 0x995c00, 0xffd680])),
+    (
+        "high-contrast",
+        Palette([0x000000, 0xffffff, 0xffff00, 0x00ffff]),
+    ),
+];
+
+/// Keys of a palette file, named after Octo's colour options
+static KEYS: [&str; 4] = ["background", "fill", "fill2", "blend"];
+
+impl Default for Palette {
+    fn default() -> Self {
+        Palette([0x000000, 0x00ff00, 0x008000, 0xccffcc])
+    }
+}
+
+impl Palette {
+    pub fn preset(name: &str) -> Option<Palette> {
+        PRESETS
+            .iter()
+            .find(|(preset, _)| *preset == name)
+            .map(|(_, palette)| *palette)
+    }
+
+    /// Names of the presets, comma separated
+    pub fn preset_names() -> String {
+        PRESETS.map(|(name, _)| name).join(", ")
+    }
+
+    /// Colour of a pixel with `planes` lit, bit 0 being plane 1
+    pub fn color(&self, planes: usize) -> u32 {
+        self.0[planes & 0b11]
+    }
+
+    /// Colour between unlit and plane 1 at `level`, from 0.0 to 1.0, for fading pixels
+    pub fn blend(&self, level: f64) -> u32 {
+        let [off, on] = [self.0[0], self.0[1]];
+        [16, 8, 0].iter().fold(0, |color, shift| {
+            let (from, to) = ((off >> shift & 0xff) as f64, (on >> shift & 0xff) as f64);
+            color | ((from + (to - from) * level).round() as u32) << shift
+        })
+    }
+
+    /// Parses a palette file, one `key = value` per line.
+    ///
+    /// `preset` picks the palette to start from, `background`, `fill`, `fill2`
+    /// and `blend` set its colours as `#RRGGBB`. Lines starting with `#` are comments.
+    pub fn parse(text: &str) -> Result<Palette, String> {
+        let mut palette = Palette::default();
+        for (n, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = |message: String| format!("Line {}: {}", n + 1, message);
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(error(format!("Expected key = value, got {}", line)));
+            };
+
+            match (key.trim(), value.trim()) {
+                ("preset", name) => {
+                    palette = Palette::preset(name).ok_or_else(|| {
+                        error(format!(
+                            "Unknown preset: {}. Available presets: {}",
+                            name,
+                            Palette::preset_names()
+                        ))
+                    })?;
+                }
+                (key, color) => {
+                    let index = KEYS
+                        .iter()
+                        .position(|name| *name == key)
+                        .ok_or_else(|| error(format!("Unknown key: {}", key)))?;
+                    palette.0[index] = parse_color(color)
+                        .ok_or_else(|| error(format!("Invalid colour for {}: {}", key, color)))?;
+                }
+            }
+        }
+        Ok(palette)
+    }
+
+    pub fn load_file(path: &str) -> Result<Palette, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read palette {}: {}", path, e))?;
+        Palette::parse(&text).map_err(|e| format!("Invalid palette {}: {}", path, e))
+    }
+}
+
+/// Parses an `#RRGGBB` colour, as Octo writes them
+pub fn parse_color(color: &str) -> Option<u32> {
+    let hex = color.strip_prefix('#')?;
+    match u32::from_str_radix(hex, 16) {
+        Ok(rgb) if hex.len() == 6 => Some(rgb),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    #[case::vip("vip", Some(0xffffff))]
+    #[case::lcd("lcd", Some(0x0f380f))]
+    #[case::amber("amber", Some(0xffb000))]
+    #[case::high_contrast("high-contrast", Some(0xffffff))]
+    #[case::unknown("sepia", None)]
+    fn test_presets(#[case] name: &str, #[case] fill: Option<u32>) {
+        assert_eq!(Palette::preset(name).map(|palette| palette.color(1)), fill);
+    }
+
+    #[rstest]
+    #[case::unlit(0.0, 0x000080)]
+    #[case::lit(1.0, 0xffcc00)]
+    #[case::half(0.5, 0x806640)]
+    fn test_blend(#[case] level: f64, #[case] expected: u32) {
+        assert_eq!(Palette([0x000080, 0xffcc00, 0, 0]).blend(level), expected);
+    }
+
+    #[rstest]
+    fn test_parse() {
+        let palette = Palette::parse(
+            "# Amber with a brighter blend
+preset = amber
+
+blend = #FFFFFF
+background=#000000
+",
+        );
+        assert_eq!(
+            palette,
+            Ok(Palette([0x000000, 0xffb000, 0x995c00, 0xffffff]))
+        );
+    }
+
+    #[rstest]
+    #[case::no_value("fill", "Line 1: Expected key = value, got fill")]
+    #[case::unknown_key("fill3 = #000000", "Line 1: Unknown key: fill3")]
+    #[case::bad_color("\nfill = 00ff00", "Line 2: Invalid colour for fill: 00ff00")]
+    #[case::unknown_preset(
+        "preset = sepia",
+        "Line 1: Unknown preset: sepia. Available presets: vip, lcd, amber, high-contrast"
+    )]
+    fn test_parse_errors(#[case] text: &str, #[case] error: &str) {
+        assert_eq!(Palette::parse(text), Err(error.to_string()));
+    }
+}
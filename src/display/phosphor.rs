@@ -0,0 +1,233 @@
+use crate::display::framebuffer::Framebuffer;
+
+/// Frames remembered when a filter doesn't say
+const DEFAULT_FRAMES: usize = 3;
+
+/// How remembered frames are combined
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PhosphorMode {
+    /// Pixels dim step by step over the remembered frames after turning off
+    Fade,
+    /// Pixels stay fully lit while lit in any remembered frame
+    Or,
+}
+
+/// Persistence filter options, written `<fade|or>[:frames]`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PhosphorConfig {
+    pub mode: PhosphorMode,
+    /// Frames remembered, including the current one
+    pub frames: usize,
+}
+
+impl PhosphorConfig {
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let (mode, frames) = match spec.trim().split_once(':') {
+            Some((mode, frames)) => (mode, Some(frames)),
+            None => (spec.trim(), None),
+        };
+        let mode = match mode {
+            "fade" => PhosphorMode::Fade,
+            "or" => PhosphorMode::Or,
+            other => {
+                return Err(format!(
+                    "Invalid phosphor mode: {}. Available options: fade, or",
+                    other
+                ))
+            }
+        };
+        let frames = match frames.map(str::parse) {
+            Some(Ok(frames)) if frames >= 1 => frames,
+            Some(_) => return Err(format!("Invalid phosphor frame count in {}", spec)),
+            None => DEFAULT_FRAMES,
+        };
+        Ok(PhosphorConfig { mode, frames })
+    }
+}
+
+/// Simulates phosphor persistence to hide the flicker of sprites erased and
+/// redrawn with XOR.
+///
+/// Works on the framebuffer before any scaling, so every backend can use the
+/// combined [`Phosphor::framebuffer`] and those drawing brightness can use
+/// [`Phosphor::level`] as well.
+pub struct Phosphor {
+    config: PhosphorConfig,
+    /// Rows of the remembered frames, a ring reusing the oldest frame's rows
+    history: Vec<Vec<u128>>,
+    /// Index in `history` of the newest frame
+    newest: usize,
+    /// Frames remembered so far, up to `config.frames`
+    remembered: usize,
+    /// Pixels lit in any remembered frame
+    buffer: Framebuffer,
+}
+
+impl Phosphor {
+    pub fn new(config: PhosphorConfig) -> Self {
+        let buffer = Framebuffer::new(64, 32);
+        Phosphor {
+            config,
+            history: vec![vec![0; buffer.height()]; config.frames],
+            newest: 0,
+            remembered: 0,
+            buffer,
+        }
+    }
+
+    /// Adds the next frame, marking the rows whose filtered output changed dirty
+    pub fn apply(&mut self, frame: &Framebuffer) {
+        if (frame.width(), frame.height()) != (self.buffer.width(), self.buffer.height()) {
+            self.buffer = Framebuffer::new(frame.width(), frame.height());
+            self.history = vec![vec![0; frame.height()]; self.config.frames];
+            self.remembered = 0;
+        }
+
+        // The new frame takes the place of the oldest one once the ring is full
+        let full = self.remembered == self.config.frames;
+        self.newest = (self.newest + 1) % self.config.frames;
+        self.remembered = (self.remembered + 1).min(self.config.frames);
+
+        for y in 0..frame.height() {
+            let newest = frame.row(y);
+            let dropped = std::mem::replace(&mut self.history[self.newest][y], newest);
+            let lit = self.frames().fold(0, |lit, rows| lit | rows[y]);
+
+            // Levels also change while any remembered row differs from the newest
+            let fading = self.frames().any(|rows| rows[y] != newest) || (full && dropped != newest);
+
+            self.buffer.set_row(y, lit);
+            if self.config.mode == PhosphorMode::Fade && fading {
+                self.buffer.mark_dirty(1 << y);
+            }
+        }
+    }
+
+    /// Rows of the remembered frames, newest first
+    fn frames(&self) -> impl Iterator<Item = &Vec<u128>> {
+        let count = self.history.len();
+        (0..self.remembered).map(move |age| &self.history[(self.newest + count - age) % count])
+    }
+
+    /// Pixels lit in any remembered frame
+    pub fn framebuffer(&self) -> &Framebuffer {
+        &self.buffer
+    }
+
+    /// Marks the filtered output as presented
+    pub fn clear_dirty(&mut self) {
+        self.buffer.clear_dirty();
+    }
+
+    /// Brightness of a pixel, from 0.0 for unlit to 1.0 for fully lit
+    pub fn level(&self, x: usize, y: usize) -> f64 {
+        let bit = 1 << (self.buffer.width() - 1 - x);
+        let age = self.frames().position(|rows| rows[y] & bit != 0);
+        match (age, self.config.mode) {
+            (None, _) => 0.0,
+            (Some(_), PhosphorMode::Or) => 1.0,
+            (Some(age), PhosphorMode::Fade) => 1.0 - age as f64 / self.config.frames as f64,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    fn frame(lit: bool) -> Framebuffer {
+        let mut frame = Framebuffer::new(64, 32);
+        if lit {
+            frame.set_row(1, 1 << 63);
+        }
+        frame
+    }
+
+    /// Filters a pixel lit for one frame, returning its level over the following frames
+    fn levels(mode: PhosphorMode, frames: usize) -> Vec<f64> {
+        let mut phosphor = Phosphor::new(PhosphorConfig { mode, frames });
+        phosphor.apply(&frame(true));
+        (0..frames + 1)
+            .map(|_| {
+                let level = phosphor.level(0, 1);
+                phosphor.apply(&frame(false));
+                level
+            })
+            .collect()
+    }
+
+    #[rstest]
+    #[case::fade(PhosphorMode::Fade, vec![1.0, 0.75, 0.5, 0.25, 0.0])]
+    #[case::or(PhosphorMode::Or, vec![1.0, 1.0, 1.0, 1.0, 0.0])]
+    fn test_levels(#[case] mode: PhosphorMode, #[case] expected: Vec<f64>) {
+        assert_eq!(levels(mode, 4), expected);
+    }
+
+    #[rstest]
+    fn test_framebuffer_ors_remembered_frames() {
+        let mut phosphor = Phosphor::new(PhosphorConfig {
+            mode: PhosphorMode::Or,
+            frames: 2,
+        });
+        phosphor.apply(&frame(true));
+        phosphor.apply(&frame(false));
+        assert!(phosphor.framebuffer().get(0, 1));
+
+        phosphor.apply(&frame(false));
+        assert!(!phosphor.framebuffer().get(0, 1));
+    }
+
+    #[rstest]
+    fn test_history_reuses_its_rows() {
+        let mut phosphor = Phosphor::new(PhosphorConfig {
+            mode: PhosphorMode::Fade,
+            frames: 2,
+        });
+        let rows = |phosphor: &Phosphor| {
+            phosphor
+                .history
+                .iter()
+                .map(|rows| rows.as_ptr())
+                .collect::<Vec<_>>()
+        };
+        let before = rows(&phosphor);
+
+        for lit in [true, false, true, false, true] {
+            phosphor.apply(&frame(lit));
+        }
+
+        assert_eq!(rows(&phosphor), before);
+        assert_eq!(phosphor.level(0, 1), 1.0);
+    }
+
+    #[rstest]
+    #[case::fade(PhosphorMode::Fade, vec![0b10, 0b10, 0, 0])]
+    #[case::or(PhosphorMode::Or, vec![0, 0b10, 0, 0])]
+    fn test_dirty_rows(#[case] mode: PhosphorMode, #[case] expected: Vec<u64>) {
+        let mut phosphor = Phosphor::new(PhosphorConfig { mode, frames: 2 });
+        phosphor.apply(&frame(true));
+        phosphor.clear_dirty();
+
+        let dirty_rows = (0..4)
+            .map(|_| {
+                phosphor.apply(&frame(false));
+                let dirty_rows = phosphor.framebuffer().dirty_rows();
+                phosphor.clear_dirty();
+                dirty_rows
+            })
+            .collect::<Vec<_>>();
+
+        // Unchanged rows stay clean once the pixel has faded out
+        assert_eq!(dirty_rows, expected);
+    }
+
+    #[rstest]
+    #[case::fade("fade", Ok(PhosphorConfig { mode: PhosphorMode::Fade, frames: 3 }))]
+    #[case::or_frames("or:2", Ok(PhosphorConfig { mode: PhosphorMode::Or, frames: 2 }))]
+    #[case::bad_mode("glow", Err("Invalid phosphor mode: glow. Available options: fade, or".to_string()))]
+    #[case::bad_frames("fade:0", Err("Invalid phosphor frame count in fade:0".to_string()))]
+    fn test_parse(#[case] spec: &str, #[case] expected: Result<PhosphorConfig, String>) {
+        assert_eq!(PhosphorConfig::parse(spec), expected);
+    }
+}
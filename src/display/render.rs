@@ -0,0 +1,245 @@
+use crate::display::{
+    framebuffer::Framebuffer,
+    palette::Palette,
+    phosphor::Phosphor,
+    scaler::{Scaler, ScalerBuffers},
+};
+
+/// Rendering options for backends drawing scaled images
+#[derive(Clone, Debug)]
+pub struct RenderConfig {
+    /// Size of a CHIP-8 pixel in window pixels
+    pub scaling_factor: usize,
+    pub scaler: Scaler,
+    /// Draw a grid line along the top and left edge of every pixel
+    pub grid: bool,
+    pub grid_color: u32,
+    pub palette: Palette,
+}
+
+impl Default for RenderConfig {
+    fn default() -> Self {
+        RenderConfig {
+            scaling_factor: 32,
+            scaler: Scaler::Nearest,
+            grid: true,
+            grid_color: 0x404040,
+            palette: Palette::default(),
+        }
+    }
+}
+
+/// Renders frames to scaled `0xRRGGBB` images, the same for a window and a snapshot
+pub struct Renderer {
+    config: RenderConfig,
+
+    width: usize,
+    height: usize,
+
+    /// Colour of every CHIP-8 pixel
+    source: Vec<u32>,
+    /// Scaled image, reused between frames
+    image: Vec<u32>,
+    /// Scaler's working buffers, reused between frames
+    buffers: ScalerBuffers,
+}
+
+impl Renderer {
+    pub fn new(width: usize, height: usize, config: RenderConfig) -> Result<Self, String> {
+        if config.scaling_factor == 0 {
+            return Err("Scaling factor must be at least 1".to_string());
+        }
+
+        Ok(Renderer {
+            image: vec![0; width * height * config.scaling_factor.pow(2)],
+            buffers: ScalerBuffers::new(config.scaler, width, height, config.scaling_factor),
+            source: vec![0; width * height],
+            config,
+            width,
+            height,
+        })
+    }
+
+    /// Size of the scaled image
+    pub fn size(&self) -> (usize, usize) {
+        let scale = self.config.scaling_factor;
+        (self.width * scale, self.height * scale)
+    }
+
+    pub fn image(&self) -> &[u32] {
+        &self.image
+    }
+
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.config.palette = palette;
+    }
+
+    /// Redraws the rows set in `rows` of `buffer`
+    pub fn render(&mut self, rows: u64, buffer: &Framebuffer) {
+        let palette = self.config.palette;
+        self.render_rows(rows, |x, y| palette.color(buffer.get(x, y) as usize));
+    }
+
+    /// Redraws the rows set in `rows` of `phosphor`'s output, fading pixels
+    /// between the unlit and lit colours
+    pub fn render_phosphor(&mut self, rows: u64, phosphor: &Phosphor) {
+        let palette = self.config.palette;
+        self.render_rows(rows, |x, y| palette.blend(phosphor.level(x, y)));
+    }
+
+    fn render_rows(&mut self, rows: u64, color: impl Fn(usize, usize) -> u32) {
+        for (y, row) in self
+            .source
+            .chunks_exact_mut(self.width)
+            .enumerate()
+            .filter(|(y, _)| rows & (1 << y) != 0)
+        {
+            for (x, pixel) in row.iter_mut().enumerate() {
+                *pixel = color(x, y);
+            }
+        }
+
+        let scale = self.config.scaling_factor;
+        let rows = self.config.scaler.scale(
+            &self.source,
+            self.width,
+            scale,
+            rows,
+            &mut self.buffers,
+            &mut self.image,
+        );
+
+        if !self.config.grid {
+            return;
+        }
+        let line_len = self.width * scale;
+        for (_, block) in self
+            .image
+            .chunks_exact_mut(line_len * scale)
+            .enumerate()
+            .filter(|(y, _)| rows & (1 << y) != 0)
+        {
+            for line in block.chunks_exact_mut(line_len) {
+                line.iter_mut().step_by(scale).for_each(|pixel| {
+                    *pixel = self.config.grid_color;
+                });
+            }
+            block[..line_len].fill(self.config.grid_color);
+        }
+    }
+
+    /// Fills the `width` by `height` `output` with the image scaled to the largest
+    /// size fitting in it without changing its aspect ratio, centred between black bars
+    pub fn letterbox_into(&self, width: usize, height: usize, output: &mut [u32]) {
+        let (image_width, image_height) = self.size();
+        let (fit_width, fit_height) = if width * image_height <= height * image_width {
+            (width, image_height * width / image_width)
+        } else {
+            (image_width * height / image_height, height)
+        };
+        let (left, top) = ((width - fit_width) / 2, (height - fit_height) / 2);
+
+        for (y, line) in output.chunks_exact_mut(width).enumerate() {
+            if !(top..top + fit_height).contains(&y) {
+                line.fill(0);
+                continue;
+            }
+            let source_y = (y - top) * image_height / fit_height;
+            let (left_bar, rest) = line.split_at_mut(left);
+            let (fitted, right_bar) = rest.split_at_mut(fit_width);
+            left_bar.fill(0);
+            right_bar.fill(0);
+            for (x, pixel) in fitted.iter_mut().enumerate() {
+                *pixel = self.image[source_y * image_width + x * image_width / fit_width];
+            }
+        }
+    }
+}
+
+/// Encodes a `width` pixel wide image as a binary PPM file
+pub fn ppm(image: &[u32], width: usize) -> Vec<u8> {
+    let mut ppm = format!("P6\n{} {}\n255\n", width, image.len() / width).into_bytes();
+    for pixel in image {
+        ppm.extend_from_slice(&pixel.to_be_bytes()[1..]);
+    }
+    ppm
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    const ON: u32 = 0x00ff00;
+    const GRID: u32 = 0x404040;
+
+    /// 2 by 1 renderer scaling by 2, the left pixel lit
+    fn rendered(grid: bool) -> Renderer {
+        let mut renderer = Renderer::new(
+            2,
+            1,
+            RenderConfig {
+                scaling_factor: 2,
+                grid,
+                ..RenderConfig::default()
+            },
+        )
+        .unwrap();
+        renderer.render_rows(u64::MAX, |x, _| if x == 0 { ON } else { 0 });
+        renderer
+    }
+
+    #[rstest]
+    fn test_render() {
+        assert_eq!(rendered(false).image(), &[ON, ON, 0, 0, ON, ON, 0, 0]);
+    }
+
+    #[rstest]
+    fn test_grid() {
+        assert_eq!(
+            rendered(true).image(),
+            &[GRID, GRID, GRID, GRID, GRID, ON, GRID, 0]
+        );
+    }
+
+    #[rstest]
+    #[case::wide(8, 2, vec![
+        0, 0, ON, ON, 0, 0, 0, 0,
+        0, 0, ON, ON, 0, 0, 0, 0,
+    ])]
+    #[case::tall(2, 3, vec![
+        0, 0,
+        ON, 0,
+        0, 0,
+    ])]
+    #[case::exact(4, 2, vec![
+        ON, ON, 0, 0,
+        ON, ON, 0, 0,
+    ])]
+    fn test_letterbox(#[case] width: usize, #[case] height: usize, #[case] expected: Vec<u32>) {
+        // Bars are cleared, whatever the buffer held before
+        let mut output = vec![GRID; width * height];
+        rendered(false).letterbox_into(width, height, &mut output);
+        assert_eq!(output, expected);
+    }
+
+    #[rstest]
+    fn test_ppm() {
+        assert_eq!(
+            ppm(&[0x102030, 0x405060], 2),
+            b"P6\n2 1\n255\n\x10\x20\x30\x40\x50\x60".to_vec()
+        );
+    }
+
+    #[rstest]
+    fn test_zero_scaling_factor() {
+        let config = RenderConfig {
+            scaling_factor: 0,
+            ..RenderConfig::default()
+        };
+        assert_eq!(
+            Renderer::new(64, 32, config).err(),
+            Some("Scaling factor must be at least 1".to_string())
+        );
+    }
+}
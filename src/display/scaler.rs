@@ -0,0 +1,305 @@
+/// Upscaling filter turning every CHIP-8 pixel into a block of window pixels
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Scaler {
+    /// Plain blocks
+    Nearest,
+    /// Scale2x (EPX) applied for every factor of two in the scaling factor,
+    /// smoothing diagonal edges, the rest scaled with plain blocks
+    Epx,
+    /// Plain blocks with every other line at half brightness
+    Scanlines,
+    /// Scanlines seen through a red, green and blue aperture grille
+    Crt,
+}
+
+/// Buffers a scaler reuses between frames, sized for one image size and scaling factor
+pub struct ScalerBuffers {
+    /// Even and odd output line of the block being shaded
+    lines: [Vec<u32>; 2],
+    /// Image after every Scale2x pass, for EPX
+    epx: Vec<Vec<u32>>,
+}
+
+impl ScalerBuffers {
+    pub fn new(scaler: Scaler, width: usize, height: usize, factor: usize) -> Self {
+        let line_len = match scaler {
+            Scaler::Scanlines | Scaler::Crt => width * factor,
+            Scaler::Nearest | Scaler::Epx => 0,
+        };
+        let passes = match scaler {
+            Scaler::Epx => factor.trailing_zeros(),
+            _ => 0,
+        };
+
+        ScalerBuffers {
+            lines: [vec![0; line_len], vec![0; line_len]],
+            epx: (1..=passes)
+                .map(|pass| vec![0; (width * height) << (2 * pass)])
+                .collect(),
+        }
+    }
+}
+
+impl Scaler {
+    /// Scales the rows set in `rows` of the `width` pixel wide `source` image by
+    /// `factor` into `output`, which is `width * factor` pixels wide.
+    ///
+    /// Returns the rows redrawn. EPX also redraws the rows around them, as pixels
+    /// depend on their neighbours, one row further for every Scale2x pass.
+    pub fn scale(
+        &self,
+        source: &[u32],
+        width: usize,
+        factor: usize,
+        rows: u64,
+        buffers: &mut ScalerBuffers,
+        output: &mut [u32],
+    ) -> u64 {
+        let height = source.len() / width;
+        if *self != Scaler::Epx {
+            for (y, block) in output
+                .chunks_exact_mut(width * factor * factor)
+                .enumerate()
+                .filter(|(y, _)| rows & (1 << y) != 0)
+            {
+                let row = &source[y * width..(y + 1) * width];
+                self.scale_row(row, y, factor, &mut buffers.lines, block);
+            }
+            return rows;
+        }
+
+        let passes = buffers.epx.len();
+        let rows = (0..passes).fold(rows, |rows, _| rows | rows << 1 | rows >> 1);
+        let dirty = || (0..height).filter(move |y| rows & (1 << y) != 0);
+
+        for pass in 0..passes {
+            let (done, rest) = buffers.epx.split_at_mut(pass);
+            let image = done.last().map_or(source, Vec::as_slice);
+            // Every source row covers this many rows of the pass's input
+            let span = 1 << pass;
+            let input_rows = dirty().flat_map(|y| y * span..(y + 1) * span);
+            scale2x(image, width << pass, input_rows, &mut rest[0]);
+        }
+
+        // Scale the rest of the factor with plain blocks
+        let image = buffers.epx.last().map_or(source, Vec::as_slice);
+        let (image_width, span, factor) = (width << passes, 1 << passes, factor >> passes);
+        let block_len = image_width * factor * factor;
+        for y in dirty().flat_map(|y| y * span..(y + 1) * span) {
+            let row = &image[y * image_width..(y + 1) * image_width];
+            let block = &mut output[y * block_len..(y + 1) * block_len];
+            Scaler::Nearest.scale_row(row, y, factor, &mut buffers.lines, block);
+        }
+        rows
+    }
+
+    /// Scales source row `y` into its block of `factor` lines
+    fn scale_row(
+        &self,
+        row: &[u32],
+        y: usize,
+        factor: usize,
+        lines: &mut [Vec<u32>; 2],
+        block: &mut [u32],
+    ) {
+        let line_len = row.len() * factor;
+        if let Scaler::Nearest | Scaler::Epx = self {
+            let (first_line, other_lines) = block.split_at_mut(line_len);
+            for (pixels, color) in first_line.chunks_exact_mut(factor).zip(row) {
+                pixels.fill(*color);
+            }
+            for line in other_lines.chunks_exact_mut(line_len) {
+                line.copy_from_slice(first_line);
+            }
+            return;
+        }
+
+        // Lines only differ by whether they are an even or an odd line of the output
+        for (odd, line) in lines.iter_mut().enumerate() {
+            for (x, pixel) in line.iter_mut().enumerate() {
+                *pixel = self.shade(row[x / factor], x, odd == 1);
+            }
+        }
+        for (line_y, line) in block.chunks_exact_mut(line_len).enumerate() {
+            line.copy_from_slice(&lines[(y * factor + line_y) % 2]);
+        }
+    }
+
+    /// Colour of output pixel `x` on an even or odd output line showing `color`
+    fn shade(&self, color: u32, x: usize, odd_line: bool) -> u32 {
+        // Brightness of red, green and blue in quarters
+        let quarters = match self {
+            Scaler::Nearest | Scaler::Epx => return color,
+            Scaler::Scanlines if odd_line => [2; 3],
+            Scaler::Scanlines => return color,
+            Scaler::Crt => {
+                let grille = [0, 1, 2].map(|channel| if channel == x % 3 { 4 } else { 2 });
+                grille.map(|quarters| if odd_line { quarters / 2 } else { quarters })
+            }
+        };
+
+        [16, 8, 0]
+            .iter()
+            .zip(quarters)
+            .fold(0, |shaded, (shift, quarters)| {
+                shaded | ((color >> shift & 0xff) * quarters / 4) << shift
+            })
+    }
+}
+
+/// Doubles `input_rows` of the `width` pixel wide `image` into `output` with
+/// Scale2x, edges repeating the border pixels
+fn scale2x(
+    image: &[u32],
+    width: usize,
+    input_rows: impl Iterator<Item = usize>,
+    output: &mut [u32],
+) {
+    let height = image.len() / width;
+    let pixel = |x: usize, y: usize| image[y * width + x];
+
+    for y in input_rows {
+        for x in 0..width {
+            let p = pixel(x, y);
+            let above = pixel(x, y.saturating_sub(1));
+            let below = pixel(x, (y + 1).min(height - 1));
+            let left = pixel(x.saturating_sub(1), y);
+            let right = pixel((x + 1).min(width - 1), y);
+
+            // A corner takes the colour of both neighbours meeting at it,
+            // unless either opposite neighbour has that colour too
+            let corners = [
+                (left, above, below, right),
+                (above, right, left, below),
+                (left, below, above, right),
+                (below, right, left, above),
+            ]
+            .map(|(a, b, c, d)| if a == b && a != c && b != d { a } else { p });
+
+            for (corner, color) in corners.iter().enumerate() {
+                output[(2 * y + corner / 2) * 2 * width + 2 * x + corner % 2] = *color;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    const W: u32 = 0xffffff;
+    const B: u32 = 0x000000;
+
+    /// A diagonal from the top left to the bottom right
+    static DIAGONAL: [u32; 9] = [
+        W, B, B, //
+        B, W, B, //
+        B, B, W, //
+    ];
+
+    fn scaled(scaler: Scaler, source: &[u32], width: usize, factor: usize) -> Vec<u32> {
+        let height = source.len() / width;
+        let mut buffers = ScalerBuffers::new(scaler, width, height, factor);
+        let mut output = vec![0; source.len() * factor * factor];
+        scaler.scale(source, width, factor, u64::MAX, &mut buffers, &mut output);
+        output
+    }
+
+    #[rstest]
+    fn test_nearest() {
+        assert_eq!(
+            scaled(Scaler::Nearest, &[1, 2, 3, 4], 2, 2),
+            vec![
+                1, 1, 2, 2, //
+                1, 1, 2, 2, //
+                3, 3, 4, 4, //
+                3, 3, 4, 4, //
+            ]
+        );
+    }
+
+    #[rstest]
+    fn test_epx_smooths_diagonals() {
+        assert_eq!(
+            scaled(Scaler::Epx, &DIAGONAL, 3, 2),
+            vec![
+                W, W, B, B, B, B, //
+                W, B, W, B, B, B, //
+                B, W, W, W, B, B, //
+                B, B, W, W, W, B, //
+                B, B, B, W, B, W, //
+                B, B, B, B, W, W, //
+            ]
+        );
+    }
+
+    #[rstest]
+    fn test_epx_keeps_straight_edges() {
+        let source = [W, B, W, B];
+        assert_eq!(
+            scaled(Scaler::Epx, &source, 2, 4),
+            scaled(Scaler::Nearest, &source, 2, 4)
+        );
+    }
+
+    #[rstest]
+    fn test_epx_scales_odd_factors_with_blocks() {
+        assert_eq!(
+            scaled(Scaler::Epx, &DIAGONAL, 3, 3),
+            scaled(Scaler::Nearest, &DIAGONAL, 3, 3)
+        );
+        assert_eq!(
+            scaled(Scaler::Epx, &DIAGONAL, 3, 6),
+            scaled(Scaler::Nearest, &scaled(Scaler::Epx, &DIAGONAL, 3, 2), 6, 3)
+        );
+    }
+
+    #[rstest]
+    fn test_scanlines() {
+        assert_eq!(
+            scaled(Scaler::Scanlines, &[0x808080, 0xff0000], 1, 2),
+            vec![0x808080, 0x808080, 0x404040, 0x404040, 0xff0000, 0xff0000, 0x7f0000, 0x7f0000]
+        );
+    }
+
+    #[rstest]
+    fn test_crt() {
+        assert_eq!(
+            scaled(Scaler::Crt, &[W], 1, 3),
+            vec![
+                0xff7f7f, 0x7fff7f, 0x7f7fff, //
+                0x7f3f3f, 0x3f7f3f, 0x3f3f7f, //
+                0xff7f7f, 0x7fff7f, 0x7f7fff, //
+            ]
+        );
+    }
+
+    #[rstest]
+    fn test_only_scales_given_rows() {
+        let mut buffers = ScalerBuffers::new(Scaler::Nearest, 1, 2, 2);
+        let mut output = vec![0; 8];
+        let drawn = Scaler::Nearest.scale(&[1, 2], 1, 2, 0b10, &mut buffers, &mut output);
+        assert_eq!(drawn, 0b10);
+        assert_eq!(output, vec![0, 0, 0, 0, 2, 2, 2, 2]);
+    }
+
+    #[rstest]
+    #[case::one_pass(2, 0b1111 << 1)]
+    #[case::two_passes(4, 0b111111)]
+    fn test_epx_redraws_rows_next_to_dirty_rows(#[case] factor: usize, #[case] redrawn: u64) {
+        let mut source = [B; 8 * 8];
+        source[2 * 8 + 2] = W;
+        let mut buffers = ScalerBuffers::new(Scaler::Epx, 8, 8, factor);
+        let mut output = vec![0; source.len() * factor * factor];
+        Scaler::Epx.scale(&source, 8, factor, u64::MAX, &mut buffers, &mut output);
+
+        // Move the pixel down a row, only redrawing the two changed rows
+        source[2 * 8 + 2] = B;
+        source[3 * 8 + 2] = W;
+        let drawn = Scaler::Epx.scale(&source, 8, factor, 0b11 << 2, &mut buffers, &mut output);
+
+        assert_eq!(drawn, redrawn);
+        assert_eq!(output, scaled(Scaler::Epx, &source, 8, factor));
+    }
+}
@@ -0,0 +1,155 @@
+use std::io::Write;
+
+use crate::display::{framebuffer::Framebuffer, palette::Palette, Display};
+
+static PIXEL_UPPER: char = '▀';
+static PIXEL_LOWER: char = '▄';
+static PIXEL_FULL: char = '█';
+static PIXEL_EMPTY: char = ' ';
+
+/// Renders frames to the terminal, two pixel rows per text line
+pub struct TerminalDisplay {
+    width: usize,
+    height: usize,
+
+    /// Drawn in 24 bit colour once set, otherwise in the terminal's own colours
+    palette: Option<Palette>,
+}
+
+impl TerminalDisplay {
+    pub fn new(width: usize, height: usize) -> Self {
+        // Clear the screen once, every frame afterwards only moves the cursor home
+        print!("\x1b[2J");
+
+        TerminalDisplay {
+            width,
+            height,
+            palette: None,
+        }
+    }
+}
+
+impl Display for TerminalDisplay {
+    fn update(&mut self, buffer: &Framebuffer) {
+        if !buffer.is_dirty() {
+            return;
+        }
+
+        // Only rewrite the text lines covering a dirty row
+        let dirty_rows = buffer.dirty_rows();
+        let mut output = String::new();
+        for line in 0..buffer.height().div_ceil(2) {
+            if dirty_rows & (0b11 << (line * 2)) != 0 {
+                output.push_str(&format!("\x1b[{};1H", line + 1));
+                match &self.palette {
+                    Some(palette) => render_colored_line(buffer, line, palette, &mut output),
+                    None => render_line(buffer, line, &mut output),
+                }
+            }
+        }
+
+        let mut stdout = std::io::stdout().lock();
+        if let Err(err) = stdout
+            .write_all(output.as_bytes())
+            .and_then(|_| stdout.flush())
+        {
+            panic!("{}", err);
+        }
+    }
+
+    fn get_size(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    fn is_open(&self) -> bool {
+        true
+    }
+
+    fn set_palette(&mut self, palette: Palette) {
+        self.palette = Some(palette);
+    }
+}
+
+/// Renders a frame as text using half block characters, one line per two pixel rows
+pub(super) fn render_frame(buffer: &Framebuffer) -> String {
+    let lines = buffer.height().div_ceil(2);
+    let mut frame = String::with_capacity((buffer.width() + 1) * lines * 3);
+
+    for line in 0..lines {
+        render_line(buffer, line, &mut frame);
+        frame.push('\n');
+    }
+
+    frame
+}
+
+/// Renders pixel rows `2 * line` and `2 * line + 1` as a single line of text
+fn render_line(buffer: &Framebuffer, line: usize, output: &mut String) {
+    let y = line * 2;
+
+    for x in 0..buffer.width() {
+        let upper = buffer.get(x, y);
+        let lower = y + 1 < buffer.height() && buffer.get(x, y + 1);
+        output.push(match (upper, lower) {
+            (true, true) => PIXEL_FULL,
+            (true, false) => PIXEL_UPPER,
+            (false, true) => PIXEL_LOWER,
+            (false, false) => PIXEL_EMPTY,
+        });
+    }
+}
+
+/// Renders pixel rows `2 * line` and `2 * line + 1` as upper half blocks, coloured
+/// with the upper pixel's colour in front of the lower pixel's
+fn render_colored_line(buffer: &Framebuffer, line: usize, palette: &Palette, output: &mut String) {
+    let y = line * 2;
+    let mut previous = None;
+
+    for x in 0..buffer.width() {
+        let upper = palette.color(buffer.get(x, y) as usize);
+        let lower = palette.color((y + 1 < buffer.height() && buffer.get(x, y + 1)) as usize);
+        if previous != Some((upper, lower)) {
+            output.push_str(&format!(
+                "\x1b[38;2;{};{};{};48;2;{};{};{}m",
+                upper >> 16,
+                upper >> 8 & 0xff,
+                upper & 0xff,
+                lower >> 16,
+                lower >> 8 & 0xff,
+                lower & 0xff
+            ));
+            previous = Some((upper, lower));
+        }
+        output.push(PIXEL_UPPER);
+    }
+    output.push_str("\x1b[0m");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    fn test_render_colored_line() {
+        let mut buffer = Framebuffer::new(64, 2);
+        buffer.set_row(0, 0b11 << 62);
+        buffer.set_row(1, 0b01 << 62);
+
+        let mut output = String::new();
+        render_colored_line(
+            &buffer,
+            0,
+            &Palette([0x000080, 0xffcc00, 0, 0]),
+            &mut output,
+        );
+
+        assert_eq!(
+            output,
+            format!(
+                "\x1b[38;2;255;204;0;48;2;0;0;128m▀\x1b[38;2;255;204;0;48;2;255;204;0m▀\x1b[38;2;0;0;128;48;2;0;0;128m{}\x1b[0m",
+                "▀".repeat(62)
+            )
+        );
+    }
+}
@@ -1,13 +1,19 @@
-use crate::display::Display;
+use crate::display::{framebuffer::Framebuffer, palette::Palette, Display};
 
-pub struct TestDisplay {}
+#[derive(Default)]
+pub struct TestDisplay {
+    /// Palette, once set
+    pub palette: Option<Palette>,
+}
 
-impl Display for TestDisplay {
-    fn new() -> Self {
-        TestDisplay {}
+impl TestDisplay {
+    pub fn new() -> Self {
+        TestDisplay { palette: None }
     }
+}
 
-    fn update(&mut self, _buffer: &mut Vec<bool>) {
+impl Display for TestDisplay {
+    fn update(&mut self, _buffer: &Framebuffer) {
         print!("update")
     }
 
@@ -18,4 +24,8 @@ impl Display for TestDisplay {
     fn is_open(&self) -> bool {
         true
     }
+
+    fn set_palette(&mut self, palette: Palette) {
+        self.palette = Some(palette);
+    }
 }
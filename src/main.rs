@@ -1,56 +1,745 @@
 #[macro_use]
 extern crate twelve_bit;
 
-use crate::chip8::{compat::Compatibility, get_chip8_macos};
+use crate::{
+    audio::{
+        beeper::{Beeper, BeeperConfig, Waveform},
+        pattern::PatternPlayer,
+        wav::WavWriter,
+        Audio, Voice, SAMPLE_RATE,
+    },
+    chip8::{
+        compat::Compatibility,
+        coverage::Coverage,
+        movie::Movie,
+        trace::{
+            diff::{diff_files, diff_runs},
+            TraceFormat, Tracer,
+        },
+        Chip8, FRAME_RATE,
+    },
+    debugger::{gdb::GdbStub, Debugger},
+    disasm::{analysis::Analysis, Disassembly, Style, ENTRY_POINT},
+    display::{
+        headless::{HeadlessDisplay, Snapshot},
+        minifb::MinifbDisplay,
+        null::NullDisplay,
+        palette::Palette,
+        phosphor::PhosphorConfig,
+        render::{RenderConfig, Renderer},
+        scaler::Scaler,
+        terminal::TerminalDisplay,
+        Display, HEIGHT, WIDTH,
+    },
+};
 
+mod asm;
+mod audio;
 mod chip8;
+mod debugger;
+mod disasm;
 mod display;
 
+const DEFAULT_REWIND_SECONDS: usize = 30;
+
+/// Instructions compared by `trace-diff --rom` before giving up
+const DEFAULT_DIFF_STEPS: u64 = 100_000;
+
+/// Trace lines shown before and after a difference
+const DEFAULT_DIFF_CONTEXT: usize = 3;
+
 fn main() {
     let args: Vec<String> = std::env::args().collect();
+    match args.get(1).map(String::as_str) {
+        Some("analyze") => return analyze(&args),
+        Some("asm") => return asm(&args),
+        Some("coverage") => return coverage(&args),
+        Some("disasm") => return disasm(&args),
+        Some("trace-diff") => return trace_diff(&args),
+        _ => {}
+    }
     validate_args(&args);
 
     let compatibility = get_compatibility(&args);
     let rom_path = get_rom_path(&args);
 
-    println!("compatibility: {}", compatibility);
+    let coverage_path = get_option(&args, "--coverage", "");
+
+    if let Some(movie_path) = get_option(&args, "--replay", "") {
+        replay(&args, rom_path, movie_path, coverage_path);
+        return;
+    }
+
+    let load_state_path = get_option(&args, "--load-state", "");
+    let record_path = get_option(&args, "--record", "");
+    if load_state_path.is_some() && record_path.is_some() {
+        eprintln!("Movies are recorded from power on and can't start from a save state");
+        std::process::exit(1);
+    }
+    let debug = args.iter().any(|arg| arg == "--debug");
+    let gdb_port = get_gdb_port(&args);
+    if (debug || gdb_port.is_some()) && record_path.is_some() {
+        eprintln!("Movies can't be recorded while debugging");
+        std::process::exit(1);
+    }
+
+    let display = get_display(&args);
+
+    println!(
+        "compatibility: {}",
+        compatibility.unwrap_or(Compatibility::Cosmac)
+    );
     println!("rom_path: {}", rom_path);
 
-    let mut chip8 = get_chip8_macos(compatibility);
-    chip8.load_rom(rom_path);
+    let mut chip8 = Chip8::new(display, compatibility.unwrap_or(Compatibility::Cosmac));
+    chip8.set_rewind_depth(get_rewind_seconds(&args) * FRAME_RATE as usize);
+    chip8.load_rom(rom_path).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+
+    // Options given on the command line take precedence over an Octo cartridge's
+    if let Some(compatibility) = compatibility {
+        chip8.set_compatibility(compatibility);
+    }
+    if let Some(cycles_per_frame) = get_cycles_per_frame(&args) {
+        chip8.set_cycles_per_frame(cycles_per_frame);
+    }
+    if let Some(palette) = get_palette(&args) {
+        chip8.set_palette(palette);
+    }
+    if let Some(phosphor) = get_phosphor(&args, rom_path) {
+        chip8.set_phosphor(phosphor);
+    }
+    chip8.set_state_path(&format!("{}.state", rom_path));
+
+    if let Some(trace_path) = get_option(&args, "--trace", "") {
+        chip8.set_tracer(get_tracer(&args, trace_path));
+    }
+    if args.iter().any(|arg| arg == "--profile") {
+        chip8.start_profiling();
+    }
+    if coverage_path.is_some() {
+        chip8.start_coverage();
+    }
+    if args.iter().any(|arg| arg == "--warn-self-modifying") {
+        chip8.track_code_writes();
+    }
+    if let Some(wav_path) = get_option(&args, "--wav", "") {
+        let output = get_wav_writer(wav_path);
+        chip8.set_audio(get_voice(&args, output.sample_rate()), output);
+    }
+
+    if let Some(state_path) = load_state_path {
+        if let Err(e) = chip8.load_state_file(state_path) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+    if record_path.is_some() {
+        chip8.start_recording();
+    }
+
+    if debug {
+        Debugger::new(chip8).run();
+        return;
+    }
+    if let Some(port) = gdb_port {
+        if let Err(e) = GdbStub::new(chip8).listen(port) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
     chip8.run();
+
+    if let Some(report) = chip8.profile_report() {
+        print!("{}", report);
+    }
+    if let (Some(coverage_path), Some(coverage)) = (coverage_path, chip8.take_coverage()) {
+        save_coverage(coverage, rom_path, coverage_path);
+    }
+
+    if let (Some(record_path), Some(movie)) = (record_path, chip8.take_recording()) {
+        match movie.save_file(record_path) {
+            Ok(()) => println!("Recorded {} frames to {}", movie.frame_count(), record_path),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+    }
 }
 
-fn validate_args(args: &[String]) {
-    if args.len() < 2 {
-        eprintln!(
-            "Usage: {} --compatibility, -c <cosmac|chip48> <rom_path>",
-            args[0]
-        );
+/// Replays a movie without a display as fast as possible and prints the final state hash
+fn replay(args: &[String], rom_path: &str, movie_path: &str, coverage_path: Option<&str>) {
+    let wav_path = get_option(args, "--wav", "");
+    let movie = Movie::load_file(movie_path).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+
+    let mut chip8 = Chip8::new(NullDisplay::new(WIDTH, HEIGHT), Compatibility::Cosmac);
+    chip8.load_rom(rom_path).unwrap_or_else(|e| {
+        eprintln!("{}", e);
         std::process::exit(1);
+    });
+    if let Some(wav_path) = wav_path {
+        let output = get_wav_writer(wav_path);
+        chip8.set_audio(get_voice(args, output.sample_rate()), output);
+    }
+    if coverage_path.is_some() {
+        chip8.start_coverage();
+    }
+
+    match chip8.replay(&movie) {
+        Ok(hash) => println!(
+            "Replayed {} frames, final state hash: {:016x}",
+            movie.frame_count(),
+            hash
+        ),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+
+    if let (Some(coverage_path), Some(coverage)) = (coverage_path, chip8.take_coverage()) {
+        save_coverage(coverage, rom_path, coverage_path);
     }
 }
 
-fn get_compatibility(args: &[String]) -> Compatibility {
-    for (i, arg) in args.iter().enumerate() {
-        if arg == "--compatibility" || arg == "-c" {
-            return match args[i + 1].as_str() {
-                "cosmac" => Compatibility::Cosmac,
-                "chip48" => Compatibility::Chip48,
-                _ => {
-                    eprintln!(
-                        "Invalid compatibility mode: {}. Available options: cosmac, chip48",
-                        args[i + 1]
-                    );
+/// Adds the coverage of a run to the tracefile at `coverage_path`, creating it if needed
+fn save_coverage(mut coverage: Coverage, rom_path: &str, coverage_path: &str) {
+    if let Ok(lcov) = std::fs::read_to_string(coverage_path) {
+        if let Err(e) = coverage.merge_lcov(&lcov) {
+            eprintln!("Failed to merge coverage {}: {}", coverage_path, e);
+            std::process::exit(1);
+        }
+    }
+    if let Err(e) = std::fs::write(coverage_path, coverage.lcov(rom_path)) {
+        eprintln!("Failed to write coverage {}: {}", coverage_path, e);
+        std::process::exit(1);
+    }
+}
+
+/// Assembles a source file into a ROM, optionally writing an address listing
+fn asm(args: &[String]) {
+    let (Some(source_path), Some(rom_path)) = (args.get(2), get_option(args, "--output", "-o"))
+    else {
+        usage(&args[0]);
+    };
+
+    let assembly = asm::assemble_file(source_path).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+
+    if let Err(e) = std::fs::write(rom_path, &assembly.rom) {
+        eprintln!("Failed to write ROM {}: {}", rom_path, e);
+        std::process::exit(1);
+    }
+    if let Some(listing_path) = get_option(args, "--listing", "-l") {
+        if let Err(e) = std::fs::write(listing_path, assembly.listing()) {
+            eprintln!("Failed to write listing {}: {}", listing_path, e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Merges LCOV tracefiles of a ROM and prints its annotated listing
+fn coverage(args: &[String]) {
+    let Some(rom_path) = args.get(2) else {
+        usage(&args[0]);
+    };
+    let lcov_paths = args[3..]
+        .iter()
+        .take_while(|arg| !arg.starts_with('-'))
+        .collect::<Vec<_>>();
+    if lcov_paths.is_empty() {
+        usage(&args[0]);
+    }
+
+    let mut coverage = Coverage::new(read_rom(rom_path));
+    for lcov_path in lcov_paths {
+        let merged = std::fs::read_to_string(lcov_path)
+            .map_err(|e| e.to_string())
+            .and_then(|lcov| coverage.merge_lcov(&lcov));
+        if let Err(e) = merged {
+            eprintln!("Failed to merge coverage {}: {}", lcov_path, e);
+            std::process::exit(1);
+        }
+    }
+
+    if let Some(output_path) = get_option(args, "--output", "-o") {
+        if let Err(e) = std::fs::write(output_path, coverage.lcov(rom_path)) {
+            eprintln!("Failed to write coverage {}: {}", output_path, e);
+            std::process::exit(1);
+        }
+    }
+    print!("{}", coverage.listing());
+}
+
+/// Prints the disassembly of a ROM in the requested syntax
+fn disasm(args: &[String]) {
+    let Some(rom_path) = args.get(2) else {
+        usage(&args[0]);
+    };
+    let style = match get_option(args, "--style", "-s") {
+        Some("octo") | None => Style::Octo,
+        Some("cowgod") => Style::Cowgod,
+        Some(other) => {
+            eprintln!("Invalid style: {}. Available options: octo, cowgod", other);
+            std::process::exit(1);
+        }
+    };
+
+    let rom = read_rom(rom_path);
+
+    print!("{}", Disassembly::new(&rom).listing(style));
+}
+
+/// Prints the control-flow graph of a ROM as a text report or Graphviz DOT
+fn analyze(args: &[String]) {
+    let Some(rom_path) = args.get(2) else {
+        usage(&args[0]);
+    };
+    let rom = read_rom(rom_path);
+    let analysis = Analysis::new(&rom);
+
+    match get_option(args, "--format", "-f") {
+        Some("text") | None => print!("{}", analysis.report()),
+        Some("dot") => print!("{}", analysis.dot()),
+        Some(other) => {
+            eprintln!("Invalid format: {}. Available options: text, dot", other);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Reports where two trace files, or two runs of a ROM in different compatibility modes, differ
+fn trace_diff(args: &[String]) {
+    let context = get_count(args, "--context", DEFAULT_DIFF_CONTEXT as u64) as usize;
+
+    let divergence = match get_option(args, "--rom", "") {
+        Some(rom_path) => {
+            let compatibilities = [
+                get_compatibility_option(args, "--compatibility-a", "-a")
+                    .unwrap_or(Compatibility::Cosmac),
+                get_compatibility_option(args, "--compatibility-b", "-b")
+                    .unwrap_or(Compatibility::Chip48),
+            ];
+            let machines = compatibilities.map(|compatibility| {
+                let mut chip8 = Chip8::new(NullDisplay::new(WIDTH, HEIGHT), compatibility);
+                chip8.load_rom(rom_path).unwrap_or_else(|e| {
+                    eprintln!("{}", e);
                     std::process::exit(1);
-                }
+                });
+                chip8.set_compatibility(compatibility);
+                chip8
+            });
+            let steps = get_count(args, "--steps", DEFAULT_DIFF_STEPS);
+            diff_runs(
+                machines,
+                compatibilities.map(|compatibility| compatibility.to_string()),
+                steps,
+                context,
+            )
+        }
+        None => {
+            let (Some(a), Some(b)) = (args.get(2), args.get(3)) else {
+                usage(&args[0]);
             };
+            diff_files([a, b], context).unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            })
+        }
+    };
+
+    match divergence {
+        Some(divergence) => {
+            print!("{}", divergence);
+            std::process::exit(1);
+        }
+        None => println!("No difference found"),
+    }
+}
+
+/// Reads a raw ROM for the static tools, exiting if it doesn't fit in memory
+fn read_rom(rom_path: &str) -> Vec<u8> {
+    let rom = std::fs::read(rom_path).unwrap_or_else(|e| {
+        eprintln!("Failed to read ROM file: {}", e);
+        std::process::exit(1);
+    });
+    if rom.len() > 0x1000 - ENTRY_POINT as usize {
+        eprintln!("ROM is too large: {} bytes", rom.len());
+        std::process::exit(1);
+    }
+    rom
+}
+
+fn validate_args(args: &[String]) {
+    if args.len() < 2 {
+        usage(&args[0]);
+    }
+}
+
+fn usage(program: &str) -> ! {
+    eprintln!(
+        "Usage: {0} <rom_path> [--compatibility, -c <cosmac|chip48>] [--display, -d <window|terminal|headless|null>] [--frames <count>] [--snapshot <ppm_path>] [--snapshot-size <width>x<height>] [--cycles-per-frame <count>] [--load-state <path>] [--rewind <seconds>] [--record <movie>] [--replay <movie>] [--debug] [--gdb <port>] [--trace <path>] [--trace-format <text|jsonl>] [--trace-pc <start-end>] [--trace-cycles <start-end>] [--profile] [--coverage <lcov_path>] [--warn-self-modifying] [--wav <path>] [--tone <hz>] [--waveform <square|triangle|sawtooth|sine>] [--volume <percent>] [--scale <factor>] [--scaler <nearest|epx|scanlines|crt>] [--no-grid] [--grid-color <RRGGBB>] [--palette <vip|lcd|amber|high-contrast|path>] [--phosphor <fade|or>[:frames]] [--pixel-on <RRGGBB>] [--pixel-off <RRGGBB>] [--pixel-plane2 <RRGGBB>] [--pixel-both <RRGGBB>]\n       {0} analyze <rom_path> [--format, -f <text|dot>]\n       {0} asm <source_path> --output, -o <rom_path> [--listing, -l <path>]\n       {0} coverage <rom_path> <lcov_path>... [--output, -o <lcov_path>]\n       {0} disasm <rom_path> [--style, -s <octo|cowgod>]\n       {0} trace-diff <trace_a> <trace_b> [--context <count>]\n       {0} trace-diff --rom <rom_path> [--compatibility-a, -a <cosmac|chip48>] [--compatibility-b, -b <cosmac|chip48>] [--steps <count>] [--context <count>]",
+        program
+    );
+    std::process::exit(1);
+}
+
+fn get_compatibility(args: &[String]) -> Option<Compatibility> {
+    get_compatibility_option(args, "--compatibility", "-c")
+}
+
+fn get_compatibility_option(args: &[String], long: &str, short: &str) -> Option<Compatibility> {
+    match get_option(args, long, short) {
+        Some("cosmac") => Some(Compatibility::Cosmac),
+        Some("chip48") => Some(Compatibility::Chip48),
+        None => None,
+        Some(other) => {
+            eprintln!(
+                "Invalid compatibility mode: {}. Available options: cosmac, chip48",
+                other
+            );
+            std::process::exit(1);
+        }
+    }
+}
+
+fn get_display(args: &[String]) -> Box<dyn Display> {
+    match get_option(args, "--display", "-d") {
+        Some("window") | None => match MinifbDisplay::new(WIDTH, HEIGHT, get_render_config(args)) {
+            Ok(display) => Box::new(display),
+            Err(err) => {
+                eprintln!("Failed to open window: {}", err);
+                std::process::exit(1);
+            }
+        },
+        Some("terminal") => Box::new(TerminalDisplay::new(WIDTH, HEIGHT)),
+        Some("headless") => {
+            let display = HeadlessDisplay::new(WIDTH, HEIGHT, get_frames(args));
+            match get_snapshot(args) {
+                Some(snapshot) => Box::new(display.with_snapshot(snapshot)),
+                None => Box::new(display),
+            }
+        }
+        Some("null") => Box::new(NullDisplay::new(WIDTH, HEIGHT)),
+        Some(other) => {
+            eprintln!(
+                "Invalid display: {}. Available options: window, terminal, headless, null",
+                other
+            );
+            std::process::exit(1);
+        }
+    }
+}
+
+fn get_render_config(args: &[String]) -> RenderConfig {
+    let default = RenderConfig::default();
+
+    RenderConfig {
+        scaling_factor: match get_option(args, "--scale", "") {
+            Some(scale) => scale.parse().unwrap_or_else(|_| {
+                eprintln!("Invalid scaling factor: {}", scale);
+                std::process::exit(1);
+            }),
+            None => default.scaling_factor,
+        },
+        scaler: match get_option(args, "--scaler", "") {
+            Some("nearest") | None => Scaler::Nearest,
+            Some("epx") => Scaler::Epx,
+            Some("scanlines") => Scaler::Scanlines,
+            Some("crt") => Scaler::Crt,
+            Some(other) => {
+                eprintln!(
+                    "Invalid scaler: {}. Available options: nearest, epx, scanlines, crt",
+                    other
+                );
+                std::process::exit(1);
+            }
+        },
+        grid: !args.iter().any(|arg| arg == "--no-grid"),
+        grid_color: get_color(args, "--grid-color").unwrap_or(default.grid_color),
+        palette: get_palette(args).unwrap_or(default.palette),
+    }
+}
+
+/// Image of the final headless frame, letterboxed into `--snapshot-size` when given
+fn get_snapshot(args: &[String]) -> Option<Snapshot> {
+    let path = get_option(args, "--snapshot", "")?;
+    let renderer = Renderer::new(WIDTH, HEIGHT, get_render_config(args)).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+    let window_size = get_option(args, "--snapshot-size", "").map(|size| {
+        let parsed = size
+            .split_once('x')
+            .and_then(|(width, height)| Some((width.parse().ok()?, height.parse().ok()?)));
+        match parsed {
+            Some((width, height)) if width > 0 && height > 0 => (width, height),
+            _ => {
+                eprintln!("Invalid snapshot size: {}. Expected <width>x<height>", size);
+                std::process::exit(1);
+            }
+        }
+    });
+
+    Some(Snapshot {
+        path: path.to_string(),
+        renderer,
+        window_size,
+    })
+}
+
+/// Palette from `--palette`, a preset name or a palette file, with any single
+/// colours given on the command line replacing its own
+fn get_palette(args: &[String]) -> Option<Palette> {
+    let mut palette = get_option(args, "--palette", "").map(|palette| {
+        Palette::preset(palette).unwrap_or_else(|| {
+            Palette::load_file(palette).unwrap_or_else(|e| {
+                eprintln!("{}. Available presets: {}", e, Palette::preset_names());
+                std::process::exit(1);
+            })
+        })
+    });
+
+    let colors = [
+        "--pixel-off",
+        "--pixel-on",
+        "--pixel-plane2",
+        "--pixel-both",
+    ];
+    for (index, name) in colors.iter().enumerate() {
+        if let Some(color) = get_color(args, name) {
+            palette.get_or_insert_with(Palette::default).0[index] = color;
         }
     }
+    palette
+}
 
-    Compatibility::Cosmac
+/// Persistence filter from `--phosphor`, otherwise from the ROM's own
+/// `<rom_path>.phosphor` file holding the same `<fade|or>[:frames]` setting
+fn get_phosphor(args: &[String], rom_path: &str) -> Option<PhosphorConfig> {
+    let setting = match get_option(args, "--phosphor", "") {
+        Some(setting) => setting.to_string(),
+        None => std::fs::read_to_string(format!("{}.phosphor", rom_path)).ok()?,
+    };
+    match PhosphorConfig::parse(&setting) {
+        Ok(config) => Some(config),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Parses an `RRGGBB` colour, optionally prefixed with `#` or `0x`
+fn get_color(args: &[String], name: &str) -> Option<u32> {
+    get_option(args, name, "").map(|color| {
+        let hex = color
+            .strip_prefix('#')
+            .or_else(|| color.strip_prefix("0x"))
+            .unwrap_or(color);
+        match u32::from_str_radix(hex, 16) {
+            Ok(rgb) if hex.len() == 6 => rgb,
+            _ => {
+                eprintln!("Invalid colour for {}: {}", name, color);
+                std::process::exit(1);
+            }
+        }
+    })
+}
+
+fn get_frames(args: &[String]) -> Option<u64> {
+    get_option(args, "--frames", "").map(|frames| match frames.parse() {
+        Ok(frames) => frames,
+        Err(_) => {
+            eprintln!("Invalid frame count: {}", frames);
+            std::process::exit(1);
+        }
+    })
+}
+
+fn get_cycles_per_frame(args: &[String]) -> Option<u32> {
+    get_option(args, "--cycles-per-frame", "").map(|cycles| {
+        cycles.parse().unwrap_or_else(|_| {
+            eprintln!("Invalid cycles per frame: {}", cycles);
+            std::process::exit(1);
+        })
+    })
+}
+
+fn get_tracer(args: &[String], path: &str) -> Tracer {
+    let format = match get_option(args, "--trace-format", "") {
+        Some("text") | None => TraceFormat::Text,
+        Some("jsonl") => TraceFormat::JsonLines,
+        Some(other) => {
+            eprintln!(
+                "Invalid trace format: {}. Available options: text, jsonl",
+                other
+            );
+            std::process::exit(1);
+        }
+    };
+    let addresses = get_range(args, "--trace-pc", 0, 0xfff);
+    let cycles = get_range(args, "--trace-cycles", 0, u64::MAX);
+
+    Tracer::create(path, format, addresses, cycles).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    })
+}
+
+/// Parses an inclusive `start-end` range of decimal or 0x prefixed numbers.
+/// Either bound may be left out, `--trace-cycles 1000-` traces from cycle 1000 on.
+fn get_range<T>(args: &[String], name: &str, min: T, max: T) -> std::ops::RangeInclusive<T>
+where
+    T: TryFrom<u64> + Copy,
+{
+    let Some(range) = get_option(args, name, "") else {
+        return min..=max;
+    };
+    let bound = |bound: &str, default: T| {
+        if bound.is_empty() {
+            return Some(default);
+        }
+        match bound.strip_prefix("0x") {
+            Some(hex) => u64::from_str_radix(hex, 16).ok(),
+            None => bound.parse().ok(),
+        }
+        .and_then(|value| T::try_from(value).ok())
+    };
+
+    match range
+        .split_once('-')
+        .and_then(|(start, end)| Some(bound(start, min)?..=bound(end, max)?))
+    {
+        Some(range) => range,
+        None => {
+            eprintln!("Invalid range for {}: {}", name, range);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn get_count(args: &[String], name: &str, default: u64) -> u64 {
+    match get_option(args, name, "") {
+        Some(count) => count.parse().unwrap_or_else(|_| {
+            eprintln!("Invalid value for {}: {}", name, count);
+            std::process::exit(1);
+        }),
+        None => default,
+    }
+}
+
+/// The beeper, replaced by XO-CHIP pattern playback once the program loads a pattern
+fn get_voice(args: &[String], sample_rate: u32) -> Box<dyn Voice> {
+    let config = get_beeper_config(args);
+    let volume = config.volume;
+    Box::new(PatternPlayer::new(
+        Beeper::new(config, sample_rate),
+        volume,
+        sample_rate,
+    ))
+}
+
+fn get_beeper_config(args: &[String]) -> BeeperConfig {
+    let mut config = BeeperConfig::default();
+    if let Some(frequency) = get_option(args, "--tone", "") {
+        config.frequency = match frequency.parse() {
+            Ok(frequency) if frequency > 0.0 => frequency,
+            _ => {
+                eprintln!("Invalid tone frequency: {}", frequency);
+                std::process::exit(1);
+            }
+        };
+    }
+    match get_option(args, "--waveform", "") {
+        Some("square") | None => {}
+        Some("triangle") => config.waveform = Waveform::Triangle,
+        Some("sawtooth") => config.waveform = Waveform::Sawtooth,
+        Some("sine") => config.waveform = Waveform::Sine,
+        Some(other) => {
+            eprintln!(
+                "Invalid waveform: {}. Available options: square, triangle, sawtooth, sine",
+                other
+            );
+            std::process::exit(1);
+        }
+    }
+    if let Some(volume) = get_volume(args) {
+        config.volume = volume;
+    }
+    config
+}
+
+fn get_volume(args: &[String]) -> Option<f64> {
+    get_option(args, "--volume", "").map(|volume| match volume.parse::<u8>() {
+        Ok(percent) if percent <= 100 => percent as f64 / 100.0,
+        _ => {
+            eprintln!(
+                "Invalid volume: {}. Expected a percentage from 0 to 100",
+                volume
+            );
+            std::process::exit(1);
+        }
+    })
+}
+
+fn get_wav_writer(path: &str) -> Box<WavWriter> {
+    match WavWriter::create(path, SAMPLE_RATE) {
+        Ok(writer) => Box::new(writer),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn get_gdb_port(args: &[String]) -> Option<u16> {
+    get_option(args, "--gdb", "").map(|port| {
+        port.parse().unwrap_or_else(|_| {
+            eprintln!("Invalid port: {}", port);
+            std::process::exit(1);
+        })
+    })
+}
+
+/// Seconds of gameplay kept for rewinding, 0 disables rewind
+fn get_rewind_seconds(args: &[String]) -> usize {
+    match get_option(args, "--rewind", "") {
+        Some(seconds) => seconds.parse().unwrap_or_else(|_| {
+            eprintln!("Invalid rewind duration: {}", seconds);
+            std::process::exit(1);
+        }),
+        None => DEFAULT_REWIND_SECONDS,
+    }
 }
 
 fn get_rom_path(args: &[String]) -> &str {
     &args[1]
 }
+
+/// Returns the value following `long` or `short`, exiting if the value is missing
+fn get_option<'a>(args: &'a [String], long: &str, short: &str) -> Option<&'a str> {
+    for (i, arg) in args.iter().enumerate() {
+        if arg == long || (!short.is_empty() && arg == short) {
+            return match args.get(i + 1) {
+                Some(value) => Some(value.as_str()),
+                None => {
+                    eprintln!("Missing value for {}", arg);
+                    std::process::exit(1);
+                }
+            };
+        }
+    }
+
+    None
+}